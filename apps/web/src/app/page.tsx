@@ -7,6 +7,22 @@ import { useEffect, useMemo, useRef, useState } from 'react';
 import { BskyAgent } from '@atproto/api';
 import { IocainePowderAI, type RPS } from './ai/IocainePowderAI';
 
+/** Commit encodings this client can reveal under (see rps-match-engine). */
+const COMMIT_VERSIONS = ['v1', 'v2'];
+
+/** Match protocol version sent in HELLO; keep in step with rps_shared_types::PROTOCOL_VERSION. */
+const PROTOCOL_VERSION = 1;
+
+/** First message on every signaling socket; the server answers WELCOME. */
+const HELLO = JSON.stringify({ type: 'HELLO', protocol_version: PROTOCOL_VERSION, client_name: 'rps-web', capabilities: ['commit_v2'] });
+
+/** v2 commits need a 32-byte random nonce (hex); v1 accepts any string. */
+function makeNonce(version: 'v1' | 'v2'): string {
+  if (version === 'v1') return Math.random().toString(36).slice(2);
+  const bytes = crypto.getRandomValues(new Uint8Array(32));
+  return Array.from(bytes, (b) => b.toString(16).padStart(2, '0')).join('');
+}
+
 export default function HomePage() {
   const MODE: 'normal' | 'tournament' = (process.env.NEXT_PUBLIC_MODE === 'tournament') ? 'tournament' : 'normal';
   const [session, setSession] = useState<{ did: string; handle: string } | null>(null);
@@ -36,6 +52,7 @@ export default function HomePage() {
   const processedKeyedRef = useMemo(() => ({ set: new Set<string>() }), []);
   const [auditSeen, setAuditSeen] = useState<Record<string, boolean>>({});
   const [clockSkewMs, setClockSkewMs] = useState<number>(0); // serverNow - localNow
+  const [commitVersion, setCommitVersion] = useState<'v1'|'v2'>('v1'); // negotiated via ticket/ASSIGN
   const findingRef = useRef<boolean>(false);
   const shortDid = (d?: string | null) => d ? ((d.startsWith('did:plc:') ? d.slice(8, 14) : d.slice(0, 6)) + '…') : '-';
   /** Extract the numeric round from a match id like "...-r7-...". */
@@ -134,7 +151,7 @@ export default function HomePage() {
       const rr = await fetch(`${coordBase}/ready_for_round`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ tid: 'demo', round: 1, did: session.did }),
+        body: JSON.stringify({ tid: 'demo', round: 1, did: session.did, commit_versions: COMMIT_VERSIONS }),
       });
       if (!rr.ok) {
         const txt = await rr.text().catch(() => '');
@@ -142,10 +159,12 @@ export default function HomePage() {
         alert('Connect failed: coordinator not reachable (check port 8082)');
         return;
       }
-      const { ticket } = await rr.json();
+      const { ticket, commit_version } = await rr.json();
+      setCommitVersion(commit_version === 'v2' ? 'v2' : 'v1');
       const url = `${wsBase}?ticket=${encodeURIComponent(ticket)}`;
       const socket = new WebSocket(url);
       socket.onopen = () => {
+        socket.send(HELLO);
         socket.send(JSON.stringify({ type: 'READY_FOR_ROUND', tid: 'demo', round: 1 }));
       };
       socket.onmessage = (ev) => {
@@ -168,6 +187,7 @@ export default function HomePage() {
           }
           if (msg.type === 'ASSIGN') {
             setMatchId(msg.match_id);
+            if (msg.commit_version) setCommitVersion(msg.commit_version === 'v2' ? 'v2' : 'v1');
             if (msg.role) setRole(msg.role);
             if (msg.peer && typeof msg.peer === 'object') {
               if (msg.peer.did) setPeerDid(msg.peer.did);
@@ -205,13 +225,32 @@ export default function HomePage() {
     }
   };
 
-  /** Tournament mode: register this user and then poll for assignment. */
+  /**
+   * Tournament mode: write a registration record to the user's repo, then
+   * register handle and commit versions once the coordinator has seen the
+   * record (it 403s until then), and poll for assignment.
+   */
   const registerEntrant = async () => {
     if (!session) return;
-    const res = await fetch(`${coordBase}/register`, { method: 'POST', headers: { 'Content-Type': 'application/json' }, body: JSON.stringify({ tid: 'demo', did: session.did, handle: session.handle }) });
-    if (!res.ok) { setLog(prev => ["register failed", ...prev]); return; }
-    setLog(prev => ["registered", ...prev]);
-    pollAssignment();
+    try {
+      const record = { $type: 'zone.peace.rps.registration', tid: 'demo', did: session.did, createdAt: new Date().toISOString() };
+      await agent.com.atproto.repo.createRecord({ repo: session.did, collection: 'zone.peace.rps.registration', record });
+    } catch (e) {
+      console.error(e);
+      setLog(prev => ["registration record failed", ...prev]);
+      return;
+    }
+    for (let i = 0; i < 30; i++) {
+      const res = await fetch(`${coordBase}/register`, { method: 'POST', headers: { 'Content-Type': 'application/json' }, body: JSON.stringify({ tid: 'demo', did: session.did, handle: session.handle, commit_versions: COMMIT_VERSIONS }) });
+      if (res.ok) {
+        setLog(prev => ["registered", ...prev]);
+        pollAssignment();
+        return;
+      }
+      if (res.status !== 403) break;
+      await new Promise(r => setTimeout(r, 1000));
+    }
+    setLog(prev => ["register failed", ...prev]);
   };
 
   /** Poll the coordinator for an ASSIGN entry, then connect via ticket. */
@@ -237,11 +276,13 @@ export default function HomePage() {
       return;
     }
     setMatchId(assign.match_id); setRole(assign.role); setPeerDid(assign?.peer?.did || null); setPeerHandle(assign?.peer?.handle || null);
+    setCommitVersion(assign.commit_version === 'v2' ? 'v2' : 'v1');
     const myRole: 'P1'|'P2' = assign.role;
     const url = `${wsBase}?ticket=${encodeURIComponent(assign.ticket)}`;
     const socket = new WebSocket(url);
     socket.onopen = async () => {
       if (aiModeRef.current) { try { socket.close(1000, 'ai_mode'); } catch {} return; }
+      socket.send(HELLO);
       const peer = new RTCPeerConnection({ iceServers: [{ urls: 'stun:stun.l.google.com:19302' }] });
       peer.onicecandidate = (e) => { if (e.candidate) socket.send(JSON.stringify({ type: 'ICE', match_id: assign.match_id, candidate: JSON.stringify(e.candidate) })); };
       peer.ondatachannel = (e) => setDc(e.channel);
@@ -366,7 +407,7 @@ export default function HomePage() {
       const res = await fetch(`${coordBase}/queue_ready`, {
         method: 'POST',
         headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ tid: 'demo', did: session.did, handle: session.handle })
+        body: JSON.stringify({ tid: 'demo', did: session.did, handle: session.handle, commit_versions: COMMIT_VERSIONS })
       });
       const j = await res.json();
       if (j.status === 'ASSIGN') { assign = j; break; }
@@ -497,7 +538,7 @@ export default function HomePage() {
       return;
     }
     if (!ws || !matchId || !turn) return;
-    const nonce = Math.random().toString(36).slice(2);
+    const nonce = makeNonce(commitVersion);
     const payload = { type: 'REVEAL', match_id: matchId, turn, move_: move, nonce };
     ws.send(JSON.stringify(payload));
     setLastMove(move);
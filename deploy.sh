@@ -20,6 +20,13 @@ else
   echo "TICKET_SECRET exists; using latest version"
 fi
 
+echo "Creating INTERNAL_TOKEN (if not exists)..."
+if ! gcloud secrets describe INTERNAL_TOKEN >/dev/null 2>&1; then
+  head -c 32 /dev/urandom | base64 | gcloud secrets create INTERNAL_TOKEN --data-file=-
+else
+  echo "INTERNAL_TOKEN exists; using latest version"
+fi
+
 echo "Building and pushing images via Cloud Build..."
 gcloud builds submit --config cloudbuild.yaml --substitutions _REGION="$REGION",_REPO="$REPO"
 
@@ -31,7 +38,8 @@ WEB_IMG="$REGION-docker.pkg.dev/$PROJECT_ID/$REPO/web:latest"
 
 echo "Deploying fairness..."
 gcloud run deploy rps-fairness --image "$FAIR_IMG" --region "$REGION" --allow-unauthenticated \
-  --port 8084 --concurrency 80 --cpu 1 --memory 512Mi
+  --port 8084 --concurrency 80 --cpu 1 --memory 512Mi \
+  --set-secrets INTERNAL_TOKEN=INTERNAL_TOKEN:latest
 
 echo "Deploying match-engine..."
 gcloud run deploy rps-match-engine --image "$MATCH_IMG" --region "$REGION" --allow-unauthenticated \
@@ -40,7 +48,8 @@ gcloud run deploy rps-match-engine --image "$MATCH_IMG" --region "$REGION" --all
 echo "Deploying coordinator..."
 gcloud run deploy rps-coordinator --image "$COORD_IMG" --region "$REGION" --allow-unauthenticated \
   --port 8082 --concurrency 200 --cpu 1 --memory 512Mi \
-  --set-secrets TICKET_SECRET=TICKET_SECRET:latest
+  --set-secrets TICKET_SECRET=TICKET_SECRET:latest,INTERNAL_TOKEN=INTERNAL_TOKEN:latest \
+  --set-env-vars JETSTREAM_URL="${JETSTREAM_URL:-wss://jetstream2.us-east.bsky.network/subscribe}"
 
 FAIR_URL=$(gcloud run services describe rps-fairness --region "$REGION" --format='value(status.url)')
 MATCH_URL=$(gcloud run services describe rps-match-engine --region "$REGION" --format='value(status.url)')
@@ -48,7 +57,7 @@ MATCH_URL=$(gcloud run services describe rps-match-engine --region "$REGION" --f
 echo "Deploying signaling..."
 gcloud run deploy rps-signaling --image "$SIGNAL_IMG" --region "$REGION" --allow-unauthenticated \
   --port 8081 --concurrency 50 --cpu 1 --memory 1Gi \
-  --set-secrets TICKET_SECRET=TICKET_SECRET:latest \
+  --set-secrets TICKET_SECRET=TICKET_SECRET:latest,INTERNAL_TOKEN=INTERNAL_TOKEN:latest \
   --set-env-vars TURN_DEADLINE_MS=30000,MATCH_ENGINE_HTTP="$MATCH_URL",FAIRNESS_HTTP="$FAIR_URL"
 
 SIGNAL_URL=$(gcloud run services describe rps-signaling --region "$REGION" --format='value(status.url)')
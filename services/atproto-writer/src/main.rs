@@ -1,16 +1,51 @@
-use axum::{routing::{get, post}, Router, Json};
+use axum::{routing::{get, post}, Router, Json, extract::Path};
+use axum::http::{header::AUTHORIZATION, HeaderMap, StatusCode};
+use once_cell::sync::Lazy;
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use serde::{Deserialize, Serialize};
+use rps_shared_types::internal;
+use rps_shared_types::lexicon::{LexiconRecord, MatchDigest, RoundAnchor};
 
-/// Service entrypoint: accepts round anchors and logs them.
+mod outbox;
+mod pds;
+mod posts;
+
+use outbox::{Outbox, Status};
+use pds::PdsClient;
+use posts::{Announcement, Templates};
+
+// PDS client from ATPROTO_* env; None means records are only logged
+static PDS: Lazy<Option<PdsClient>> = Lazy::new(PdsClient::from_env);
+// durable write queue at OUTBOX_PATH, only opened when there is a PDS to deliver to
+static OUTBOX: Lazy<Option<Outbox>> = Lazy::new(|| {
+    PDS.as_ref()?;
+    let path = std::env::var("OUTBOX_PATH").unwrap_or_else(|_| "atproto-outbox.sqlite3".to_string());
+    Some(Outbox::open(&path).unwrap_or_else(|e| panic!("cannot open outbox {path}: {e}")))
+});
+// post templates from POST_TEMPLATE_* / BRACKET_URL_BASE / POST_HASHTAG
+static TEMPLATES: Lazy<Templates> = Lazy::new(Templates::from_env);
+
+/// Service entrypoint: accepts round anchors, match digests and tournament
+/// announcement posts into the outbox, which delivers them to the configured PDS.
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt().with_env_filter("info").init();
+    match (PDS.as_ref(), OUTBOX.as_ref()) {
+        (Some(p), Some(o)) => {
+            tracing::info!(pds = %p.base(), "writing records to PDS");
+            tokio::spawn(outbox::run(o, p));
+        }
+        _ => tracing::warn!("no ATPROTO credentials set; records are only logged"),
+    }
 
     let app = Router::new()
         .route("/healthz", get(|| async { "ok" }))
-        .route("/round_anchor", post(round_anchor));
+        .route("/round_anchor", post(round_anchor))
+        .route("/match_digest", post(match_digest))
+        .route("/posts", post(create_post))
+        .route("/posts/opt_in", post(opt_in))
+        .route("/status/:id", get(status));
 
     let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8080);
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
@@ -19,21 +54,170 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+#[derive(Debug, Default, Serialize)]
+struct WriteResp {
+    ok: bool,
+    /// Outbox entry, absent when records are only logged.
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    status: Option<Status>,
+    /// The same record was already queued; `status` is the existing entry.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    duplicate: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// The rendered record, for dry runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    record: Option<serde_json::Value>,
+    /// Why nothing was written, when that is not an error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    skipped: Option<String>,
+}
+
+/// Queues a round anchor as a `zone.peace.rps.roundAnchor` record keyed by
+/// (tid, round). Without a PDS configured it only logs. Internal only.
+async fn round_anchor(headers: HeaderMap, Json(body): Json<serde_json::Value>) -> (StatusCode, Json<WriteResp>) {
+    if !internal_call(&headers) { return unauthorized(); }
+    let req = match RoundAnchor::from_record(body) {
+        Ok(req) => req,
+        Err(err) => return failed(StatusCode::BAD_REQUEST, err.to_string()),
+    };
+    tracing::info!(tid = %req.tid, round = %req.round, alive_root = %req.alive_root, pairing_seed = %req.pairing_seed, drand_round = req.drand_round.unwrap_or(0), merkle_root = %req.merkle_root, posted_at = %req.posted_at, "roundAnchor received");
+    write(RoundAnchor::NSID, outbox::rkey(RoundAnchor::NSID, &[&req.tid, &req.round.to_string()]), req.to_record())
+}
+
+/// Queues a `zone.peace.rps.matchDigest` keyed by match id, with `logUri`
+/// pointing at the signed transcript. Internal only.
+async fn match_digest(headers: HeaderMap, Json(body): Json<serde_json::Value>) -> (StatusCode, Json<WriteResp>) {
+    if !internal_call(&headers) { return unauthorized(); }
+    let req = match MatchDigest::from_record(body) {
+        Ok(req) => req,
+        Err(err) => return failed(StatusCode::BAD_REQUEST, err.to_string()),
+    };
+    tracing::info!(mid = %req.mid, winner = %req.winner, turns = req.turns, commits_root = %req.commits_root, "matchDigest received");
+    write(MatchDigest::NSID, outbox::rkey(MatchDigest::NSID, &[&req.mid]), req.to_record())
+}
+
 #[derive(Debug, Deserialize)]
-struct RoundAnchorReq {
-    tid: String,
-    round: u32,
-    aliveRoot: String,
-    pairingSeed: String,
-    merkleRoot: String,
-    postedAt: String,
-}
-
-#[derive(Debug, Serialize)]
-struct RoundAnchorResp { ok: bool }
-
-/// Stub endpoint for recording a round anchor. Currently logs and returns ok.
-async fn round_anchor(Json(req): Json<RoundAnchorReq>) -> Json<RoundAnchorResp> {
-    tracing::info!(tid = %req.tid, round = %req.round, alive_root = %req.aliveRoot, pairing_seed = %req.pairingSeed, merkle_root = %req.merkleRoot, posted_at = %req.postedAt, "roundAnchor received");
-    Json(RoundAnchorResp { ok: true })
+struct PostReq {
+    #[serde(flatten)]
+    announcement: Announcement,
+    /// Render and return the record without posting (also `POSTS_DRY_RUN=1`).
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Composes a tournament announcement (`kind`: opening, round_start, finals or
+/// champion) as an `app.bsky.feed.post` and queues it, once per announcement,
+/// if the tournament opted in. Internal only.
+async fn create_post(headers: HeaderMap, Json(req): Json<PostReq>) -> (StatusCode, Json<WriteResp>) {
+    if !internal_call(&headers) { return unauthorized(); }
+    let a = &req.announcement;
+    let dry_run = req.dry_run || std::env::var("POSTS_DRY_RUN").is_ok_and(|v| v == "1" || v == "true");
+    if !dry_run {
+        match posts::opted_in(OUTBOX.as_ref(), a.tid()) {
+            Ok(true) => {}
+            Ok(false) => return (StatusCode::OK, Json(WriteResp { ok: true, skipped: Some(format!("{} has not opted in to posts", a.tid())), ..Default::default() })),
+            Err(err) => return failed(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+        }
+    }
+    let record = match TEMPLATES.compose(a, &chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)) {
+        Ok(r) => r,
+        Err(err) => return failed(StatusCode::BAD_REQUEST, err.to_string()),
+    };
+    tracing::info!(key = %a.key(), dry_run, text = %record["text"], "post composed");
+    if dry_run {
+        return (StatusCode::OK, Json(WriteResp { ok: true, record: Some(record), ..Default::default() }));
+    }
+    let Some(outbox) = OUTBOX.as_ref() else { return write(posts::NSID, String::new(), record) };
+    let mint = || posts::tid_rkey(outbox::now_ms() as u64 * 1000, (std::process::id() & 0x3ff) as u16);
+    match outbox.rkey_for(&format!("{}/{}", posts::NSID, a.key()), mint) {
+        Ok(rkey) => write(posts::NSID, rkey, record),
+        Err(err) => failed(StatusCode::INTERNAL_SERVER_ERROR, err.to_string()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct OptInReq { tid: String, #[serde(default = "yes")] enabled: bool }
+
+fn yes() -> bool { true }
+
+/// Turns announcement posts on or off for a tournament; the choice is kept in
+/// the outbox database. Internal only.
+async fn opt_in(headers: HeaderMap, Json(req): Json<OptInReq>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    if !internal_call(&headers) { return Err((StatusCode::UNAUTHORIZED, "internal route".into())); }
+    posts::set_opt_in(OUTBOX.as_ref(), &req.tid, req.enabled).map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    tracing::info!(tid = %req.tid, enabled = req.enabled, "posts opt-in changed");
+    Ok(Json(serde_json::json!({ "ok": true, "tid": req.tid, "enabled": req.enabled })))
+}
+
+/// Whether a request carries the internal service token. Records are written
+/// under the service's account, so only the other services may queue them.
+fn internal_call(headers: &HeaderMap) -> bool {
+    internal::authorized(headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()))
+}
+
+fn unauthorized() -> (StatusCode, Json<WriteResp>) {
+    failed(StatusCode::UNAUTHORIZED, "internal route".to_string())
+}
+
+fn failed(code: StatusCode, error: String) -> (StatusCode, Json<WriteResp>) {
+    (code, Json(WriteResp { ok: false, error: Some(error), ..Default::default() }))
+}
+
+/// Stores the record in the outbox and answers 202 with its entry (200 if it
+/// was already queued); delivery happens in the background (see `GET /status/{id}`).
+fn write(collection: &str, rkey: String, record: serde_json::Value) -> (StatusCode, Json<WriteResp>) {
+    let Some(outbox) = OUTBOX.as_ref() else {
+        return (StatusCode::OK, Json(WriteResp { ok: true, ..Default::default() }));
+    };
+    let queued = outbox.enqueue(collection, &rkey, &record, outbox::now_ms())
+        .and_then(|(id, new)| Ok((outbox.status(id)?, new)));
+    match queued {
+        Ok((status, new)) => {
+            let code = if new { StatusCode::ACCEPTED } else { StatusCode::OK };
+            (code, Json(WriteResp { ok: true, status, duplicate: !new, ..Default::default() }))
+        }
+        Err(err) => {
+            tracing::error!(%err, collection, "outbox write failed");
+            failed(StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+        }
+    }
+}
+
+/// Reports whether an outbox entry is pending, delivered (with `uri`/`cid`) or failed.
+async fn status(Path(id): Path<i64>) -> Result<Json<Status>, (StatusCode, String)> {
+    let outbox = OUTBOX.as_ref().ok_or((StatusCode::NOT_FOUND, "no outbox: records are only logged".into()))?;
+    match outbox.status(id) {
+        Ok(Some(s)) => Ok(Json(s)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, "unknown id".into())),
+        Err(err) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn record_routes_need_the_internal_token() {
+        std::env::set_var(internal::ENV, "test-token");
+        let mut forged = HeaderMap::new();
+        forged.insert(AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        for headers in [HeaderMap::new(), forged] {
+            let (code, _) = round_anchor(headers.clone(), Json(serde_json::json!({}))).await;
+            assert_eq!(code, StatusCode::UNAUTHORIZED);
+            let (code, _) = match_digest(headers.clone(), Json(serde_json::json!({}))).await;
+            assert_eq!(code, StatusCode::UNAUTHORIZED);
+            let post = PostReq { announcement: Announcement::Opening { tid: "t".into() }, dry_run: true };
+            let (code, _) = create_post(headers.clone(), Json(post)).await;
+            assert_eq!(code, StatusCode::UNAUTHORIZED);
+            let err = opt_in(headers, Json(OptInReq { tid: "t".into(), enabled: true })).await.unwrap_err();
+            assert_eq!(err.0, StatusCode::UNAUTHORIZED);
+        }
+        let mut ok = HeaderMap::new();
+        ok.insert(AUTHORIZATION, "Bearer test-token".parse().unwrap());
+        // past the token check, an empty body fails lexicon validation instead
+        let (code, _) = round_anchor(ok, Json(serde_json::json!({}))).await;
+        assert_eq!(code, StatusCode::BAD_REQUEST);
+    }
 }
@@ -0,0 +1,399 @@
+//! Durable outbox for record writes.
+//!
+//! Writes are stored in SQLite (`OUTBOX_PATH`) before they are acknowledged and
+//! delivered by a background worker with `putRecord`. Each record has a
+//! deterministic key (see [`rkey`]), so enqueueing the same record twice returns
+//! the existing entry and a retried delivery replaces rather than duplicates.
+//! Failed deliveries back off exponentially; writes are paced by a token bucket
+//! sized to the PDS's write limits. Entries survive restarts and are resumed.
+
+use crate::pds::PdsClient;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS outbox (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    collection TEXT NOT NULL,
+    rkey TEXT NOT NULL,
+    record TEXT NOT NULL,
+    state TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_ms INTEGER NOT NULL,
+    last_error TEXT,
+    uri TEXT,
+    cid TEXT,
+    created_ms INTEGER NOT NULL,
+    updated_ms INTEGER NOT NULL,
+    UNIQUE (collection, rkey)
+);
+CREATE INDEX IF NOT EXISTS outbox_due ON outbox (state, next_attempt_ms);
+CREATE TABLE IF NOT EXISTS rkeys (
+    key TEXT PRIMARY KEY,
+    rkey TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS post_opt_ins (
+    tid TEXT PRIMARY KEY,
+    enabled INTEGER NOT NULL
+);
+";
+
+/// Record key for a record identified by `parts` (e.g. tid and round): the
+/// first 32 hex chars of `sha256(collection || 0x00 || part || 0x00 || ...)`.
+pub fn rkey(collection: &str, parts: &[&str]) -> String {
+    let mut h = Sha256::new().chain_update(collection);
+    for p in parts { h = h.chain_update([0u8]).chain_update(p); }
+    hex::encode(h.finalize())[..32].to_string()
+}
+
+pub fn now_ms() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+/// Where an entry stands; `pending` until it lands or gives up.
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Status {
+    pub id: i64,
+    pub collection: String,
+    pub rkey: String,
+    pub state: String,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+}
+
+/// An entry due for delivery.
+#[derive(Debug, Clone)]
+pub struct Due {
+    pub id: i64,
+    pub collection: String,
+    pub rkey: String,
+    pub record: serde_json::Value,
+    pub attempts: u32,
+}
+
+/// Retry policy: `base * 2^attempts`, capped, for at most `max_attempts` tries.
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff { pub base_ms: i64, pub max_ms: i64, pub max_attempts: u32 }
+
+impl Backoff {
+    pub fn from_env() -> Self {
+        let var = |k: &str, d: i64| std::env::var(k).ok().and_then(|s| s.parse().ok()).unwrap_or(d);
+        Backoff { base_ms: var("OUTBOX_BACKOFF_MS", 1_000), max_ms: var("OUTBOX_MAX_BACKOFF_MS", 600_000), max_attempts: var("OUTBOX_MAX_ATTEMPTS", 10) as u32 }
+    }
+
+    /// Delay before the next try after `attempts` failed ones.
+    pub fn delay_ms(&self, attempts: u32) -> i64 {
+        self.base_ms.saturating_mul(1i64 << attempts.saturating_sub(1).min(30)).min(self.max_ms)
+    }
+}
+
+/// Token bucket: `burst` writes at once, refilled at `per_hour`.
+#[derive(Debug)]
+pub struct RateLimit { capacity: f64, tokens: f64, per_ms: f64, last_ms: i64 }
+
+impl RateLimit {
+    pub fn new(per_hour: u32, burst: u32, now: i64) -> Self {
+        RateLimit { capacity: burst.max(1) as f64, tokens: burst.max(1) as f64, per_ms: per_hour as f64 / 3_600_000.0, last_ms: now }
+    }
+
+    /// `OUTBOX_RATE_PER_HOUR` (1500, under Bluesky's 5000 points/hour at 3 per write) and `OUTBOX_BURST` (30).
+    pub fn from_env() -> Self {
+        let var = |k: &str, d: u32| std::env::var(k).ok().and_then(|s| s.parse().ok()).unwrap_or(d);
+        RateLimit::new(var("OUTBOX_RATE_PER_HOUR", 1_500), var("OUTBOX_BURST", 30), now_ms())
+    }
+
+    /// Takes a token, or returns how many ms until one is available.
+    pub fn try_take(&mut self, now: i64) -> Result<(), i64> {
+        self.tokens = (self.tokens + (now - self.last_ms).max(0) as f64 * self.per_ms).min(self.capacity);
+        self.last_ms = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        if self.per_ms <= 0.0 { return Err(i64::MAX); }
+        Err(((1.0 - self.tokens) / self.per_ms).ceil() as i64)
+    }
+}
+
+pub struct Outbox {
+    db: Mutex<Connection>,
+    wake: Notify,
+}
+
+impl Outbox {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let db = Connection::open(path)?;
+        db.execute_batch("PRAGMA journal_mode = WAL;")?;
+        db.execute_batch(SCHEMA)?;
+        Ok(Outbox { db: Mutex::new(db), wake: Notify::new() })
+    }
+
+    /// Stores a write and wakes the worker. Returns the entry's id and whether
+    /// it is new; an existing (collection, rkey) is left as it is.
+    pub fn enqueue(&self, collection: &str, rkey: &str, record: &serde_json::Value, now: i64) -> rusqlite::Result<(i64, bool)> {
+        let db = self.db.lock().unwrap();
+        let inserted = db.execute(
+            "INSERT OR IGNORE INTO outbox (collection, rkey, record, state, next_attempt_ms, created_ms, updated_ms) VALUES (?1, ?2, ?3, 'pending', ?4, ?4, ?4)",
+            params![collection, rkey, record.to_string(), now],
+        )?;
+        let id = db.query_row("SELECT id FROM outbox WHERE collection = ?1 AND rkey = ?2", params![collection, rkey], |r| r.get(0))?;
+        drop(db);
+        if inserted > 0 { self.wake.notify_one(); }
+        Ok((id, inserted > 0))
+    }
+
+    /// The record key assigned to `key`, minting one with `mint` the first time.
+    /// For collections keyed by TID, where the key cannot be derived from the record.
+    pub fn rkey_for(&self, key: &str, mint: impl FnOnce() -> String) -> rusqlite::Result<String> {
+        let db = self.db.lock().unwrap();
+        db.execute("INSERT OR IGNORE INTO rkeys (key, rkey) VALUES (?1, ?2)", params![key, mint()])?;
+        db.query_row("SELECT rkey FROM rkeys WHERE key = ?1", params![key], |r| r.get(0))
+    }
+
+    /// A tournament's last announcement opt-in choice, if it made one.
+    pub fn opt_in(&self, tid: &str) -> rusqlite::Result<Option<bool>> {
+        self.db.lock().unwrap().query_row("SELECT enabled FROM post_opt_ins WHERE tid = ?1", [tid], |r| r.get(0)).optional()
+    }
+
+    pub fn set_opt_in(&self, tid: &str, enabled: bool) -> rusqlite::Result<()> {
+        self.db.lock().unwrap().execute(
+            "INSERT INTO post_opt_ins (tid, enabled) VALUES (?1, ?2) ON CONFLICT (tid) DO UPDATE SET enabled = ?2",
+            params![tid, enabled],
+        ).map(|_| ())
+    }
+
+    /// Pending entries whose next attempt is due, oldest first.
+    pub fn due(&self, now: i64, limit: usize) -> rusqlite::Result<Vec<Due>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare(
+            "SELECT id, collection, rkey, record, attempts FROM outbox WHERE state = 'pending' AND next_attempt_ms <= ?1 ORDER BY next_attempt_ms, id LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![now, limit as i64], |r| {
+            let record: String = r.get(3)?;
+            Ok(Due { id: r.get(0)?, collection: r.get(1)?, rkey: r.get(2)?, record: serde_json::from_str(&record).unwrap_or_default(), attempts: r.get(4)? })
+        })?;
+        rows.collect()
+    }
+
+    /// Earliest next attempt among pending entries.
+    pub fn next_due(&self) -> rusqlite::Result<Option<i64>> {
+        self.db.lock().unwrap().query_row("SELECT MIN(next_attempt_ms) FROM outbox WHERE state = 'pending'", [], |r| r.get(0))
+    }
+
+    pub fn delivered(&self, id: i64, uri: &str, cid: &str, now: i64) -> rusqlite::Result<()> {
+        self.db.lock().unwrap().execute(
+            "UPDATE outbox SET state = 'delivered', attempts = attempts + 1, uri = ?2, cid = ?3, last_error = NULL, updated_ms = ?4 WHERE id = ?1",
+            params![id, uri, cid, now],
+        ).map(|_| ())
+    }
+
+    /// Records a failed attempt: rescheduled with backoff if `retryable` and
+    /// attempts remain, otherwise `failed` for good.
+    pub fn attempt_failed(&self, id: i64, error: &str, retryable: bool, backoff: &Backoff, now: i64) -> rusqlite::Result<()> {
+        let db = self.db.lock().unwrap();
+        let attempts: u32 = db.query_row("SELECT attempts FROM outbox WHERE id = ?1", [id], |r| r.get(0))?;
+        let attempts = attempts + 1;
+        let (state, next) = if retryable && attempts < backoff.max_attempts { ("pending", now + backoff.delay_ms(attempts)) } else { ("failed", now) };
+        db.execute(
+            "UPDATE outbox SET state = ?2, attempts = ?3, next_attempt_ms = ?4, last_error = ?5, updated_ms = ?6 WHERE id = ?1",
+            params![id, state, attempts, next, error, now],
+        ).map(|_| ())
+    }
+
+    /// Pushes a due entry back without counting an attempt (e.g. rate limited).
+    pub fn defer(&self, id: i64, until: i64) -> rusqlite::Result<()> {
+        self.db.lock().unwrap().execute("UPDATE outbox SET next_attempt_ms = ?2 WHERE id = ?1", params![id, until]).map(|_| ())
+    }
+
+    pub fn status(&self, id: i64) -> rusqlite::Result<Option<Status>> {
+        self.db.lock().unwrap().query_row(
+            "SELECT id, collection, rkey, state, attempts, next_attempt_ms, last_error, uri, cid FROM outbox WHERE id = ?1",
+            [id],
+            |r| {
+                let state: String = r.get(3)?;
+                Ok(Status {
+                    id: r.get(0)?,
+                    collection: r.get(1)?,
+                    rkey: r.get(2)?,
+                    next_attempt_ms: if state == "pending" { Some(r.get(5)?) } else { None },
+                    state,
+                    attempts: r.get(4)?,
+                    last_error: r.get(6)?,
+                    uri: r.get(7)?,
+                    cid: r.get(8)?,
+                })
+            },
+        ).optional()
+    }
+}
+
+/// Delivers what is due now, within the rate limit. Returns how many landed.
+pub async fn deliver_due(outbox: &Outbox, pds: &PdsClient, limit: &mut RateLimit, backoff: &Backoff) -> rusqlite::Result<usize> {
+    let mut landed = 0;
+    for item in outbox.due(now_ms(), 50)? {
+        if let Err(wait) = limit.try_take(now_ms()) {
+            outbox.defer(item.id, now_ms().saturating_add(wait))?;
+            continue;
+        }
+        match pds.put_record(&item.collection, &item.rkey, item.record).await {
+            Ok(r) => {
+                tracing::info!(id = item.id, uri = %r.uri, cid = %r.cid, "record delivered");
+                outbox.delivered(item.id, &r.uri, &r.cid, now_ms())?;
+                landed += 1;
+            }
+            Err(err) => {
+                tracing::warn!(id = item.id, attempts = item.attempts + 1, %err, "record delivery failed");
+                outbox.attempt_failed(item.id, &err.to_string(), err.is_retryable(), backoff, now_ms())?;
+            }
+        }
+    }
+    Ok(landed)
+}
+
+/// Worker loop: delivers due entries, then sleeps until the next one is due,
+/// a new entry arrives, or `OUTBOX_POLL_MS` (1000) passes.
+pub async fn run(outbox: &'static Outbox, pds: &'static PdsClient) {
+    let mut limit = RateLimit::from_env();
+    let backoff = Backoff::from_env();
+    let poll_ms: i64 = std::env::var("OUTBOX_POLL_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(1_000);
+    loop {
+        if let Err(err) = deliver_due(outbox, pds, &mut limit, &backoff).await {
+            tracing::error!(%err, "outbox read failed");
+        }
+        let until_next = outbox.next_due().ok().flatten().map_or(poll_ms, |t| (t - now_ms()).clamp(0, poll_ms));
+        tokio::select! {
+            _ = outbox.wake.notified() => {}
+            _ = tokio::time::sleep(Duration::from_millis(until_next as u64)) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pds::tests::{app_password, mock_pds};
+    use serde_json::json;
+
+    fn outbox() -> Outbox { Outbox::open(":memory:").unwrap() }
+
+    const BACKOFF: Backoff = Backoff { base_ms: 1_000, max_ms: 8_000, max_attempts: 4 };
+
+    #[test]
+    fn rkeys_are_deterministic_and_valid() {
+        let a = rkey("zone.peace.rps.roundAnchor", &["t1", "1"]);
+        assert_eq!(a, rkey("zone.peace.rps.roundAnchor", &["t1", "1"]));
+        assert_ne!(a, rkey("zone.peace.rps.roundAnchor", &["t1", "2"]));
+        assert_ne!(rkey("c", &["ab", "c"]), rkey("c", &["a", "bc"]));
+        assert!(a.len() == 32 && a.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn dedupes_by_collection_and_rkey() {
+        let o = outbox();
+        let (a, new_a) = o.enqueue("c", "k1", &json!({ "tid": "t" }), 0).unwrap();
+        let (b, new_b) = o.enqueue("c", "k1", &json!({ "tid": "other" }), 5).unwrap();
+        let (c, _) = o.enqueue("d", "k1", &json!({ "tid": "t" }), 5).unwrap();
+        assert!(new_a && !new_b);
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(o.due(10, 10).unwrap().iter().map(|d| d.id).collect::<Vec<_>>(), [a, c]);
+        assert_eq!(o.due(10, 10).unwrap()[0].record["tid"], "t");
+    }
+
+    #[test]
+    fn minted_rkeys_are_kept_per_key() {
+        let o = outbox();
+        let a = o.rkey_for("t/opening", || "3aaa".into()).unwrap();
+        assert_eq!(a, "3aaa");
+        assert_eq!(o.rkey_for("t/opening", || "3bbb".into()).unwrap(), "3aaa");
+        assert_eq!(o.rkey_for("t/finals", || "3bbb".into()).unwrap(), "3bbb");
+    }
+
+    #[test]
+    fn opt_ins_survive_reopening() {
+        let path = std::env::temp_dir().join(format!("rps-outbox-{}.sqlite3", std::process::id()));
+        let path = path.to_str().unwrap();
+        let o = Outbox::open(path).unwrap();
+        assert_eq!(o.opt_in("t").unwrap(), None);
+        o.set_opt_in("t", true).unwrap();
+        o.set_opt_in("u", true).unwrap();
+        o.set_opt_in("u", false).unwrap();
+        drop(o);
+        let o = Outbox::open(path).unwrap();
+        assert_eq!((o.opt_in("t").unwrap(), o.opt_in("u").unwrap()), (Some(true), Some(false)));
+        drop(o);
+        for suffix in ["", "-wal", "-shm"] { let _ = std::fs::remove_file(format!("{path}{suffix}")); }
+    }
+
+    #[test]
+    fn backs_off_then_gives_up() {
+        let o = outbox();
+        let (id, _) = o.enqueue("c", "k", &json!({}), 0).unwrap();
+        let mut now = 0;
+        for (attempt, delay) in [(1, 1_000), (2, 2_000), (3, 4_000)] {
+            o.attempt_failed(id, "503", true, &BACKOFF, now).unwrap();
+            let s = o.status(id).unwrap().unwrap();
+            assert_eq!((s.state.as_str(), s.attempts, s.next_attempt_ms), ("pending", attempt, Some(now + delay)));
+            assert!(o.due(now + delay - 1, 10).unwrap().is_empty());
+            now += delay;
+        }
+        o.attempt_failed(id, "503", true, &BACKOFF, now).unwrap();
+        let s = o.status(id).unwrap().unwrap();
+        assert_eq!((s.state.as_str(), s.attempts, s.last_error.as_deref()), ("failed", 4, Some("503")));
+        assert!(o.due(i64::MAX, 10).unwrap().is_empty());
+        assert_eq!(BACKOFF.delay_ms(10), 8_000);
+    }
+
+    #[test]
+    fn permanent_errors_fail_at_once() {
+        let o = outbox();
+        let (id, _) = o.enqueue("c", "k", &json!({}), 0).unwrap();
+        o.attempt_failed(id, "InvalidRequest", false, &BACKOFF, 0).unwrap();
+        assert_eq!(o.status(id).unwrap().unwrap().state, "failed");
+        assert!(o.status(id + 1).unwrap().is_none());
+    }
+
+    #[test]
+    fn rate_limit_refills_over_time() {
+        let mut l = RateLimit::new(3_600, 2, 0); // one per second
+        assert!(l.try_take(0).is_ok() && l.try_take(0).is_ok());
+        assert_eq!(l.try_take(0), Err(1_000));
+        assert_eq!(l.try_take(400), Err(600));
+        assert!(l.try_take(1_000).is_ok());
+        assert!(l.try_take(100_000).is_ok() && l.try_take(100_000).is_ok());
+        assert!(l.try_take(100_000).is_err());
+    }
+
+    #[tokio::test]
+    async fn delivers_and_retries_against_a_pds() {
+        let (url, mock) = mock_pds().await;
+        let pds = app_password(&url);
+        let o = outbox();
+        let mut limit = RateLimit::new(3_600_000, 10, now_ms());
+        let instant = Backoff { base_ms: 0, ..BACKOFF };
+        let (a, _) = o.enqueue("zone.peace.rps.roundAnchor", "ka", &json!({ "tid": "t1" }), now_ms()).unwrap();
+        let (b, _) = o.enqueue("zone.peace.rps.roundAnchor", "kb", &json!({ "tid": "t1" }), now_ms()).unwrap();
+        let (bad, _) = o.enqueue("zone.peace.rps.roundAnchor", "kc", &json!({}), now_ms()).unwrap();
+        mock.lock().unwrap().fail_next = vec![503];
+        assert_eq!(deliver_due(&o, &pds, &mut limit, &instant).await.unwrap(), 1);
+        assert_eq!(o.status(a).unwrap().unwrap().state, "pending");
+        assert_eq!(o.status(bad).unwrap().unwrap().state, "failed");
+        assert_eq!(deliver_due(&o, &pds, &mut limit, &instant).await.unwrap(), 1);
+        let s = o.status(a).unwrap().unwrap();
+        assert_eq!((s.state.as_str(), s.attempts), ("delivered", 2));
+        assert_eq!(s.uri.unwrap(), "at://did:plc:writer/zone.peace.rps.roundAnchor/ka");
+        assert_eq!(o.status(b).unwrap().unwrap().state, "delivered");
+        assert_eq!(mock.lock().unwrap().puts.len(), 2);
+    }
+}
@@ -0,0 +1,289 @@
+//! Minimal XRPC client for writing records to a PDS.
+//!
+//! Authenticates with an app password (`com.atproto.server.createSession`) or
+//! with an existing session's bearer tokens (e.g. from an OAuth login), keeps
+//! the session, refreshes it when the PDS reports an expired token, and writes
+//! with `com.atproto.repo.putRecord` under a caller-chosen record key, so a
+//! retried write replaces rather than duplicates. The PDS is a plain base URL,
+//! so tests point the client at a local mock XRPC server.
+
+use reqwest::{Client as HttpClient, RequestBuilder, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DEFAULT_PDS: &str = "https://bsky.social";
+
+#[derive(Debug, thiserror::Error)]
+pub enum PdsError {
+    #[error("request failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("{method} returned {status}: {error} {message}")]
+    Xrpc { method: &'static str, status: u16, error: String, message: String },
+    #[error("session expired and no app password is configured")]
+    SessionExpired,
+}
+
+/// How the writer authenticates.
+#[derive(Debug, Clone)]
+pub enum Auth {
+    /// Handle or DID plus an app password.
+    AppPassword { identifier: String, password: String },
+    /// Tokens of a session created elsewhere; without a refresh token it cannot be renewed.
+    Session { did: String, access_jwt: String, refresh_jwt: Option<String> },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Session {
+    did: String,
+    access_jwt: String,
+    #[serde(default)]
+    refresh_jwt: Option<String>,
+}
+
+/// Where a written record lives.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RecordRef {
+    pub uri: String,
+    pub cid: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct XrpcError {
+    #[serde(default)]
+    error: String,
+    #[serde(default)]
+    message: String,
+}
+
+pub struct PdsClient {
+    base: String,
+    auth: Auth,
+    http: HttpClient,
+    session: Mutex<Option<Session>>,
+}
+
+impl PdsClient {
+    pub fn new(base: &str, auth: Auth, timeout: Duration) -> Self {
+        let session = match &auth {
+            Auth::Session { did, access_jwt, refresh_jwt } => Some(Session { did: did.clone(), access_jwt: access_jwt.clone(), refresh_jwt: refresh_jwt.clone() }),
+            Auth::AppPassword { .. } => None,
+        };
+        PdsClient {
+            base: base.trim_end_matches('/').to_string(),
+            auth,
+            http: HttpClient::builder().timeout(timeout).build().expect("http client builds"),
+            session: Mutex::new(session),
+        }
+    }
+
+    /// Reads `ATPROTO_PDS_URL` and either `ATPROTO_IDENTIFIER` + `ATPROTO_APP_PASSWORD`
+    /// or `ATPROTO_DID` + `ATPROTO_ACCESS_JWT` (+ `ATPROTO_REFRESH_JWT`).
+    /// `None` when no credentials are set.
+    pub fn from_env() -> Option<Self> {
+        let var = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+        let auth = match (var("ATPROTO_IDENTIFIER"), var("ATPROTO_APP_PASSWORD"), var("ATPROTO_DID"), var("ATPROTO_ACCESS_JWT")) {
+            (Some(identifier), Some(password), _, _) => Auth::AppPassword { identifier, password },
+            (_, _, Some(did), Some(access_jwt)) => Auth::Session { did, access_jwt, refresh_jwt: var("ATPROTO_REFRESH_JWT") },
+            _ => return None,
+        };
+        let base = var("ATPROTO_PDS_URL").unwrap_or_else(|| DEFAULT_PDS.to_string());
+        let timeout = Duration::from_millis(var("ATPROTO_TIMEOUT_MS").and_then(|s| s.parse().ok()).unwrap_or(5_000));
+        Some(PdsClient::new(&base, auth, timeout))
+    }
+
+    pub fn base(&self) -> &str { &self.base }
+
+    /// Writes `record` to `collection` under `rkey` in the session's repo,
+    /// replacing any record already there. The record's `$type` is set to the collection.
+    pub async fn put_record(&self, collection: &str, rkey: &str, mut record: serde_json::Value) -> Result<RecordRef, PdsError> {
+        if let Some(o) = record.as_object_mut() { o.insert("$type".into(), collection.into()); }
+        let mut session = self.session().await?;
+        for attempt in 0..2 {
+            let body = serde_json::json!({ "repo": session.did, "collection": collection, "rkey": rkey, "record": record });
+            let req = self.http.post(self.xrpc("com.atproto.repo.putRecord")).bearer_auth(&session.access_jwt).json(&body);
+            match send(req, "com.atproto.repo.putRecord").await {
+                Err(PdsError::Xrpc { error, .. }) if attempt == 0 && is_expired(&error) => {
+                    session = self.renew(&session).await?;
+                }
+                res => return res,
+            }
+        }
+        unreachable!("second attempt always returns")
+    }
+
+    fn xrpc(&self, method: &str) -> String { format!("{}/xrpc/{}", self.base, method) }
+
+    async fn session(&self) -> Result<Session, PdsError> {
+        if let Some(s) = self.session.lock().unwrap().clone() { return Ok(s); }
+        self.login().await
+    }
+
+    async fn login(&self) -> Result<Session, PdsError> {
+        let Auth::AppPassword { identifier, password } = &self.auth else { return Err(PdsError::SessionExpired) };
+        let req = self.http.post(self.xrpc("com.atproto.server.createSession"))
+            .json(&serde_json::json!({ "identifier": identifier, "password": password }));
+        let s: Session = send(req, "com.atproto.server.createSession").await?;
+        tracing::info!(did = %s.did, pds = %self.base, "atproto session created");
+        *self.session.lock().unwrap() = Some(s.clone());
+        Ok(s)
+    }
+
+    /// Refreshes with the refresh token, falling back to a fresh login.
+    async fn renew(&self, old: &Session) -> Result<Session, PdsError> {
+        if let Some(refresh) = &old.refresh_jwt {
+            let req = self.http.post(self.xrpc("com.atproto.server.refreshSession")).bearer_auth(refresh);
+            match send::<Session>(req, "com.atproto.server.refreshSession").await {
+                Ok(s) => {
+                    *self.session.lock().unwrap() = Some(s.clone());
+                    return Ok(s);
+                }
+                Err(err) => tracing::warn!(%err, "atproto session refresh failed"),
+            }
+        }
+        *self.session.lock().unwrap() = None;
+        self.login().await
+    }
+}
+
+impl PdsError {
+    /// Whether the same write may succeed later: transport failures, rate
+    /// limits, server errors and sessions that could be renewed after a restart.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            PdsError::Http(_) | PdsError::SessionExpired => true,
+            PdsError::Xrpc { status, error, .. } => *status >= 500 || *status == 429 || error == "RateLimitExceeded",
+        }
+    }
+}
+
+fn is_expired(error: &str) -> bool {
+    matches!(error, "ExpiredToken" | "InvalidToken")
+}
+
+async fn send<T: serde::de::DeserializeOwned>(req: RequestBuilder, method: &'static str) -> Result<T, PdsError> {
+    let res = req.send().await?;
+    let status = res.status();
+    if status == StatusCode::OK {
+        return Ok(res.json().await?);
+    }
+    let e: XrpcError = res.json().await.unwrap_or_default();
+    Err(PdsError::Xrpc { method, status: status.as_u16(), error: e.error, message: e.message })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    //! A mock PDS serving the three XRPC methods the client uses. Access
+    //! tokens are `access-N`; bumping `generation` expires the current one.
+
+    use super::*;
+    use axum::{extract::State, http::HeaderMap, routing::post, Json, Router};
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    const DID: &str = "did:plc:writer";
+
+    #[derive(Default)]
+    pub(crate) struct Mock { pub generation: usize, pub logins: usize, pub refreshes: usize, pub puts: Vec<Value>, pub fail_next: Vec<u16> }
+
+    pub(crate) type Shared = Arc<Mutex<Mock>>;
+    type Reply = (axum::http::StatusCode, Json<Value>);
+
+    fn bearer(h: &HeaderMap) -> String {
+        h.get("authorization").and_then(|v| v.to_str().ok()).unwrap_or("").trim_start_matches("Bearer ").to_string()
+    }
+
+    fn err(status: u16, error: &str) -> Reply {
+        (axum::http::StatusCode::from_u16(status).unwrap(), Json(json!({ "error": error, "message": "mock" })))
+    }
+
+    fn tokens(m: &Mock) -> Reply {
+        let g = m.generation;
+        (axum::http::StatusCode::OK, Json(json!({ "did": DID, "handle": "writer.test", "accessJwt": format!("access-{g}"), "refreshJwt": format!("refresh-{g}") })))
+    }
+
+    pub(crate) async fn mock_pds() -> (String, Shared) {
+        let state: Shared = Arc::default();
+        let app = Router::new()
+            .route("/xrpc/com.atproto.server.createSession", post(|State(s): State<Shared>, Json(b): Json<Value>| async move {
+                if b["identifier"] != "writer.test" || b["password"] != "app-pass" { return err(401, "AuthenticationRequired"); }
+                let mut m = s.lock().unwrap();
+                m.logins += 1;
+                tokens(&m)
+            }))
+            .route("/xrpc/com.atproto.server.refreshSession", post(|State(s): State<Shared>, h: HeaderMap| async move {
+                let mut m = s.lock().unwrap();
+                if bearer(&h) != format!("refresh-{}", m.generation.wrapping_sub(1)) { return err(400, "ExpiredToken"); }
+                m.refreshes += 1;
+                tokens(&m)
+            }))
+            .route("/xrpc/com.atproto.repo.putRecord", post(|State(s): State<Shared>, h: HeaderMap, Json(b): Json<Value>| async move {
+                let mut m = s.lock().unwrap();
+                if bearer(&h) != format!("access-{}", m.generation) { return err(400, "ExpiredToken"); }
+                if let Some(status) = m.fail_next.pop() { return err(status, if status == 429 { "RateLimitExceeded" } else { "InternalServerError" }); }
+                if b["record"]["tid"].is_null() { return err(400, "InvalidRequest"); }
+                m.puts.push(b.clone());
+                let (repo, collection, rkey) = (b["repo"].as_str().unwrap(), b["collection"].as_str().unwrap(), b["rkey"].as_str().unwrap());
+                (axum::http::StatusCode::OK, Json(json!({ "uri": format!("at://{repo}/{collection}/{rkey}"), "cid": format!("bafy{}", m.puts.len()) })))
+            }))
+            .with_state(state.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), state)
+    }
+
+    pub(crate) fn app_password(url: &str) -> PdsClient {
+        PdsClient::new(url, Auth::AppPassword { identifier: "writer.test".into(), password: "app-pass".into() }, Duration::from_secs(2))
+    }
+
+    #[tokio::test]
+    async fn logs_in_once_and_writes_typed_records() {
+        let (url, mock) = mock_pds().await;
+        let c = app_password(&url);
+        let a = c.put_record("zone.peace.rps.roundAnchor", "k1", json!({ "tid": "t1", "round": 1 })).await.unwrap();
+        let b = c.put_record("zone.peace.rps.roundAnchor", "k2", json!({ "tid": "t1", "round": 2 })).await.unwrap();
+        assert_eq!(a, RecordRef { uri: format!("at://{DID}/zone.peace.rps.roundAnchor/k1"), cid: "bafy1".into() });
+        assert_eq!(b.cid, "bafy2");
+        let m = mock.lock().unwrap();
+        assert_eq!(m.logins, 1);
+        assert_eq!(m.puts[1]["repo"], DID);
+        assert_eq!(m.puts[1]["rkey"], "k2");
+        assert_eq!(m.puts[1]["record"]["$type"], "zone.peace.rps.roundAnchor");
+    }
+
+    #[tokio::test]
+    async fn refreshes_an_expired_session_and_retries() {
+        let (url, mock) = mock_pds().await;
+        let c = app_password(&url);
+        c.put_record("zone.peace.rps.roundAnchor", "k", json!({ "tid": "t1" })).await.unwrap();
+        mock.lock().unwrap().generation += 1;
+        c.put_record("zone.peace.rps.roundAnchor", "k", json!({ "tid": "t1" })).await.unwrap();
+        let m = mock.lock().unwrap();
+        assert_eq!((m.logins, m.refreshes, m.puts.len()), (1, 1, 2));
+    }
+
+    #[tokio::test]
+    async fn uses_an_existing_session_without_logging_in() {
+        let (url, mock) = mock_pds().await;
+        let c = PdsClient::new(&url, Auth::Session { did: DID.into(), access_jwt: "access-0".into(), refresh_jwt: None }, Duration::from_secs(2));
+        c.put_record("zone.peace.rps.roundAnchor", "k", json!({ "tid": "t1" })).await.unwrap();
+        mock.lock().unwrap().generation += 1;
+        let e = c.put_record("zone.peace.rps.roundAnchor", "k", json!({ "tid": "t1" })).await.unwrap_err();
+        assert!(matches!(e, PdsError::SessionExpired), "{e}");
+        assert_eq!(mock.lock().unwrap().logins, 0);
+    }
+
+    #[tokio::test]
+    async fn surfaces_xrpc_errors() {
+        let (url, _) = mock_pds().await;
+        let e = app_password(&url).put_record("zone.peace.rps.roundAnchor", "k", json!({})).await.unwrap_err();
+        assert!(matches!(e, PdsError::Xrpc { status: 400, ref error, .. } if error == "InvalidRequest"), "{e}");
+        assert!(!e.is_retryable());
+        let bad = PdsClient::new(&url, Auth::AppPassword { identifier: "writer.test".into(), password: "wrong".into() }, Duration::from_secs(2));
+        let e = bad.put_record("zone.peace.rps.roundAnchor", "k", json!({ "tid": "t1" })).await.unwrap_err();
+        assert!(matches!(e, PdsError::Xrpc { status: 401, .. }), "{e}");
+        assert!(!e.is_retryable());
+    }
+}
@@ -0,0 +1,289 @@
+//! Tournament announcements as `app.bsky.feed.post` records.
+//!
+//! Each kind of announcement has a text template with `{name}` placeholders
+//! (overridable with `POST_TEMPLATE_<KIND>`). Rendering fills them in and
+//! records a rich-text facet for every mention, link and hashtag, with UTF-8
+//! byte offsets as `app.bsky.richtext.facet` requires. Posts are only written
+//! for tournaments that opted in; a dry run returns the rendered record instead.
+
+use crate::outbox::Outbox;
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+pub const NSID: &str = "app.bsky.feed.post";
+/// Bluesky's post length limit (graphemes; counted here as chars).
+const MAX_CHARS: usize = 300;
+
+// tids opted in to announcements at startup with POSTS_TIDS
+static SEEDED: Lazy<HashSet<String>> = Lazy::new(|| {
+    let tids = std::env::var("POSTS_TIDS").unwrap_or_default();
+    tids.split(',').map(str::trim).filter(|t| !t.is_empty()).map(str::to_string).collect()
+});
+// opt-in changes while there is no outbox to store them (records are only logged)
+static UNSTORED: Lazy<Mutex<HashMap<String, bool>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Whether `tid` gets announcements: its last `/posts/opt_in` choice, kept in
+/// the outbox database, or else whether it is in `POSTS_TIDS`.
+pub fn opted_in(store: Option<&Outbox>, tid: &str) -> rusqlite::Result<bool> {
+    let chosen = match store {
+        Some(o) => o.opt_in(tid)?,
+        None => UNSTORED.lock().unwrap().get(tid).copied(),
+    };
+    Ok(chosen.unwrap_or_else(|| SEEDED.contains(tid)))
+}
+
+pub fn set_opt_in(store: Option<&Outbox>, tid: &str, enabled: bool) -> rusqlite::Result<()> {
+    match store {
+        Some(o) => o.set_opt_in(tid, enabled),
+        None => { UNSTORED.lock().unwrap().insert(tid.to_string(), enabled); Ok(()) }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Player { pub did: String, pub handle: String }
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Announcement {
+    Opening { tid: String },
+    RoundStart { tid: String, round: u32, #[serde(default)] pairs: usize },
+    Finals { tid: String, p1: Player, p2: Player },
+    Champion { tid: String, champion: Player },
+}
+
+impl Announcement {
+    pub fn tid(&self) -> &str {
+        match self {
+            Announcement::Opening { tid } | Announcement::RoundStart { tid, .. } | Announcement::Finals { tid, .. } | Announcement::Champion { tid, .. } => tid,
+        }
+    }
+
+    /// What makes two announcements the same post, for deduplication.
+    pub fn key(&self) -> String {
+        match self {
+            Announcement::Opening { tid } => format!("{tid}/opening"),
+            Announcement::RoundStart { tid, round, .. } => format!("{tid}/round/{round}"),
+            Announcement::Finals { tid, .. } => format!("{tid}/finals"),
+            Announcement::Champion { tid, .. } => format!("{tid}/champion"),
+        }
+    }
+}
+
+/// A placeholder's value.
+#[derive(Debug, Clone)]
+pub enum Piece {
+    Text(String),
+    /// Rendered `@handle`, faceted with the DID.
+    Mention { handle: String, did: String },
+    /// Rendered as the URI itself.
+    Link(String),
+    /// Rendered `#tag`.
+    Tag(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RichText { pub text: String, pub facets: Vec<Value> }
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum PostError {
+    #[error("template has no value for {{{0}}}")]
+    UnknownPlaceholder(String),
+    #[error("template has an unclosed {{")]
+    Unclosed,
+    #[error("post is {0} characters, over the {MAX_CHARS} limit")]
+    TooLong(usize),
+}
+
+/// Fills `{name}` placeholders from `vars`; `{{` is a literal brace.
+pub fn render(template: &str, vars: &HashMap<&str, Piece>) -> Result<RichText, PostError> {
+    let mut text = String::new();
+    let mut facets = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find(['{', '}']) {
+        text.push_str(&rest[..open]);
+        if rest[open..].starts_with("{{") || rest[open..].starts_with("}}") {
+            text.push_str(&rest[open..open + 1]);
+            rest = &rest[open + 2..];
+            continue;
+        }
+        if rest[open..].starts_with('}') {
+            text.push('}');
+            rest = &rest[open + 1..];
+            continue;
+        }
+        let close = rest[open..].find('}').ok_or(PostError::Unclosed)? + open;
+        let name = &rest[open + 1..close];
+        let piece = vars.get(name).ok_or_else(|| PostError::UnknownPlaceholder(name.to_string()))?;
+        let start = text.len();
+        let feature = match piece {
+            Piece::Text(s) => { text.push_str(s); None }
+            Piece::Mention { handle, did } => {
+                text.push('@');
+                text.push_str(handle.trim_start_matches('@'));
+                Some(json!({ "$type": "app.bsky.richtext.facet#mention", "did": did }))
+            }
+            Piece::Link(uri) => { text.push_str(uri); Some(json!({ "$type": "app.bsky.richtext.facet#link", "uri": uri })) }
+            Piece::Tag(tag) => { text.push('#'); text.push_str(tag); Some(json!({ "$type": "app.bsky.richtext.facet#tag", "tag": tag })) }
+        };
+        if let Some(f) = feature {
+            facets.push(json!({ "index": { "byteStart": start, "byteEnd": text.len() }, "features": [f] }));
+        }
+        rest = &rest[close + 1..];
+    }
+    text.push_str(rest);
+    let chars = text.chars().count();
+    if chars > MAX_CHARS { return Err(PostError::TooLong(chars)); }
+    Ok(RichText { text, facets })
+}
+
+/// Templates per announcement kind, plus the bracket link base and hashtag.
+#[derive(Debug, Clone)]
+pub struct Templates {
+    pub opening: String,
+    pub round_start: String,
+    pub finals: String,
+    pub champion: String,
+    /// Bracket pages are `{bracket_base}/{tid}`.
+    pub bracket_base: String,
+    pub hashtag: String,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Templates {
+            opening: "{tid} is open! Register by writing a zone.peace.rps.registration record. Bracket: {bracket} {tag}".into(),
+            round_start: "Round {round} of {tid} is underway with {pairs} matches. Follow along: {bracket} {tag}".into(),
+            finals: "Finals of {tid}: {p1} vs {p2}! Watch: {bracket} {tag}".into(),
+            champion: "🏆 {champion} wins {tid}! Results: {bracket} {tag}".into(),
+            bracket_base: "http://localhost:3001/t".into(),
+            hashtag: "TournamentRPS".into(),
+        }
+    }
+}
+
+impl Templates {
+    /// Defaults overridden by `POST_TEMPLATE_{OPENING,ROUND_START,FINALS,CHAMPION}`,
+    /// `BRACKET_URL_BASE` and `POST_HASHTAG`.
+    pub fn from_env() -> Self {
+        let d = Templates::default();
+        let var = |k: &str, d: String| std::env::var(k).ok().filter(|v| !v.is_empty()).unwrap_or(d);
+        Templates {
+            opening: var("POST_TEMPLATE_OPENING", d.opening),
+            round_start: var("POST_TEMPLATE_ROUND_START", d.round_start),
+            finals: var("POST_TEMPLATE_FINALS", d.finals),
+            champion: var("POST_TEMPLATE_CHAMPION", d.champion),
+            bracket_base: var("BRACKET_URL_BASE", d.bracket_base),
+            hashtag: var("POST_HASHTAG", d.hashtag),
+        }
+    }
+
+    /// Renders the announcement into an `app.bsky.feed.post` record.
+    pub fn compose(&self, a: &Announcement, created_at: &str) -> Result<Value, PostError> {
+        let tid = a.tid();
+        let mut vars: HashMap<&str, Piece> = HashMap::from([
+            ("tid", Piece::Text(tid.to_string())),
+            ("bracket", Piece::Link(format!("{}/{}", self.bracket_base.trim_end_matches('/'), tid))),
+            ("tag", Piece::Tag(self.hashtag.clone())),
+        ]);
+        let mention = |p: &Player| Piece::Mention { handle: p.handle.clone(), did: p.did.clone() };
+        let template = match a {
+            Announcement::Opening { .. } => &self.opening,
+            Announcement::RoundStart { round, pairs, .. } => {
+                vars.insert("round", Piece::Text(round.to_string()));
+                vars.insert("pairs", Piece::Text(pairs.to_string()));
+                &self.round_start
+            }
+            Announcement::Finals { p1, p2, .. } => {
+                vars.insert("p1", mention(p1));
+                vars.insert("p2", mention(p2));
+                &self.finals
+            }
+            Announcement::Champion { champion, .. } => {
+                vars.insert("champion", mention(champion));
+                &self.champion
+            }
+        };
+        let rt = render(template, &vars)?;
+        let mut record = json!({ "$type": NSID, "text": rt.text, "createdAt": created_at, "langs": ["en"] });
+        if !rt.facets.is_empty() { record["facets"] = rt.facets.into(); }
+        Ok(record)
+    }
+}
+
+/// A record key in TID form (13 base32-sortable chars: 53-bit microsecond
+/// timestamp, 10-bit clock id), as `app.bsky.feed.post` requires.
+pub fn tid_rkey(micros: u64, clock_id: u16) -> String {
+    const S32: &[u8] = b"234567abcdefghijklmnopqrstuvwxyz";
+    let v = ((micros & ((1 << 53) - 1)) << 10) | (clock_id as u64 & 0x3ff);
+    (0..13).rev().map(|i| S32[((v >> (i * 5)) & 31) as usize] as char).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(handle: &str) -> Player { Player { did: format!("did:plc:{handle}"), handle: format!("{handle}.test") } }
+
+    fn byte_slice<'a>(text: &'a str, facet: &Value) -> &'a str {
+        let (s, e) = (facet["index"]["byteStart"].as_u64().unwrap() as usize, facet["index"]["byteEnd"].as_u64().unwrap() as usize);
+        &text[s..e]
+    }
+
+    #[test]
+    fn facets_use_utf8_byte_offsets() {
+        let vars = HashMap::from([("who", Piece::Mention { handle: "@alice.test".into(), did: "did:plc:alice".into() }), ("t", Piece::Tag("RPS".into()))]);
+        let rt = render("🏆 {who} {{won}} {t}", &vars).unwrap();
+        assert_eq!(rt.text, "🏆 @alice.test {won} #RPS");
+        assert_eq!(rt.facets[0]["index"], json!({ "byteStart": 5, "byteEnd": 16 }));
+        assert_eq!(byte_slice(&rt.text, &rt.facets[0]), "@alice.test");
+        assert_eq!(rt.facets[0]["features"][0]["did"], "did:plc:alice");
+        assert_eq!(byte_slice(&rt.text, &rt.facets[1]), "#RPS");
+        assert_eq!(rt.facets[1]["features"][0]["tag"], "RPS");
+    }
+
+    #[test]
+    fn rejects_bad_templates_and_long_posts() {
+        let vars = HashMap::from([("x", Piece::Text("y".repeat(MAX_CHARS)))]);
+        assert_eq!(render("{nope}", &vars), Err(PostError::UnknownPlaceholder("nope".into())));
+        assert_eq!(render("{x", &vars), Err(PostError::Unclosed));
+        assert_eq!(render("{x}!", &vars), Err(PostError::TooLong(MAX_CHARS + 1)));
+    }
+
+    #[test]
+    fn composes_each_announcement() {
+        let t = Templates { bracket_base: "https://rps.example/t/".into(), ..Templates::default() };
+        let finals = t.compose(&Announcement::Finals { tid: "spring".into(), p1: player("alice"), p2: player("bob") }, "2026-10-18T12:00:00Z").unwrap();
+        assert_eq!(finals["text"], "Finals of spring: @alice.test vs @bob.test! Watch: https://rps.example/t/spring #TournamentRPS");
+        assert_eq!(finals["$type"], NSID);
+        let features: Vec<&Value> = finals["facets"].as_array().unwrap().iter().map(|f| &f["features"][0]).collect();
+        assert_eq!(features[0]["did"], "did:plc:alice");
+        assert_eq!(features[1]["did"], "did:plc:bob");
+        assert_eq!(features[2]["uri"], "https://rps.example/t/spring");
+        let round = t.compose(&Announcement::RoundStart { tid: "spring".into(), round: 2, pairs: 4 }, "2026-10-18T12:00:00Z").unwrap();
+        assert!(round["text"].as_str().unwrap().starts_with("Round 2 of spring is underway with 4 matches."));
+        for a in [Announcement::Opening { tid: "spring".into() }, Announcement::Champion { tid: "spring".into(), champion: player("alice") }] {
+            assert!(t.compose(&a, "2026-10-18T12:00:00Z").is_ok(), "{a:?}");
+        }
+    }
+
+    #[test]
+    fn announcements_parse_by_kind() {
+        let a: Announcement = serde_json::from_value(json!({ "kind": "champion", "tid": "t", "champion": { "did": "did:plc:a", "handle": "a.test" } })).unwrap();
+        assert_eq!((a.tid(), a.key().as_str()), ("t", "t/champion"));
+        let r: Announcement = serde_json::from_value(json!({ "kind": "round_start", "tid": "t", "round": 3 })).unwrap();
+        assert_eq!(r.key(), "t/round/3");
+    }
+
+    #[test]
+    fn tid_rkeys_sort_by_time() {
+        let a = tid_rkey(1_760_788_800_000_000, 7);
+        let b = tid_rkey(1_760_788_800_000_001, 0);
+        assert_eq!(a.len(), 13);
+        assert!(a < b);
+        assert!(matches!(a.chars().next(), Some('2'..='7' | 'a' | 'b')));
+        assert_eq!(tid_rkey(0, 0), "2222222222222");
+    }
+}
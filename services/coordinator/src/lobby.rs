@@ -0,0 +1,132 @@
+//! Server-push lobby channel.
+//!
+//! Clients subscribe with `GET /lobby?tid=..&did=..` and receive Server-Sent
+//! Events instead of polling `/queue_ready` or `/assignment`. Each event is a
+//! JSON object tagged by `type`, mirroring the polling payloads.
+
+use axum::response::sse::{Event, KeepAlive, Sse};
+use futures::stream::{self, Stream};
+use once_cell::sync::Lazy;
+use rps_match_engine::Version as CommitVersion;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// Events pushed to lobby subscribers.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum LobbyEvent {
+    /// Same payload as the polling ASSIGN response. A notification only: the
+    /// assignment stays pending until claimed (see `deliver_assignment`).
+    Assign { match_id: String, role: String, peer: serde_json::Value, ticket: String, commit_version: CommitVersion },
+    /// A tournament round has been paired; an ASSIGN follows for each entrant.
+    RoundStarting { tid: String, round: u32, pairs: usize },
+    /// Entrant count changed for a tournament.
+    TournamentUpdate { tid: String, entrants: usize },
+    /// A pending match was called off (e.g. the opponent never claimed it);
+    /// the subscriber has been put back in the queue.
+    MatchCancelled { match_id: String, reason: String },
+    /// Current queue standing for the subscriber.
+    QueuePosition { tid: String, position: usize, waiting: usize, estimated_wait_ms: u64 },
+}
+
+struct Subscriber { tid: String, tx: mpsc::UnboundedSender<LobbyEvent> }
+
+// did -> live subscriptions (a player may have several tabs open)
+static SUBSCRIBERS: Lazy<Mutex<HashMap<String, Vec<Subscriber>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Pushes an event to every live subscription of `did`. Returns true if at
+/// least one subscription accepted it.
+pub fn publish(did: &str, ev: LobbyEvent) -> bool {
+    let mut subs = SUBSCRIBERS.lock().unwrap();
+    let Some(list) = subs.get_mut(did) else { return false };
+    list.retain(|s| s.tx.send(ev.clone()).is_ok());
+    let delivered = !list.is_empty();
+    if !delivered { subs.remove(did); }
+    delivered
+}
+
+/// Pushes an event to every subscriber watching `tid`.
+pub fn publish_tid(tid: &str, ev: LobbyEvent) {
+    let mut subs = SUBSCRIBERS.lock().unwrap();
+    for list in subs.values_mut() {
+        list.retain(|s| s.tid != tid || s.tx.send(ev.clone()).is_ok());
+    }
+    subs.retain(|_, list| !list.is_empty());
+}
+
+/// True if `did` has at least one open lobby subscription.
+pub fn is_subscribed(did: &str) -> bool {
+    SUBSCRIBERS.lock().unwrap().get(did).map(|l| l.iter().any(|s| !s.tx.is_closed())).unwrap_or(false)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LobbyQuery { pub tid: String, pub did: String }
+
+/// Registers a subscription for `did` and returns its event stream plus a
+/// sender the caller can use to replay current state to this subscriber only.
+pub fn subscribe(tid: &str, did: &str) -> (impl Stream<Item = Result<Event, Infallible>>, mpsc::UnboundedSender<LobbyEvent>) {
+    let (tx, rx) = register(tid, did);
+    let stream = stream::unfold(rx, |mut rx| async move {
+        let ev = rx.recv().await?;
+        let data = serde_json::to_string(&ev).unwrap_or_default();
+        Some((Ok(Event::default().data(data)), rx))
+    });
+    (stream, tx)
+}
+
+/// Registers a subscription and returns its raw event channel.
+pub fn register(tid: &str, did: &str) -> (mpsc::UnboundedSender<LobbyEvent>, mpsc::UnboundedReceiver<LobbyEvent>) {
+    let (tx, rx) = mpsc::unbounded_channel::<LobbyEvent>();
+    SUBSCRIBERS.lock().unwrap().entry(did.to_string()).or_default().push(Subscriber { tid: tid.to_string(), tx: tx.clone() });
+    (tx, rx)
+}
+
+/// Wraps a subscription stream in an SSE response with keep-alive comments.
+pub fn sse_response(stream: impl Stream<Item = Result<Event, Infallible>> + Send + 'static) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    Sse::new(stream).keep_alive(KeepAlive::new().interval(Duration::from_secs(15)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(tid: &str, entrants: usize) -> LobbyEvent { LobbyEvent::TournamentUpdate { tid: tid.into(), entrants } }
+
+    #[test]
+    fn publishes_to_every_live_subscription() {
+        let did = "did:plc:lobby-tabs";
+        assert!(!publish(did, update("t", 1)));
+        let (_tx1, mut rx1) = register("t", did);
+        let (_tx2, mut rx2) = register("t", did);
+        assert!(is_subscribed(did));
+        assert!(publish(did, update("t", 2)));
+        assert!(matches!(rx1.try_recv(), Ok(LobbyEvent::TournamentUpdate { entrants: 2, .. })));
+        assert!(matches!(rx2.try_recv(), Ok(LobbyEvent::TournamentUpdate { entrants: 2, .. })));
+        // a closed tab is pruned; the other still receives
+        drop(rx1);
+        assert!(publish(did, update("t", 3)));
+        assert!(matches!(rx2.try_recv(), Ok(LobbyEvent::TournamentUpdate { entrants: 3, .. })));
+        drop(rx2);
+        assert!(!publish(did, update("t", 4)));
+        assert!(!is_subscribed(did));
+    }
+
+    #[test]
+    fn tid_broadcast_reaches_only_that_tid() {
+        let (_a, mut in_t) = register("lobby-t1", "did:plc:lobby-a");
+        let (_b, mut other) = register("lobby-t2", "did:plc:lobby-b");
+        publish_tid("lobby-t1", update("lobby-t1", 5));
+        assert!(matches!(in_t.try_recv(), Ok(LobbyEvent::TournamentUpdate { entrants: 5, .. })));
+        assert!(other.try_recv().is_err());
+    }
+
+    #[test]
+    fn events_are_tagged_by_type() {
+        let ev = LobbyEvent::QueuePosition { tid: "t".into(), position: 1, waiting: 2, estimated_wait_ms: 300 };
+        assert_eq!(serde_json::to_value(&ev).unwrap(), serde_json::json!({ "type": "QUEUE_POSITION", "tid": "t", "position": 1, "waiting": 2, "estimated_wait_ms": 300 }));
+    }
+}
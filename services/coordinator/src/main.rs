@@ -1,4 +1,5 @@
 use axum::{routing::{get, post}, Router, Json, extract::Query};
+use axum::http::{header::AUTHORIZATION, HeaderMap, StatusCode};
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use serde::{Deserialize, Serialize};
@@ -9,15 +10,56 @@ use tower_http::cors::{CorsLayer, Any};
 use std::sync::Mutex;
 use once_cell::sync::Lazy;
 use std::time::Instant;
+use sha2::{Digest, Sha256};
+use rps_match_engine::Version as CommitVersion;
+use rps_crypto::merkle::{self, MerkleTree, Mode};
+use rps_shared_types::internal;
+use rps_shared_types::lexicon::RoundAnchor;
+
+mod lobby;
+mod practice;
+mod queue;
+mod registrations;
+use lobby::{LobbyEvent, LobbyQuery};
+use practice::AI_BYE_DID;
+use queue::{elo_update, MatchPool, PoolConfig, Waiter, DEFAULT_RATING};
 
 #[derive(Debug, Deserialize)]
-struct TicketRequest { did: String, match_id: String }
+struct TicketRequest {
+    did: String,
+    match_id: String,
+    #[serde(default, deserialize_with = "known_commit_versions")]
+    commit_versions: Option<Vec<CommitVersion>>,
+}
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Claims { sub: String, mid: String, exp: usize, iat: usize }
+struct Claims {
+    sub: String,
+    mid: String,
+    exp: usize,
+    iat: usize,
+    // DID of the server-side AI opponent for bye/AI-seat matches; signaling seats it as P2
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bot: Option<String>,
+    // fairness strategy the AI seat plays (uniform if absent)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    strategy: Option<String>,
+    // practice match: signaling does not report the result for rating
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    practice: bool,
+    // commit encoding negotiated for this match (v1 if absent)
+    #[serde(default)]
+    commit_version: CommitVersion,
+    // tournament and round of a bracket match; signaling publishes its digest under them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    round: Option<u32>,
+}
 
 /// Issues a short‑lived JWT "ticket" for a specific DID and match id.
-async fn issue_ticket(Json(req): Json<TicketRequest>) -> Json<serde_json::Value> {
+async fn issue_ticket(Json(req): Json<TicketRequest>) -> Result<Json<serde_json::Value>, (StatusCode, String)> {
+    let commit_version = admit_commit_versions(&req.did, req.commit_versions.as_deref())?;
     let key = std::env::var("TICKET_SECRET").unwrap_or_else(|_| "dev-secret-change-me".into());
     let now = Utc::now();
     let exp = now + Duration::minutes(10);
@@ -26,13 +68,29 @@ async fn issue_ticket(Json(req): Json<TicketRequest>) -> Json<serde_json::Value>
         mid: req.match_id,
         iat: now.timestamp() as usize,
         exp: exp.timestamp() as usize,
+        bot: None,
+        strategy: None,
+        practice: false,
+        commit_version,
+        tid: None,
+        round: None,
     };
     let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(key.as_bytes())).unwrap();
-    Json(serde_json::json!({ "ticket": token }))
+    Ok(Json(serde_json::json!({ "ticket": token, "commit_version": commit_version })))
 }
 
 #[derive(Debug, Deserialize)]
-struct ReadyForRoundReq { tid: String, round: u32, did: String }
+struct ReadyForRoundReq {
+    tid: String,
+    round: u32,
+    did: String,
+    /// AI strategy for this practice match (`uniform`, `frequency`, `markov[:N]`, `iocaine`).
+    #[serde(default)]
+    strategy: Option<String>,
+    /// Commit encodings the client speaks; omitted means v1 only.
+    #[serde(default, deserialize_with = "known_commit_versions")]
+    commit_versions: Option<Vec<CommitVersion>>,
+}
 
 #[derive(Debug, Serialize)]
 struct ReadyForRoundResp {
@@ -40,37 +98,247 @@ struct ReadyForRoundResp {
     role: String,
     peer: serde_json::Value,
     ticket: String,
+    commit_version: CommitVersion,
 }
 
 /// Demo pairing: forms a deterministic match id and issues a READY assignment
-/// along with a ticket. Also posts a stub round anchor to the writer service.
-async fn ready_for_round(Json(req): Json<ReadyForRoundReq>) -> Json<ReadyForRoundResp> {
+/// against the AI seat along with a ticket. Also posts the round anchor, with
+/// its beacon-bound pairing seed, to the writer service.
+async fn ready_for_round(Json(req): Json<ReadyForRoundReq>) -> Result<Json<ReadyForRoundResp>, (StatusCode, String)> {
     // Deterministic stub match id and role for MVP
     let match_id = format!("{}-r{}-{}", req.tid, req.round, &req.did);
-    let ticket = issue_jwt(&req.did, &match_id);
-    // Post round anchor stub to atproto-writer
-    let atw = std::env::var("ATPROTO_WRITER_HTTP").unwrap_or_else(|_| "http://localhost:8085".to_string());
-    let _ = HttpClient::new().post(format!("{}/round_anchor", atw))
-        .json(&serde_json::json!({
-            "tid": req.tid,
-            "round": req.round,
-            "aliveRoot": "0x00",
-            "pairingSeed": "0x00",
-            "merkleRoot": "0x00",
-            "postedAt": Utc::now().to_rfc3339(),
-        }))
-        .send().await;
+    let commit_version = admit_commit_versions(&req.did, req.commit_versions.as_deref())?;
+    let ticket = issue_ai_seat_jwt(&req.did, &match_id, AI_BYE_DID, req.strategy.as_deref(), commit_version);
+    let (drand_round, seed) = pairing_seed(&req.tid, req.round).await;
+    let roots = record_round(&req.tid, req.round, vec![req.did.clone()], std::slice::from_ref(&match_id));
+    post_round_anchor(&req.tid, req.round, &seed, drand_round, roots).await;
     let resp = ReadyForRoundResp {
         match_id,
         role: "P1".into(),
-        peer: serde_json::json!({"did":"AI","handle":"AI_BYE"}),
+        peer: serde_json::json!({"did": AI_BYE_DID, "handle": "AI_BYE"}),
+        ticket,
+        commit_version,
+    };
+    Ok(Json(resp))
+}
+
+#[derive(Debug, Deserialize)]
+struct PracticeReq {
+    did: String,
+    bot: String,
+    #[serde(default)]
+    difficulty: practice::Difficulty,
+    #[serde(default, deserialize_with = "known_commit_versions")]
+    commit_versions: Option<Vec<CommitVersion>>,
+}
+
+#[derive(Debug, Serialize)]
+struct PracticeResp {
+    match_id: String,
+    role: String,
+    peer: serde_json::Value,
+    ticket: String,
+    commit_version: CommitVersion,
+    difficulty: practice::Difficulty,
+    strategy: String,
+}
+
+/// Creates an immediate practice match against a named bot. The bot is seated
+/// by signaling as P2 and plays the difficulty's fairness strategy; the ticket
+/// is marked `practice`, so the result never touches ratings or tournaments.
+async fn practice_match(Json(req): Json<PracticeReq>) -> Result<Json<PracticeResp>, (axum::http::StatusCode, String)> {
+    let Some(bot) = practice::bot(&req.bot) else {
+        let names: Vec<&str> = practice::BOTS.iter().map(|b| b.name).collect();
+        return Err((axum::http::StatusCode::NOT_FOUND, format!("unknown bot {:?}; available: {}", req.bot, names.join(", "))));
+    };
+    let bot_did = bot.did();
+    let strategy = req.difficulty.strategy();
+    let match_id = format!("practice-{}-{}-{}", bot.name, req.did, Utc::now().timestamp_millis());
+    let commit_version = admit_commit_versions(&req.did, req.commit_versions.as_deref())?;
+    let ticket = mint_ticket(&req.did, &match_id, Some(&bot_did), Some(strategy), true, commit_version, None);
+    Ok(Json(PracticeResp {
+        match_id,
+        role: "P1".into(),
+        peer: serde_json::json!({"did": bot_did, "handle": bot.handle, "avatar": bot.avatar}),
         ticket,
+        commit_version,
+        difficulty: req.difficulty,
+        strategy: strategy.into(),
+    }))
+}
+
+/// Lists the practice bots.
+async fn practice_bots() -> Json<&'static [practice::Bot]> {
+    Json(practice::BOTS)
+}
+
+/// Fetches the current verified drand beacon from the fairness service and
+/// derives the round's pairing seed from it:
+/// `sha256("rps-pairing-v1" || u32be(len(tid)) || tid || u32be(round) || u64be(drand_round) || randomness)`.
+/// If fairness is unreachable the seed is bound to drand round 0 (zero randomness).
+async fn pairing_seed(tid: &str, round: u32) -> (u64, String) {
+    let fairness = std::env::var("FAIRNESS_HTTP").unwrap_or_else(|_| "http://localhost:8084".to_string());
+    let beacon = async {
+        let res = HttpClient::new().get(format!("{}/beacon", fairness))
+            .timeout(std::time::Duration::from_secs(2))
+            .send().await.ok()?.error_for_status().ok()?;
+        let j: serde_json::Value = res.json().await.ok()?;
+        let randomness: [u8; 32] = hex::decode(j["randomness"].as_str()?).ok()?.try_into().ok()?;
+        Some((j["round"].as_u64()?, randomness))
+    }.await;
+    let (drand_round, randomness) = beacon.unwrap_or_else(|| {
+        tracing::warn!(%tid, round, "no drand beacon; pairing seed bound to round 0");
+        (0, [0u8; 32])
+    });
+    let mut h = Sha256::new();
+    h.update(b"rps-pairing-v1");
+    h.update((tid.len() as u32).to_be_bytes());
+    h.update(tid.as_bytes());
+    h.update(round.to_be_bytes());
+    h.update(drand_round.to_be_bytes());
+    h.update(randomness);
+    (drand_round, hex::encode(h.finalize()))
+}
+
+/// Deterministic Fisher–Yates shuffle driven by `sha256(seed || u64be(i))`,
+/// so anyone holding the seed can reproduce the order.
+fn seeded_shuffle<T>(items: &mut [T], seed: &str) {
+    for i in (1..items.len()).rev() {
+        let mut h = Sha256::new();
+        h.update(seed.as_bytes());
+        h.update((i as u64).to_be_bytes());
+        let d = h.finalize();
+        let mut head = [0u8; 8];
+        head.copy_from_slice(&d[..8]);
+        let j = (u64::from_be_bytes(head) % (i as u64 + 1)) as usize;
+        items.swap(i, j);
+    }
+}
+
+/// Merkle roots (hex) published in a round anchor.
+struct RoundRoots {
+    /// Positional root over the round's entrant DIDs, sorted.
+    alive: String,
+    /// Positional root over the round's match ids, in pairing order.
+    pairings: String,
+}
+
+/// Keeps the round's alive set for `/alive_proof` and returns its roots.
+fn record_round(tid: &str, round: u32, mut alive: Vec<String>, match_ids: &[String]) -> RoundRoots {
+    alive.sort();
+    let tree = MerkleTree::new(Mode::Positional, &alive);
+    let roots = RoundRoots { alive: hex::encode(tree.root()), pairings: hex::encode(merkle::root(Mode::Positional, match_ids)) };
+    ALIVE_SETS.lock().unwrap().insert((tid.to_string(), round), (alive, tree));
+    roots
+}
+
+/// Posts the round anchor (pairing seed, its drand round and the round's Merkle roots) to atproto-writer,
+/// which stores it in its outbox. Transport errors and 5xx are retried `ROUND_ANCHOR_ATTEMPTS` (3) times
+/// with doubling delays; every failure is logged.
+async fn post_round_anchor(tid: &str, round: u32, pairing_seed: &str, drand_round: u64, roots: RoundRoots) {
+    let atw = std::env::var("ATPROTO_WRITER_HTTP").unwrap_or_else(|_| "http://localhost:8085".to_string());
+    let anchor = RoundAnchor {
+        tid: tid.to_string(),
+        round: round.into(),
+        alive_root: roots.alive,
+        pairing_seed: pairing_seed.to_string(),
+        drand_round: Some(drand_round),
+        merkle_root: roots.pairings,
+        posted_at: Utc::now().to_rfc3339(),
     };
-    Json(resp)
+    let attempts: u32 = std::env::var("ROUND_ANCHOR_ATTEMPTS").ok().and_then(|s| s.parse().ok()).unwrap_or(3);
+    let http = HttpClient::new();
+    let mut delay = std::time::Duration::from_millis(250);
+    for attempt in 1..=attempts.max(1) {
+        let mut req = http.post(format!("{}/round_anchor", atw)).json(&anchor).timeout(std::time::Duration::from_secs(5));
+        if let Some(b) = internal::bearer() { req = req.header(AUTHORIZATION, b); }
+        match req.send().await {
+            Ok(res) if res.status().is_success() => return,
+            Ok(res) if !res.status().is_server_error() => {
+                tracing::warn!(%tid, round, status = %res.status(), "round_anchor rejected");
+                return;
+            }
+            Ok(res) => tracing::warn!(%tid, round, attempt, status = %res.status(), "round_anchor failed"),
+            Err(err) => tracing::warn!(%tid, round, attempt, %err, "round_anchor failed"),
+        }
+        if attempt < attempts { tokio::time::sleep(delay).await; delay *= 2; }
+    }
+    tracing::error!(%tid, round, "round_anchor not delivered; the round has no anchor");
+}
+
+/// Asks atproto-writer to post a tournament announcement; it only posts for
+/// tournaments that opted in.
+async fn post_announcement(announcement: serde_json::Value) {
+    let atw = std::env::var("ATPROTO_WRITER_HTTP").unwrap_or_else(|_| "http://localhost:8085".to_string());
+    let mut req = HttpClient::new().post(format!("{}/posts", atw)).json(&announcement);
+    if let Some(b) = internal::bearer() { req = req.header(AUTHORIZATION, b); }
+    let _ = req.send().await;
+}
+
+/// Reads an advertised `commit_versions` list, dropping versions this server
+/// does not know (a newer client may offer more). An empty list reads as
+/// omitted; a list of only unknown versions reads as offering none.
+fn known_commit_versions<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Option<Vec<CommitVersion>>, D::Error> {
+    let offered: Vec<String> = Deserialize::deserialize(d)?;
+    if offered.is_empty() { return Ok(None); }
+    Ok(Some(offered.iter().filter_map(|v| v.parse().ok()).collect()))
+}
+
+/// Commit encodings the server accepts (`COMMIT_VERSIONS`, default `v1,v2`).
+fn accepted_commit_versions() -> Vec<CommitVersion> {
+    std::env::var("COMMIT_VERSIONS").ok()
+        .map(|s| s.split(',').filter_map(|v| v.parse().ok()).collect())
+        .unwrap_or_else(|| CommitVersion::ALL.to_vec())
+}
+
+/// Checks the commit encodings a client advertised against the server's and
+/// returns the version it would play, or 400 if they share none. Accepted
+/// lists are recorded; omitting the list keeps what was recorded before, so
+/// older request paths do not downgrade a client.
+fn admit_commit_versions(did: &str, offered: Option<&[CommitVersion]>) -> Result<CommitVersion, (StatusCode, String)> {
+    let accepts = accepted_commit_versions();
+    let mut known = COMMIT_VERSIONS.lock().unwrap();
+    let offer = offered.or_else(|| known.get(did).map(Vec::as_slice)).unwrap_or(&[CommitVersion::V1]);
+    let Some(version) = rps_match_engine::negotiate(&accepts, &[offer]) else {
+        let accepts: Vec<String> = accepts.iter().map(|v| v.to_string()).collect();
+        return Err((StatusCode::BAD_REQUEST, format!("no commit version in common with the server (accepts {})", accepts.join(", "))));
+    };
+    if let Some(v) = offered { known.insert(did.to_string(), v.to_vec()); }
+    Ok(version)
+}
+
+/// Picks a match's commit encoding: the newest version every player advertised
+/// that the server accepts. Players that never advertised are treated as
+/// v1-only. `None` means the players cannot be matched.
+fn negotiate_commit_version(dids: &[&str]) -> Option<CommitVersion> {
+    common_commit_version(&accepted_commit_versions(), dids)
+}
+
+fn common_commit_version(accepts: &[CommitVersion], dids: &[&str]) -> Option<CommitVersion> {
+    let known = COMMIT_VERSIONS.lock().unwrap();
+    let offers: Vec<&[CommitVersion]> = dids.iter()
+        .map(|d| known.get(*d).map(Vec::as_slice).unwrap_or(&[CommitVersion::V1]))
+        .collect();
+    rps_match_engine::negotiate(accepts, &offers)
 }
 
 /// Helper to mint HS256 JWT for a participant DID and match id.
-fn issue_jwt(did: &str, match_id: &str) -> String {
+fn issue_jwt(did: &str, match_id: &str, commit_version: CommitVersion) -> String {
+    mint_ticket(did, match_id, None, None, false, commit_version, None)
+}
+
+/// Ticket for a bracket match in round `round` of tournament `tid`.
+fn issue_round_jwt(did: &str, match_id: &str, tid: &str, round: u32, commit_version: CommitVersion) -> String {
+    mint_ticket(did, match_id, None, None, false, commit_version, Some((tid, round)))
+}
+
+/// Mints a ticket for a match against a server-side AI seat with DID `bot`
+/// playing `strategy` (fairness default when `None`).
+fn issue_ai_seat_jwt(did: &str, match_id: &str, bot: &str, strategy: Option<&str>, commit_version: CommitVersion) -> String {
+    mint_ticket(did, match_id, Some(bot), strategy, false, commit_version, None)
+}
+
+fn mint_ticket(did: &str, match_id: &str, bot: Option<&str>, strategy: Option<&str>, practice: bool, commit_version: CommitVersion, bracket: Option<(&str, u32)>) -> String {
     let key = std::env::var("TICKET_SECRET").unwrap_or_else(|_| "dev-secret-change-me".into());
     let now = Utc::now();
     let exp = now + Duration::minutes(10);
@@ -79,6 +347,12 @@ fn issue_jwt(did: &str, match_id: &str) -> String {
         mid: match_id.to_string(),
         iat: now.timestamp() as usize,
         exp: exp.timestamp() as usize,
+        bot: bot.map(str::to_string),
+        strategy: strategy.map(str::to_string),
+        practice,
+        commit_version,
+        tid: bracket.map(|(tid, _)| tid.to_string()),
+        round: bracket.map(|(_, round)| round),
     };
     encode(&Header::default(), &claims, &EncodingKey::from_secret(key.as_bytes())).unwrap()
 }
@@ -92,15 +366,46 @@ async fn main() {
         .route("/healthz", get(|| async { "ok" }))
         .route("/ticket", post(issue_ticket))
         .route("/ready_for_round", post(ready_for_round))
+        .route("/practice", post(practice_match))
+        .route("/practice/bots", get(practice_bots))
         .route("/queue_ready", post(queue_ready))
         .route("/queue_cancel", post(queue_cancel))
+        .route("/report_result", post(report_result))
         .route("/register", post(register))
+        .route("/registrations", get(registrations::list))
         .route("/start_round", post(start_round))
         .route("/assignment", get(assignment))
+        .route("/claim", post(claim))
+        .route("/alive_proof", get(alive_proof))
+        .route("/lobby", get(lobby_sse))
         .route("/admin/reset", post(admin_reset))
         .route("/admin/state", get(admin_state))
         .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any));
 
+    // Registrations written to players' own repos (Jetstream or a local replay log)
+    registrations::spawn(add_entrant);
+
+    // Background matchmaker so push-only clients (which do not re-poll) still get paired
+    let tick_ms: u64 = std::env::var("QUEUE_TICK_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(1_000);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_millis(tick_ms));
+        loop {
+            ticker.tick().await;
+            run_matchmaker();
+        }
+    });
+
+    // Ghost sweeper: stale waiters and unclaimed assignments
+    let assignment_ttl_ms: u64 = std::env::var("ASSIGNMENT_TTL_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(15_000);
+    let sweep_ms: u64 = std::env::var("SWEEP_INTERVAL_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(2_000);
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(std::time::Duration::from_millis(sweep_ms));
+        loop {
+            ticker.tick().await;
+            sweep_ghosts(std::time::Duration::from_millis(assignment_ttl_ms));
+        }
+    });
+
     let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8080);
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
     tracing::info!(%addr, "listening");
@@ -108,80 +413,292 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
-// --- Simple in-memory pairing queue (demo only) ---
-// Track waiting DID with timestamp to avoid ghosts
-static WAITING: Lazy<Mutex<Option<(String, Instant)>>> = Lazy::new(|| Mutex::new(None));
+// --- In-memory matchmaking pool (demo only) ---
+static POOL: Lazy<Mutex<MatchPool>> = Lazy::new(|| {
+    Mutex::new(MatchPool::new(PoolConfig::from_env()).with_compatible(|a, b| negotiate_commit_version(&[a, b]).is_some()))
+});
 static ASSIGNMENTS: Lazy<Mutex<std::collections::HashMap<String, ReadyForRoundResp>>> = Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
-// Track assignment insertion time for TTL pruning
+// Track assignment insertion time for TTL pruning (see sweep_ghosts)
 static ASSIGNMENT_TS: Lazy<Mutex<std::collections::HashMap<String, Instant>>> = Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
 static ENTRANTS: Lazy<Mutex<std::collections::HashMap<String, Vec<String>>>> = Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
 static HANDLES: Lazy<Mutex<std::collections::HashMap<String, String>>> = Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+// For unclaimed queue assignments: the partner's queue entry, restored if this DID never shows up
+static PAIRED_WITH: Lazy<Mutex<std::collections::HashMap<String, Waiter>>> = Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+// Commit encodings each DID advertised; missing means v1 only
+static COMMIT_VERSIONS: Lazy<Mutex<std::collections::HashMap<String, Vec<CommitVersion>>>> = Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+// (tid, round) -> sorted alive set and its Merkle tree, for inclusion proofs
+type AliveSet = (Vec<String>, MerkleTree);
+static ALIVE_SETS: Lazy<Mutex<std::collections::HashMap<(String, u32), AliveSet>>> = Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
+// Elo ratings by DID; missing means DEFAULT_RATING
+static RATINGS: Lazy<Mutex<std::collections::HashMap<String, f64>>> = Lazy::new(|| Mutex::new(std::collections::HashMap::new()));
 
 #[derive(Debug, Deserialize)]
-struct QueueReadyReq { tid: String, did: String, handle: Option<String>, ai_if_alone: Option<bool> }
+struct QueueReadyReq {
+    tid: String,
+    did: String,
+    handle: Option<String>,
+    // Optional matchmaking region hint (e.g. "us-central1"); cross-region pairs are allowed after a while
+    region: Option<String>,
+    // Accepted from older clients; the queue never fills in an AI opponent
+    #[allow(dead_code)]
+    ai_if_alone: Option<bool>,
+    // Commit encodings the client speaks; omitted means v1 only
+    #[serde(default, deserialize_with = "known_commit_versions")]
+    commit_versions: Option<Vec<CommitVersion>>,
+}
 
 #[derive(Debug, Serialize)]
-#[serde(tag = "status")]
+#[serde(tag = "status", rename_all = "SCREAMING_SNAKE_CASE")]
 enum QueueReadyResp {
-    WAIT,
-    ASSIGN { match_id: String, role: String, peer: serde_json::Value, ticket: String },
+    Wait { position: usize, waiting: usize, estimated_wait_ms: u64 },
+    Assign { match_id: String, role: String, peer: serde_json::Value, ticket: String, commit_version: CommitVersion },
 }
 
-/// Simple in‑memory pairing queue. Returns WAIT until a second player arrives,
-/// then emits an ASSIGN for both.
-async fn queue_ready(Json(req): Json<QueueReadyReq>) -> Json<QueueReadyResp> {
+/// Rating-aware pairing queue. Returns WAIT (with queue position and an
+/// estimated wait) until a compatible opponent is found, then emits an ASSIGN
+/// for both players.
+async fn queue_ready(Json(req): Json<QueueReadyReq>) -> Result<Json<QueueReadyResp>, (StatusCode, String)> {
     // Record/refresh handle if provided (normal mode sign-in path)
     if let Some(h) = req.handle.as_ref() { HANDLES.lock().unwrap().insert(req.did.clone(), h.clone()); }
+    admit_commit_versions(&req.did, req.commit_versions.as_deref())?;
     // Check if there is an assignment prepared for this DID
-    if let Some(a) = ASSIGNMENTS.lock().unwrap().remove(&req.did) {
-        return Json(QueueReadyResp::ASSIGN { match_id: a.match_id, role: a.role, peer: a.peer, ticket: a.ticket });
+    if let Some(a) = take_assignment(&req.did) {
+        return Ok(Json(QueueReadyResp::Assign { match_id: a.match_id, role: a.role, peer: a.peer, ticket: a.ticket, commit_version: a.commit_version }));
     }
-    let mut w = WAITING.lock().unwrap();
-    if let Some((other, _since)) = w.take() {
-        // If the other waiting DID is the same as this requester, keep waiting
-        if other == req.did {
-            *w = Some((other, Instant::now()));
-            return Json(QueueReadyResp::WAIT);
-        }
-        // Pair other with this did (canonical p1/p2 by sort for match_id stability)
-        let (p1, p2) = if other < req.did { (other.clone(), req.did.clone()) } else { (req.did.clone(), other.clone()) };
-        let match_id = format!("{}-{}-{}", req.tid, p1.replace(':',"_"), p2.replace(':',"_"));
-        let t1 = issue_jwt(&p1, &match_id);
-        let t2 = issue_jwt(&p2, &match_id);
-        // Determine handles if known
-        let (p1h, p2h) = {
-            let h = HANDLES.lock().unwrap();
-            (h.get(&p1).cloned().unwrap_or_else(|| "unknown".into()), h.get(&p2).cloned().unwrap_or_else(|| "unknown".into()))
+    let rating = rating_of(&req.did);
+    let now = Instant::now();
+    let mut pool = POOL.lock().unwrap();
+    pool.enqueue(&req.tid, &req.did, rating, req.region.clone(), now);
+    let wait = |pool: &MatchPool| {
+        let info = pool.wait_info(&req.did, now).expect("waiter was just inserted");
+        Ok(Json(QueueReadyResp::Wait { position: info.position, waiting: info.waiting, estimated_wait_ms: info.estimated_wait.as_millis() as u64 }))
+    };
+    let Some((me, other)) = pool.pair(&req.did, now) else { return wait(&pool) };
+    drop(pool);
+    tracing::info!(tid = %req.tid, a = %me.did, a_rating = me.rating, b = %other.did, b_rating = other.rating, "paired");
+    let Some((mine, theirs)) = pair_assignments(&req.tid, &me.did, &other.did) else {
+        let mut pool = POOL.lock().unwrap();
+        pool.requeue(me, now);
+        pool.requeue(other, now);
+        return wait(&pool);
+    };
+    // The waiting player gets it pushed, or picks it up on their next poll
+    deliver_assignment(&other.did, theirs, Some(me));
+    publish_queue_positions(&req.tid);
+    Ok(Json(QueueReadyResp::Assign { match_id: mine.match_id, role: mine.role, peer: mine.peer, ticket: mine.ticket, commit_version: mine.commit_version }))
+}
+
+/// Pairs every waiter that has an acceptable opponent right now. Runs on a
+/// timer so windows keep widening and push-only clients get matched.
+fn run_matchmaker() {
+    let now = Instant::now();
+    let pairs = POOL.lock().unwrap().pair_all(now);
+    let mut tids: Vec<String> = Vec::new();
+    for (a, b) in pairs {
+        tracing::info!(tid = %a.tid, a = %a.did, a_rating = a.rating, b = %b.did, b_rating = b.rating, "paired");
+        let Some((for_a, for_b)) = pair_assignments(&a.tid, &a.did, &b.did) else {
+            let mut pool = POOL.lock().unwrap();
+            pool.requeue(a, now);
+            pool.requeue(b, now);
+            continue;
+        };
+        deliver_assignment(&a.did, for_a, Some(b.clone()));
+        deliver_assignment(&b.did, for_b, Some(a.clone()));
+        if !tids.contains(&a.tid) { tids.push(a.tid); }
+    }
+    for tid in tids { publish_queue_positions(&tid); }
+}
+
+/// Hands an assignment to a DID. It is always stored until claimed through
+/// `/queue_ready`, `/assignment` or `/claim` (signaling, when the player
+/// joins the match); the lobby push is only a notification.
+/// `partner` is the opponent's queue entry for queue pairings, kept so the
+/// sweeper can re-queue them if this DID never claims the assignment.
+fn deliver_assignment(did: &str, a: ReadyForRoundResp, partner: Option<Waiter>) {
+    let ev = assign_event(&a);
+    ASSIGNMENTS.lock().unwrap().insert(did.to_string(), a);
+    ASSIGNMENT_TS.lock().unwrap().insert(did.to_string(), Instant::now());
+    if let Some(p) = partner { PAIRED_WITH.lock().unwrap().insert(did.to_string(), p); }
+    lobby::publish(did, ev);
+}
+
+fn assign_event(a: &ReadyForRoundResp) -> LobbyEvent {
+    LobbyEvent::Assign { match_id: a.match_id.clone(), role: a.role.clone(), peer: a.peer.clone(), ticket: a.ticket.clone(), commit_version: a.commit_version }
+}
+
+/// Removes and returns a stored assignment, marking it claimed.
+fn take_assignment(did: &str) -> Option<ReadyForRoundResp> {
+    let a = ASSIGNMENTS.lock().unwrap().remove(did)?;
+    ASSIGNMENT_TS.lock().unwrap().remove(did);
+    PAIRED_WITH.lock().unwrap().remove(did);
+    Some(a)
+}
+
+#[derive(Debug, Deserialize)]
+struct ClaimReq { did: String, match_id: String }
+
+#[derive(Debug, Serialize)]
+struct ClaimResp { ok: bool, claimed: bool }
+
+/// Marks `did`'s assignment to `match_id` as claimed. Called by signaling
+/// when the player joins the match, so pushed assignments are not swept.
+/// Internal: requires the `INTERNAL_TOKEN` bearer token.
+async fn claim(headers: HeaderMap, Json(req): Json<ClaimReq>) -> Result<Json<ClaimResp>, StatusCode> {
+    if !internal_call(&headers) { return Err(StatusCode::UNAUTHORIZED); }
+    let pending = ASSIGNMENTS.lock().unwrap().get(&req.did).map(|a| a.match_id == req.match_id).unwrap_or(false);
+    if pending { take_assignment(&req.did); }
+    Ok(Json(ClaimResp { ok: true, claimed: pending }))
+}
+
+/// Whether a request carries the internal service token.
+fn internal_call(headers: &HeaderMap) -> bool {
+    internal::authorized(headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok()))
+}
+
+/// Drops assignment bookkeeping without treating it as claimed.
+fn forget_assignment(did: &str) -> Option<ReadyForRoundResp> {
+    ASSIGNMENT_TS.lock().unwrap().remove(did);
+    PAIRED_WITH.lock().unwrap().remove(did);
+    ASSIGNMENTS.lock().unwrap().remove(did)
+}
+
+/// Background sweep: drops waiters that stopped polling (unless they hold a
+/// lobby stream) and expires assignments nobody picked up. The partner of an
+/// expired queue assignment is told the match is off and re-queued with their
+/// original wait time.
+fn sweep_ghosts(assignment_ttl: std::time::Duration) {
+    let now = Instant::now();
+    let gone = POOL.lock().unwrap().expire(now, lobby::is_subscribed);
+    for w in &gone { tracing::info!(did = %w.did, tid = %w.tid, "expired stale waiter"); }
+    let expired: Vec<String> = ASSIGNMENT_TS.lock().unwrap().iter()
+        .filter(|(_, at)| now.saturating_duration_since(**at) >= assignment_ttl)
+        .map(|(did, _)| did.clone())
+        .collect();
+    let mut tids: Vec<String> = gone.into_iter().map(|w| w.tid).collect();
+    for ghost in expired {
+        let partner = PAIRED_WITH.lock().unwrap().get(&ghost).cloned();
+        let Some(a) = forget_assignment(&ghost) else { continue };
+        tracing::info!(did = %ghost, match_id = %a.match_id, "expired unclaimed assignment");
+        let Some(partner) = partner else {
+            // tournament assignment: the opponent stays assigned and sees OPPONENT_LEFT in signaling
+            notify_abandoned(None, &a.match_id);
+            continue;
         };
-        // Prepare assignment for the waiting player ("other") with the correct role and ticket
-        if other == p1 {
-            ASSIGNMENTS.lock().unwrap().insert(other.clone(), ReadyForRoundResp {
-                match_id: match_id.clone(),
-                role: "P1".into(),
-                peer: serde_json::json!({"did": p2, "handle": p2h}),
-                ticket: t1,
-            });
-            ASSIGNMENT_TS.lock().unwrap().insert(other.clone(), Instant::now());
-            // Return assignment for current requester as P2
-            let resp = QueueReadyResp::ASSIGN { match_id, role: "P2".into(), peer: serde_json::json!({"did": p1, "handle": p1h}), ticket: t2 };
-            return Json(resp);
-        } else {
-            ASSIGNMENTS.lock().unwrap().insert(other.clone(), ReadyForRoundResp {
-                match_id: match_id.clone(),
-                role: "P2".into(),
-                peer: serde_json::json!({"did": p1, "handle": p1h}),
-                ticket: t2,
-            });
-            ASSIGNMENT_TS.lock().unwrap().insert(other.clone(), Instant::now());
-            // Return assignment for current requester as P1
-            let resp = QueueReadyResp::ASSIGN { match_id, role: "P1".into(), peer: serde_json::json!({"did": p2, "handle": p2h}), ticket: t1 };
-            return Json(resp);
+        // If the partner has not picked up their side either, that assignment is dead too
+        let partner_pending = ASSIGNMENTS.lock().unwrap().get(&partner.did).map(|p| p.match_id == a.match_id).unwrap_or(false);
+        if partner_pending { forget_assignment(&partner.did); }
+        if !tids.contains(&partner.tid) { tids.push(partner.tid.clone()); }
+        notify_abandoned(Some(&partner.did), &a.match_id);
+        POOL.lock().unwrap().requeue(partner, now);
+    }
+    tids.sort();
+    tids.dedup();
+    for tid in tids { publish_queue_positions(&tid); }
+}
+
+/// Tells the abandoned side that `match_id` will not happen: a MATCH_CANCELLED
+/// lobby event, plus OPPONENT_LEFT via signaling for players already connected.
+fn notify_abandoned(did: Option<&str>, match_id: &str) {
+    if let Some(did) = did {
+        lobby::publish(did, LobbyEvent::MatchCancelled { match_id: match_id.to_string(), reason: "opponent_timeout".into() });
+    }
+    let sig = std::env::var("SIGNALING_HTTP").unwrap_or_else(|_| "http://localhost:8081".to_string());
+    let body = serde_json::json!({ "match_id": match_id });
+    tokio::spawn(async move {
+        let mut req = HttpClient::new().post(format!("{}/admin/abandon", sig)).json(&body);
+        if let Some(b) = internal::bearer() { req = req.header(AUTHORIZATION, b); }
+        match req.send().await {
+            Ok(res) if !res.status().is_success() => tracing::warn!(status = %res.status(), "signaling abandon rejected"),
+            Ok(_) => {}
+            Err(err) => tracing::warn!(%err, "signaling abandon failed"),
         }
-    } else {
-        // Normal play mode (no AI auto-fill): wait for a peer
-        *w = Some((req.did, Instant::now()));
-        return Json(QueueReadyResp::WAIT);
+    });
+}
+
+/// Pushes a fresh QUEUE_POSITION to every subscribed waiter of `tid`.
+fn publish_queue_positions(tid: &str) {
+    let now = Instant::now();
+    let updates: Vec<(String, queue::WaitInfo)> = {
+        let pool = POOL.lock().unwrap();
+        pool.dids(tid).into_iter().filter_map(|d| pool.wait_info(&d, now).map(|i| (d, i))).collect()
+    };
+    for (did, info) in updates {
+        lobby::publish(&did, LobbyEvent::QueuePosition { tid: tid.to_string(), position: info.position, waiting: info.waiting, estimated_wait_ms: info.estimated_wait.as_millis() as u64 });
+    }
+}
+
+/// Opens the server-push lobby channel for a DID. Any assignment prepared
+/// before the subscription and the current queue standing are replayed first.
+async fn lobby_sse(Query(q): Query<LobbyQuery>) -> impl axum::response::IntoResponse {
+    let (stream, tx) = lobby::subscribe(&q.tid, &q.did);
+    if let Some(a) = ASSIGNMENTS.lock().unwrap().get(&q.did) { let _ = tx.send(assign_event(a)); }
+    if let Some(info) = POOL.lock().unwrap().wait_info(&q.did, Instant::now()) {
+        let _ = tx.send(LobbyEvent::QueuePosition { tid: q.tid.clone(), position: info.position, waiting: info.waiting, estimated_wait_ms: info.estimated_wait.as_millis() as u64 });
+    }
+    lobby::sse_response(stream)
+}
+
+/// Builds the two ASSIGN payloads for a queue pairing, returned in the order
+/// `(for a, for b)`. P1/P2 are canonical by sorted DID for match_id stability.
+/// `None` if the two no longer share a commit version (one re-advertised
+/// after the pool paired them).
+fn pair_assignments(tid: &str, a: &str, b: &str) -> Option<(ReadyForRoundResp, ReadyForRoundResp)> {
+    let (p1, p2) = if a < b { (a, b) } else { (b, a) };
+    let match_id = format!("{}-{}-{}", tid, p1.replace(':', "_"), p2.replace(':', "_"));
+    let (p1h, p2h) = {
+        let h = HANDLES.lock().unwrap();
+        (h.get(p1).cloned().unwrap_or_else(|| "unknown".into()), h.get(p2).cloned().unwrap_or_else(|| "unknown".into()))
+    };
+    let Some(cv) = negotiate_commit_version(&[p1, p2]) else {
+        tracing::warn!(%tid, %p1, %p2, "no common commit version; pairing dropped");
+        return None;
+    };
+    let for_p1 = ReadyForRoundResp {
+        match_id: match_id.clone(),
+        role: "P1".into(),
+        peer: serde_json::json!({"did": p2, "handle": p2h}),
+        ticket: issue_jwt(p1, &match_id, cv),
+        commit_version: cv,
+    };
+    let for_p2 = ReadyForRoundResp {
+        match_id: match_id.clone(),
+        role: "P2".into(),
+        peer: serde_json::json!({"did": p1, "handle": p1h}),
+        ticket: issue_jwt(p2, &match_id, cv),
+        commit_version: cv,
+    };
+    Some(if a == p1 { (for_p1, for_p2) } else { (for_p2, for_p1) })
+}
+
+/// Current Elo rating for a DID.
+fn rating_of(did: &str) -> f64 {
+    RATINGS.lock().unwrap().get(did).copied().unwrap_or(DEFAULT_RATING)
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportResultReq { p1: String, p2: String, winner: String }
+
+#[derive(Debug, Serialize)]
+struct ReportResultResp { ok: bool, p1_rating: f64, p2_rating: f64 }
+
+/// Applies a finished match to both players' Elo ratings. `winner` is
+/// "P1", "P2" or "DRAW". Called by signaling when a match ends; internal only.
+async fn report_result(headers: HeaderMap, Json(req): Json<ReportResultReq>) -> Result<Json<ReportResultResp>, StatusCode> {
+    if !internal_call(&headers) { return Err(StatusCode::UNAUTHORIZED); }
+    // AI seats are unrated; signaling should not report these, but never apply them
+    if practice::is_ai_seat(&req.p1) || practice::is_ai_seat(&req.p2) {
+        let r = RATINGS.lock().unwrap();
+        let rating = |d: &str| r.get(d).copied().unwrap_or(DEFAULT_RATING);
+        return Ok(Json(ReportResultResp { ok: false, p1_rating: rating(&req.p1), p2_rating: rating(&req.p2) }));
     }
+    let score_p1 = match req.winner.as_str() { "P1" => 1.0, "P2" => 0.0, _ => 0.5 };
+    let mut r = RATINGS.lock().unwrap();
+    let ra = r.get(&req.p1).copied().unwrap_or(DEFAULT_RATING);
+    let rb = r.get(&req.p2).copied().unwrap_or(DEFAULT_RATING);
+    let (na, nb) = elo_update(ra, rb, score_p1);
+    r.insert(req.p1, na);
+    r.insert(req.p2, nb);
+    Ok(Json(ReportResultResp { ok: true, p1_rating: na, p2_rating: nb }))
 }
 
 #[derive(Debug, Deserialize)]
@@ -190,89 +707,159 @@ struct QueueCancelReq { did: String }
 #[derive(Debug, Serialize)]
 struct QueueCancelResp { ok: bool, removed: bool }
 
-/// Allows a client to cancel their waiting status in the simple pairing queue.
+/// Allows a client to cancel their waiting status in the pairing queue.
 async fn queue_cancel(Json(req): Json<QueueCancelReq>) -> Json<QueueCancelResp> {
-    // Remove from WAITING if present
-    let mut removed = false;
-    {
-        let mut w = WAITING.lock().unwrap();
-        if let Some((cur, _)) = &*w {
-            if *cur == req.did { *w = None; removed = true; }
-        }
-    }
+    let removed = POOL.lock().unwrap().remove(&req.did).is_some();
     // Also clear any prepared assignment for this DID
-    ASSIGNMENTS.lock().unwrap().remove(&req.did);
-    ASSIGNMENT_TS.lock().unwrap().remove(&req.did);
+    forget_assignment(&req.did);
     Json(QueueCancelResp { ok: true, removed })
 }
 
 // --- Registration & tournament start ---
 #[derive(Debug, Deserialize)]
-struct RegisterReq { tid: String, did: String, handle: Option<String> }
+struct RegisterReq {
+    tid: String,
+    did: String,
+    handle: Option<String>,
+    #[serde(default, deserialize_with = "known_commit_versions")]
+    commit_versions: Option<Vec<CommitVersion>>,
+}
 
 #[derive(Debug, Serialize)]
 struct RegisterResp { ok: bool }
 
 /// Records a DID for a tournament id (tid) and stores handle if provided.
-async fn register(Json(req): Json<RegisterReq>) -> Json<RegisterResp> {
-    let mut e = ENTRANTS.lock().unwrap();
-    let list = e.entry(req.tid).or_default();
-    let did_clone = req.did.clone();
-    if !list.iter().any(|d| d == &req.did) { list.push(req.did.clone()); }
-    if let Some(h) = req.handle { HANDLES.lock().unwrap().insert(did_clone, h); }
-    Json(RegisterResp { ok: true })
+/// Without `ALLOW_DIRECT_REGISTER` (local dev) the DID must already have a
+/// registration record for the tid, and this only sets its handle and commit
+/// versions (403 otherwise).
+async fn register(Json(req): Json<RegisterReq>) -> Result<Json<RegisterResp>, (StatusCode, String)> {
+    let direct = registrations::env_flag("ALLOW_DIRECT_REGISTER", false);
+    if !direct && !registrations::is_registered(&req.tid, &req.did) {
+        return Err((StatusCode::FORBIDDEN, format!("no {} record for this tid; register by writing one to your repo", registrations::NSID)));
+    }
+    admit_commit_versions(&req.did, req.commit_versions.as_deref())?;
+    if let Some(h) = req.handle { HANDLES.lock().unwrap().insert(req.did.clone(), h); }
+    if direct { registrations::admit_direct(&req.tid, &req.did); }
+    add_entrant(&req.tid, &req.did);
+    Ok(Json(RegisterResp { ok: true }))
+}
+
+/// Adds a DID to a tournament's entrants and pushes the new count to the lobby.
+fn add_entrant(tid: &str, did: &str) {
+    let entrants = {
+        let mut e = ENTRANTS.lock().unwrap();
+        let list = e.entry(tid.to_string()).or_default();
+        if !list.iter().any(|d| d == did) { list.push(did.to_string()); }
+        list.len()
+    };
+    lobby::publish_tid(tid, LobbyEvent::TournamentUpdate { tid: tid.to_string(), entrants });
 }
 
 #[derive(Debug, Deserialize)]
 struct StartRoundReq { tid: String, round: u32 }
 
 #[derive(Debug, Serialize)]
-struct StartRoundResp { ok: bool, pairs: usize }
+struct StartRoundResp { ok: bool, pairs: usize, pairing_seed: String, drand_round: u64, alive_root: String }
 
-/// Creates deterministic P1/P2 assignments for entrants of a given tid/round.
+/// Creates P1/P2 assignments for entrants of a given tid/round. Entrants are
+/// sorted, then shuffled by a pairing seed bound to a drand round, so the
+/// pairing is unpredictable in advance but reproducible afterwards.
 async fn start_round(Json(req): Json<StartRoundReq>) -> Json<StartRoundResp> {
-    let mut e = ENTRANTS.lock().unwrap();
-    let mut list = e.remove(&req.tid).unwrap_or_default();
+    let mut list = ENTRANTS.lock().unwrap().remove(&req.tid).unwrap_or_default();
     list.sort();
-    let mut i = 0usize; let mut pairs = 0usize;
-    while i + 1 <= list.len() {
-        let p1 = list.get(i).cloned(); i += 1;
-        if let Some(p1did) = p1 {
-            if i < list.len() {
-                let p2did = list[i].clone(); i += 1;
-                let mid = format!("{}-r{}-{}-{}", req.tid, req.round, p1did.replace(':',"_"), p2did.replace(':',"_"));
-                let t1 = issue_jwt(&p1did, &mid);
-                let t2 = issue_jwt(&p2did, &mid);
-                let h = HANDLES.lock().unwrap();
-                let p1h = h.get(&p1did).cloned().unwrap_or_else(|| "unknown".into());
-                let p2h = h.get(&p2did).cloned().unwrap_or_else(|| "unknown".into());
-                ASSIGNMENTS.lock().unwrap().insert(p1did.clone(), ReadyForRoundResp { match_id: mid.clone(), role: "P1".into(), peer: serde_json::json!({"did": p2did, "handle": p2h}), ticket: t1 });
-                ASSIGNMENTS.lock().unwrap().insert(p2did.clone(), ReadyForRoundResp { match_id: mid.clone(), role: "P2".into(), peer: serde_json::json!({"did": p1did, "handle": p1h}), ticket: t2 });
-                pairs += 1;
-            } else {
-                // odd -> AI seat
+    let (drand_round, seed) = pairing_seed(&req.tid, req.round).await;
+    seeded_shuffle(&mut list, &seed);
+    let mut prepared: Vec<(String, ReadyForRoundResp)> = Vec::new();
+    let mut pairs = 0usize;
+    // byes stay uniform unless configured: a stronger bot would make the bye a penalty
+    let bye_strategy = std::env::var("AI_BYE_STRATEGY").ok();
+    {
+        let h = HANDLES.lock().unwrap();
+        for chunk in list.chunks(2) {
+            if let [p1did, p2did] = chunk {
+                if let Some(cv) = negotiate_commit_version(&[p1did, p2did]) {
+                    let mid = format!("{}-r{}-{}-{}", req.tid, req.round, p1did.replace(':',"_"), p2did.replace(':',"_"));
+                    let p1h = h.get(p1did).cloned().unwrap_or_else(|| "unknown".into());
+                    let p2h = h.get(p2did).cloned().unwrap_or_else(|| "unknown".into());
+                    prepared.push((p1did.clone(), ReadyForRoundResp { match_id: mid.clone(), role: "P1".into(), peer: serde_json::json!({"did": p2did, "handle": p2h}), ticket: issue_round_jwt(p1did, &mid, &req.tid, req.round, cv), commit_version: cv }));
+                    prepared.push((p2did.clone(), ReadyForRoundResp { match_id: mid.clone(), role: "P2".into(), peer: serde_json::json!({"did": p1did, "handle": p1h}), ticket: issue_round_jwt(p2did, &mid, &req.tid, req.round, cv), commit_version: cv }));
+                    pairs += 1;
+                    continue;
+                }
+                tracing::warn!(tid = %req.tid, p1 = %p1did, p2 = %p2did, "no common commit version; both play the AI seat");
+            }
+            // odd one out, or a pair that cannot agree on a commit version -> AI seat
+            for p1did in chunk {
+                let Some(cv) = negotiate_commit_version(&[p1did]) else {
+                    tracing::warn!(tid = %req.tid, did = %p1did, "no commit version in common with the server; not seated");
+                    continue;
+                };
                 let mid = format!("{}-r{}-{}-AI", req.tid, req.round, p1did.replace(':',"_"));
-                let t1 = issue_jwt(&p1did, &mid);
-                ASSIGNMENTS.lock().unwrap().insert(p1did.clone(), ReadyForRoundResp { match_id: mid.clone(), role: "P1".into(), peer: serde_json::json!({"did": "AI", "handle": "AI_BYE"}), ticket: t1 });
+                prepared.push((p1did.clone(), ReadyForRoundResp { match_id: mid.clone(), role: "P1".into(), peer: serde_json::json!({"did": AI_BYE_DID, "handle": "AI_BYE"}), ticket: issue_ai_seat_jwt(p1did, &mid, AI_BYE_DID, bye_strategy.as_deref(), cv), commit_version: cv }));
             }
         }
     }
-    Json(StartRoundResp { ok: true, pairs })
+    lobby::publish_tid(&req.tid, LobbyEvent::RoundStarting { tid: req.tid.clone(), round: req.round, pairs });
+    let match_ids: Vec<String> = prepared.iter().filter(|(_, a)| a.role == "P1").map(|(_, a)| a.match_id.clone()).collect();
+    let roots = record_round(&req.tid, req.round, list, &match_ids);
+    let alive_root = roots.alive.clone();
+    // a round with just one human pair is the final
+    let announcement = match prepared.as_slice() {
+        [(p1, a1), (p2, a2)] if pairs == 1 => serde_json::json!({
+            "kind": "finals", "tid": req.tid,
+            "p1": { "did": p1, "handle": a2.peer["handle"] },
+            "p2": { "did": p2, "handle": a1.peer["handle"] },
+        }),
+        _ => serde_json::json!({ "kind": "round_start", "tid": req.tid, "round": req.round, "pairs": pairs }),
+    };
+    for (did, a) in prepared { deliver_assignment(&did, a, None); }
+    post_round_anchor(&req.tid, req.round, &seed, drand_round, roots).await;
+    post_announcement(announcement).await;
+    Json(StartRoundResp { ok: true, pairs, pairing_seed: seed, drand_round, alive_root })
 }
 
 #[derive(Debug, Deserialize)]
-struct AssignmentQuery { tid: String, did: String }
+struct AliveProofQuery { tid: String, round: u32, did: String }
 
 #[derive(Debug, Serialize)]
-#[serde(tag = "status")]
-enum AssignmentResp { WAIT, ASSIGN { match_id: String, role: String, peer: serde_json::Value, ticket: String } }
+struct AliveProofResp { alive_root: String, index: usize, leaf_count: usize, siblings: Vec<String> }
+
+/// Inclusion proof that `did` was in the round's alive set (the anchor's
+/// `aliveRoot`). Leaves are the sorted DIDs; verify with `rps_crypto::merkle::verify`
+/// in positional mode.
+async fn alive_proof(Query(q): Query<AliveProofQuery>) -> Result<Json<AliveProofResp>, (axum::http::StatusCode, String)> {
+    let sets = ALIVE_SETS.lock().unwrap();
+    let Some((alive, tree)) = sets.get(&(q.tid.clone(), q.round)) else {
+        return Err((axum::http::StatusCode::NOT_FOUND, "unknown round".into()));
+    };
+    let proof = alive.iter().position(|d| d == &q.did).and_then(|i| tree.proof(i))
+        .ok_or((axum::http::StatusCode::NOT_FOUND, "DID not in alive set".into()))?;
+    Ok(Json(AliveProofResp {
+        alive_root: hex::encode(tree.root()),
+        index: proof.index,
+        leaf_count: proof.leaf_count,
+        siblings: proof.siblings.iter().map(hex::encode).collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+struct AssignmentQuery {
+    // accepted for API symmetry with /register; assignments are keyed by DID only
+    #[allow(dead_code)]
+    tid: String,
+    did: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "SCREAMING_SNAKE_CASE")]
+enum AssignmentResp { Wait, Assign { match_id: String, role: String, peer: serde_json::Value, ticket: String } }
 
 /// Polls for a prepared assignment for the given DID. Returns WAIT if none.
 async fn assignment(Query(q): Query<AssignmentQuery>) -> Json<AssignmentResp> {
-    if let Some(a) = ASSIGNMENTS.lock().unwrap().remove(&q.did) {
-        Json(AssignmentResp::ASSIGN { match_id: a.match_id, role: a.role, peer: a.peer, ticket: a.ticket })
+    if let Some(a) = take_assignment(&q.did) {
+        Json(AssignmentResp::Assign { match_id: a.match_id, role: a.role, peer: a.peer, ticket: a.ticket })
     } else {
-        Json(AssignmentResp::WAIT)
+        Json(AssignmentResp::Wait)
     }
 }
 
@@ -294,26 +881,29 @@ async fn admin_reset(Json(req): Json<AdminResetReq>) -> Json<AdminResetResp> {
         };
         cleared_dids = dids.len();
         // Clear assignments for these DIDs
-        if !dids.is_empty() {
-            let mut a = ASSIGNMENTS.lock().unwrap();
-            for d in &dids { a.remove(d); }
-        }
+        for d in &dids { forget_assignment(d); }
         // Clear handles for these DIDs
         if !dids.is_empty() {
             let mut h = HANDLES.lock().unwrap();
             for d in &dids { h.remove(d); }
+            let mut cv = COMMIT_VERSIONS.lock().unwrap();
+            for d in &dids { cv.remove(d); }
         }
-        // If waiting contains one of these DIDs, clear it
-        {
-            let mut w = WAITING.lock().unwrap();
-            if let Some((cur, _)) = &*w { if dids.iter().any(|d| d == cur) { *w = None; } }
-        }
+        // Drop any of these DIDs from the matchmaking pool
+        POOL.lock().unwrap().retain(|w| !dids.contains(&w.did));
+        ALIVE_SETS.lock().unwrap().retain(|(t, _), _| *t != tid);
+        registrations::clear(Some(&tid));
     } else {
         // Full wipe
         ENTRANTS.lock().unwrap().clear();
         HANDLES.lock().unwrap().clear();
+        COMMIT_VERSIONS.lock().unwrap().clear();
         ASSIGNMENTS.lock().unwrap().clear();
-        *WAITING.lock().unwrap() = None;
+        ASSIGNMENT_TS.lock().unwrap().clear();
+        PAIRED_WITH.lock().unwrap().clear();
+        POOL.lock().unwrap().clear();
+        ALIVE_SETS.lock().unwrap().clear();
+        registrations::clear(None);
     }
     // pairs cleared is approximate: number of assignment entries removed in this call
     let cleared_pairs = cleared_dids / 2;
@@ -325,11 +915,12 @@ struct AdminStateResp {
     entrants_tids: usize,
     total_entrants: usize,
     waiting_present: bool,
+    waiting: usize,
     assignments: usize,
     handles: usize,
 }
 
-/// Admin: returns counts of entrants, waiting flag, assignments, and handles.
+/// Admin: returns counts of entrants, waiters, assignments, and handles.
 async fn admin_state() -> Json<AdminStateResp> {
     let entrants_tids = ENTRANTS.lock().unwrap().len();
     let total_entrants: usize = ENTRANTS
@@ -338,8 +929,191 @@ async fn admin_state() -> Json<AdminStateResp> {
         .values()
         .map(|v| v.len())
         .sum();
-    let waiting_present = WAITING.lock().unwrap().is_some();
+    let (waiting, waiting_present) = { let p = POOL.lock().unwrap(); (p.len(), !p.is_empty()) };
     let assignments = ASSIGNMENTS.lock().unwrap().len();
     let handles = HANDLES.lock().unwrap().len();
-    Json(AdminStateResp { entrants_tids, total_entrants, waiting_present, assignments, handles })
+    Json(AdminStateResp { entrants_tids, total_entrants, waiting_present, waiting, assignments, handles })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn internal_headers() -> HeaderMap {
+        std::env::set_var(internal::ENV, "test-token");
+        let mut h = HeaderMap::new();
+        h.insert(AUTHORIZATION, "Bearer test-token".parse().unwrap());
+        h
+    }
+
+    #[tokio::test]
+    async fn background_tick_pairs_push_only_clients() {
+        let (a, b) = ("did:plc:tick-a", "did:plc:tick-b");
+        let (_ta, mut rx_a) = lobby::register("tick", a);
+        let (_tb, mut rx_b) = lobby::register("tick", b);
+        {
+            let mut pool = POOL.lock().unwrap();
+            pool.enqueue("tick", a, 1500.0, None, Instant::now());
+            pool.enqueue("tick", b, 1500.0, None, Instant::now());
+        }
+        run_matchmaker();
+        let mid = "tick-did_plc_tick-a-did_plc_tick-b";
+        for rx in [&mut rx_a, &mut rx_b] {
+            let pushed = std::iter::from_fn(|| rx.try_recv().ok()).find_map(|ev| match ev { LobbyEvent::Assign { match_id, .. } => Some(match_id), _ => None });
+            assert_eq!(pushed.as_deref(), Some(mid));
+        }
+        // the push is only a notification: both assignments wait to be claimed
+        assert_eq!(ASSIGNMENTS.lock().unwrap().get(a).map(|x| x.role.as_str()), Some("P1"));
+        assert!(ASSIGNMENT_TS.lock().unwrap().contains_key(b));
+        let req = |m: &str| Json(ClaimReq { did: a.into(), match_id: m.into() });
+        assert_eq!(claim(HeaderMap::new(), req(mid)).await.err(), Some(StatusCode::UNAUTHORIZED));
+        let Json(resp) = claim(internal_headers(), req("another-match")).await.unwrap();
+        assert!(!resp.claimed);
+        let Json(resp) = claim(internal_headers(), req(mid)).await.unwrap();
+        assert!(resp.claimed);
+        assert!(!ASSIGNMENTS.lock().unwrap().contains_key(a));
+        assert!(take_assignment(b).is_some());
+    }
+
+    #[test]
+    fn unknown_commit_versions_are_dropped() {
+        let req: QueueReadyReq = serde_json::from_str(r#"{"tid": "t", "did": "d", "commit_versions": ["v2", "v9", "v1"]}"#).unwrap();
+        assert_eq!(req.commit_versions, Some(vec![CommitVersion::V2, CommitVersion::V1]));
+        let req: QueueReadyReq = serde_json::from_str(r#"{"tid": "t", "did": "d", "commit_versions": ["v9"]}"#).unwrap();
+        assert_eq!(req.commit_versions, Some(vec![]));
+        for body in [r#"{"tid": "t", "did": "d"}"#, r#"{"tid": "t", "did": "d", "commit_versions": []}"#] {
+            assert_eq!(serde_json::from_str::<QueueReadyReq>(body).unwrap().commit_versions, None);
+        }
+    }
+
+    #[test]
+    fn clients_without_a_common_commit_version_are_refused() {
+        use CommitVersion::{V1, V2};
+        let (old, new, future) = ("did:plc:cv-old", "did:plc:cv-new", "did:plc:cv-future");
+        assert_eq!(admit_commit_versions(old, None), Ok(V1));
+        assert_eq!(admit_commit_versions(new, Some(&[V2])), Ok(V2));
+        assert_eq!(admit_commit_versions(future, Some(&[])).unwrap_err().0, StatusCode::BAD_REQUEST);
+        // a refused offer does not replace what the client advertised before
+        assert_eq!(admit_commit_versions(new, Some(&[])).unwrap_err().0, StatusCode::BAD_REQUEST);
+        assert_eq!(common_commit_version(&[V1, V2], &[new]), Some(V2));
+        // server policy wins over the old v1 fallback
+        assert_eq!(common_commit_version(&[V2], &[old]), None);
+        assert_eq!(common_commit_version(&[V1, V2], &[old, new]), None);
+        assert!(pair_assignments("cv", old, new).is_none());
+
+        let mut pool = MatchPool::new(PoolConfig::from_env()).with_compatible(|a, b| negotiate_commit_version(&[a, b]).is_some());
+        let now = Instant::now();
+        pool.enqueue("cv", old, 1500.0, None, now);
+        pool.enqueue("cv", new, 1500.0, None, now);
+        assert!(pool.pair_all(now).is_empty());
+        assert_eq!(pool.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn register_needs_a_registration_record() {
+        let did = "did:plc:gated";
+        let req = || Json(RegisterReq { tid: "gated".into(), did: did.into(), handle: Some("gated.test".into()), commit_versions: None });
+        assert_eq!(register(req()).await.unwrap_err().0, StatusCode::FORBIDDEN);
+        assert!(!ENTRANTS.lock().unwrap().contains_key("gated"));
+
+        let ev = serde_json::from_value(serde_json::json!({
+            "did": did, "time_us": 1, "kind": "commit",
+            "commit": { "rev": "r", "operation": "create", "collection": registrations::NSID, "rkey": "k",
+                "record": { "$type": registrations::NSID, "tid": "gated", "did": did, "createdAt": "2026-10-18T12:00:00Z" } }
+        })).unwrap();
+        registrations::ingest(&reqwest::Client::new(), &ev, false).await.unwrap();
+        assert!(register(req()).await.unwrap().ok);
+        assert_eq!(ENTRANTS.lock().unwrap()["gated"], [did]);
+        assert_eq!(HANDLES.lock().unwrap()[did], "gated.test");
+    }
+
+    #[test]
+    fn only_bracket_tickets_carry_tid_and_round() {
+        let claims = |t: &str| {
+            let key = std::env::var("TICKET_SECRET").unwrap_or_else(|_| "dev-secret-change-me".into());
+            let v = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::HS256);
+            jsonwebtoken::decode::<Claims>(t, &jsonwebtoken::DecodingKey::from_secret(key.as_bytes()), &v).unwrap().claims
+        };
+        let c = claims(&issue_round_jwt("did:plc:a", "cup-r3-x-y", "cup", 3, CommitVersion::V1));
+        assert_eq!((c.tid.as_deref(), c.round), (Some("cup"), Some(3)));
+        let c = claims(&issue_jwt("did:plc:a", "cup-r3-x-y", CommitVersion::V1));
+        assert_eq!((c.tid, c.round), (None, None));
+    }
+
+    #[tokio::test]
+    async fn results_against_ai_seats_are_not_rated() {
+        let human = "did:plc:rated-human";
+        let report = |p1: &str, p2: &str| Json(ReportResultReq { p1: p1.into(), p2: p2.into(), winner: "P1".into() });
+        for ai in ["bot:wally", AI_BYE_DID] {
+            let Json(resp) = report_result(internal_headers(), report(human, ai)).await.unwrap();
+            assert!(!resp.ok);
+            let Json(resp) = report_result(internal_headers(), report(ai, human)).await.unwrap();
+            assert!(!resp.ok);
+        }
+        assert!(!RATINGS.lock().unwrap().contains_key(human));
+        assert!(!RATINGS.lock().unwrap().contains_key(AI_BYE_DID));
+        let Json(resp) = report_result(internal_headers(), report(human, "did:plc:rated-rival")).await.unwrap();
+        assert!(resp.ok && resp.p1_rating > DEFAULT_RATING);
+    }
+
+    #[tokio::test]
+    async fn round_anchors_are_retried_on_server_errors() {
+        use std::sync::atomic::{AtomicU32, Ordering};
+        static HITS: AtomicU32 = AtomicU32::new(0);
+        // fails the first two tries for this tid; other tests' anchors are accepted
+        let app = Router::new().route("/round_anchor", post(|Json(a): Json<serde_json::Value>| async move {
+            if a["tid"] != "anchor-retry" { return StatusCode::ACCEPTED; }
+            if HITS.fetch_add(1, Ordering::SeqCst) < 2 { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::ACCEPTED }
+        }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        std::env::set_var("ATPROTO_WRITER_HTTP", format!("http://{}", listener.local_addr().unwrap()));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        let roots = RoundRoots { alive: "00".into(), pairings: "00".into() };
+        post_round_anchor("anchor-retry", 1, "00", 1, roots).await;
+        assert_eq!(HITS.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn results_need_the_internal_token() {
+        let (a, b) = ("did:plc:forged-a", "did:plc:forged-b");
+        let req = || Json(ReportResultReq { p1: a.into(), p2: b.into(), winner: "P1".into() });
+        internal_headers();
+        let mut forged = HeaderMap::new();
+        forged.insert(AUTHORIZATION, "Bearer wrong".parse().unwrap());
+        for headers in [HeaderMap::new(), forged] {
+            assert_eq!(report_result(headers, req()).await.err(), Some(StatusCode::UNAUTHORIZED));
+        }
+        let r = RATINGS.lock().unwrap();
+        assert!(!r.contains_key(a) && !r.contains_key(b));
+    }
+
+    #[tokio::test]
+    async fn sweeper_requeues_the_partner_of_an_unclaimed_assignment() {
+        let (ghost, partner, fresh) = ("did:plc:sweep-ghost", "did:plc:sweep-partner", "did:plc:sweep-fresh");
+        let (_tx, mut rx) = lobby::register("sweep", partner);
+        let t0 = Instant::now();
+        let waiter = |did: &str| Waiter { tid: "sweep".into(), did: did.into(), rating: 1500.0, region: None, since: t0, expires_at: t0 };
+        let (for_ghost, for_partner) = pair_assignments("sweep", ghost, partner).unwrap();
+        let mid = for_ghost.match_id.clone();
+        deliver_assignment(ghost, for_ghost, Some(waiter(partner)));
+        deliver_assignment(partner, for_partner, Some(waiter(ghost)));
+        let (for_fresh, _) = pair_assignments("sweep-fresh", fresh, "did:plc:sweep-other").unwrap();
+        deliver_assignment(fresh, for_fresh, None);
+        let old = Instant::now() - std::time::Duration::from_secs(120);
+        ASSIGNMENT_TS.lock().unwrap().insert(ghost.into(), old);
+
+        sweep_ghosts(std::time::Duration::from_secs(60));
+
+        // both sides of the dead match are gone; an unexpired assignment stays
+        assert!(!ASSIGNMENTS.lock().unwrap().contains_key(ghost));
+        assert!(!ASSIGNMENTS.lock().unwrap().contains_key(partner));
+        assert!(!PAIRED_WITH.lock().unwrap().contains_key(partner));
+        assert!(take_assignment(fresh).is_some());
+        // the partner is back in the queue with their original wait
+        assert_eq!(POOL.lock().unwrap().dids("sweep"), [partner]);
+        assert!(POOL.lock().unwrap().wait_info(partner, Instant::now()).is_some());
+        let cancelled = std::iter::from_fn(|| rx.try_recv().ok()).find_map(|ev| match ev { LobbyEvent::MatchCancelled { match_id, .. } => Some(match_id), _ => None });
+        assert_eq!(cancelled, Some(mid));
+        POOL.lock().unwrap().remove(partner);
+    }
 }
@@ -0,0 +1,91 @@
+//! Practice matches against named bots.
+//!
+//! A bot is a server-side AI seat (run by signaling) with a persona and a
+//! difficulty that selects the fairness strategy it plays. Practice tickets are
+//! marked so signaling never reports their results for rating updates, and
+//! [`is_ai_seat`] keeps any result naming a bot or the bye AI out of ratings.
+
+use serde::{Deserialize, Serialize};
+
+/// DID prefix for practice bots; results involving these DIDs are never rated.
+pub const BOT_DID_PREFIX: &str = "bot:";
+
+/// DID used for the AI seat in byes and `/ready_for_round` demo matches.
+pub const AI_BYE_DID: &str = "AI";
+
+/// A practice opponent. `avatar` is served by the web app from `public/bots`.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Bot {
+    pub name: &'static str,
+    pub handle: &'static str,
+    pub avatar: &'static str,
+}
+
+/// Bots available for practice.
+pub const BOTS: &[Bot] = &[Bot { name: "wally", handle: "Wally", avatar: "/bots/wally.png" }];
+
+/// Looks up a bot by name (case-insensitive).
+pub fn bot(name: &str) -> Option<&'static Bot> {
+    BOTS.iter().find(|b| b.name.eq_ignore_ascii_case(name.trim()))
+}
+
+impl Bot {
+    pub fn did(&self) -> String { format!("{}{}", BOT_DID_PREFIX, self.name) }
+}
+
+/// Practice difficulty; each level maps to a fairness strategy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Difficulty {
+    /// Easy plays uniformly at random, medium counters the player's most
+    /// frequent move, hard runs Iocaine Powder.
+    pub fn strategy(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "uniform",
+            Difficulty::Medium => "frequency",
+            Difficulty::Hard => "iocaine",
+        }
+    }
+}
+
+/// True if `did` is an AI seat (a practice bot or the bye AI). Matches with
+/// one are unrated.
+pub fn is_ai_seat(did: &str) -> bool { did.starts_with(BOT_DID_PREFIX) || did == AI_BYE_DID }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn difficulty_selects_a_strategy() {
+        assert_eq!(Difficulty::default(), Difficulty::Medium);
+        let parsed: Vec<Difficulty> = serde_json::from_str(r#"["easy", "medium", "hard"]"#).unwrap();
+        let strategies: Vec<&str> = parsed.into_iter().map(Difficulty::strategy).collect();
+        assert_eq!(strategies, ["uniform", "frequency", "iocaine"]);
+        assert!(serde_json::from_str::<Difficulty>(r#""nightmare""#).is_err());
+    }
+
+    #[test]
+    fn looks_up_bots_by_name() {
+        let wally = bot(" WALLY ").unwrap();
+        assert_eq!(wally.did(), "bot:wally");
+        assert!(bot("hal").is_none());
+        assert!(BOTS.iter().all(|b| is_ai_seat(&b.did())));
+    }
+
+    #[test]
+    fn only_bots_and_the_bye_ai_are_ai_seats() {
+        assert!(is_ai_seat("bot:wally"));
+        assert!(is_ai_seat(AI_BYE_DID));
+        assert!(!is_ai_seat("did:plc:alice"));
+        assert!(!is_ai_seat("ai"));
+        assert!(!is_ai_seat("did:plc:bot:wally"));
+    }
+}
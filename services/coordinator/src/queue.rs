@@ -0,0 +1,435 @@
+//! Rating-aware matchmaking pool.
+//!
+//! Waiters are paired within their tournament id by rating distance. Each
+//! waiter's acceptable window starts at `base_window` and widens linearly with
+//! time spent waiting (capped at `max_window`), so long waiters eventually get
+//! a game even in a sparse pool. Region and recent-opponent constraints are
+//! soft: they are relaxed once a waiter has been waiting long enough.
+
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Default rating for DIDs we have never seen a result for.
+pub const DEFAULT_RATING: f64 = 1500.0;
+
+/// Tunables for the pool. Read once from the environment at startup.
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    /// Rating window for a waiter that just arrived.
+    pub base_window: f64,
+    /// Window growth per second of waiting.
+    pub widen_per_sec: f64,
+    /// Upper bound for the window.
+    pub max_window: f64,
+    /// After this long, a waiter accepts opponents from any region.
+    pub region_relax_after: Duration,
+    /// Do not re-pair the same two DIDs within this period...
+    pub rematch_cooldown: Duration,
+    /// ...unless one of them has already waited this long.
+    pub rematch_relax_after: Duration,
+    /// Estimate used before any pairing has been observed.
+    pub default_wait: Duration,
+    /// A waiter that neither polls nor holds a lobby stream for this long is dropped.
+    pub waiter_ttl: Duration,
+    /// Keepalive granted to a partner re-queued after their opponent vanished,
+    /// long enough for them to leave the dead match and poll again.
+    pub requeue_grace: Duration,
+}
+
+impl PoolConfig {
+    /// Builds config from `QUEUE_*` env vars, falling back to demo-friendly defaults.
+    pub fn from_env() -> Self {
+        let f = |k: &str, d: f64| std::env::var(k).ok().and_then(|v| v.parse().ok()).unwrap_or(d);
+        let ms = |k: &str, d: u64| Duration::from_millis(std::env::var(k).ok().and_then(|v| v.parse().ok()).unwrap_or(d));
+        PoolConfig {
+            base_window: f("QUEUE_BASE_WINDOW", 100.0),
+            widen_per_sec: f("QUEUE_WIDEN_PER_SEC", 25.0),
+            max_window: f("QUEUE_MAX_WINDOW", 1000.0),
+            region_relax_after: ms("QUEUE_REGION_RELAX_MS", 10_000),
+            rematch_cooldown: ms("QUEUE_REMATCH_COOLDOWN_MS", 300_000),
+            rematch_relax_after: ms("QUEUE_REMATCH_RELAX_MS", 30_000),
+            default_wait: ms("QUEUE_DEFAULT_WAIT_MS", 15_000),
+            waiter_ttl: ms("QUEUE_WAITER_TTL_MS", 10_000),
+            requeue_grace: ms("QUEUE_REQUEUE_GRACE_MS", 45_000),
+        }
+    }
+}
+
+/// A single queued player.
+#[derive(Debug, Clone)]
+pub struct Waiter {
+    pub tid: String,
+    pub did: String,
+    pub rating: f64,
+    pub region: Option<String>,
+    /// When the player first entered the queue; preserved across re-polls
+    /// and when re-queued after an abandoned pairing.
+    pub since: Instant,
+    /// Dropped by the sweeper after this unless kept alive.
+    pub expires_at: Instant,
+}
+
+/// Queue snapshot for a waiter, reported in the WAIT response.
+#[derive(Debug, Clone, Copy)]
+pub struct WaitInfo {
+    /// 1-based position among waiters of the same tid, oldest first.
+    pub position: usize,
+    /// Number of waiters in the same tid.
+    pub waiting: usize,
+    pub estimated_wait: Duration,
+}
+
+/// The matchmaking pool. Waiters are kept in arrival order.
+#[derive(Debug)]
+pub struct MatchPool {
+    config: PoolConfig,
+    waiters: Vec<Waiter>,
+    // did -> recent opponents with pairing time, newest last
+    recent: HashMap<String, VecDeque<(String, Instant)>>,
+    // exponentially weighted average of observed waits, in ms
+    wait_ewma_ms: Option<f64>,
+    // extra constraint on a pair of DIDs (e.g. a shared commit encoding)
+    compatible: fn(&str, &str) -> bool,
+}
+
+impl MatchPool {
+    pub fn new(config: PoolConfig) -> Self {
+        MatchPool { config, waiters: Vec::new(), recent: HashMap::new(), wait_ewma_ms: None, compatible: |_, _| true }
+    }
+
+    /// Only pairs DIDs for which `compatible` holds, on top of the queue's own rules.
+    pub fn with_compatible(mut self, compatible: fn(&str, &str) -> bool) -> Self {
+        self.compatible = compatible;
+        self
+    }
+
+    /// Adds a waiter, or refreshes rating/region/keepalive of an existing one
+    /// while keeping their original `since`.
+    pub fn enqueue(&mut self, tid: &str, did: &str, rating: f64, region: Option<String>, now: Instant) {
+        let expires_at = now + self.config.waiter_ttl;
+        if let Some(cur) = self.waiters.iter_mut().find(|c| c.did == did) {
+            cur.tid = tid.to_string();
+            cur.rating = rating;
+            cur.region = region;
+            cur.expires_at = cur.expires_at.max(expires_at);
+        } else {
+            self.insert(Waiter { tid: tid.to_string(), did: did.to_string(), rating, region, since: now, expires_at });
+        }
+    }
+
+    /// Puts a previously paired waiter back with their original `since`, so
+    /// they rank ahead of everyone who joined after them.
+    pub fn requeue(&mut self, mut w: Waiter, now: Instant) {
+        if self.waiters.iter().any(|c| c.did == w.did) { return; }
+        w.expires_at = now + self.config.requeue_grace;
+        self.insert(w);
+    }
+
+    fn insert(&mut self, w: Waiter) {
+        let at = self.waiters.partition_point(|c| c.since <= w.since);
+        self.waiters.insert(at, w);
+    }
+
+    /// Removes and returns waiters past their keepalive. `keep_alive` is asked
+    /// about each stale DID first; returning true extends it by one TTL.
+    pub fn expire(&mut self, now: Instant, keep_alive: impl Fn(&str) -> bool) -> Vec<Waiter> {
+        let ttl = self.config.waiter_ttl;
+        let mut gone = Vec::new();
+        self.waiters.retain_mut(|w| {
+            if w.expires_at > now { return true; }
+            if keep_alive(&w.did) { w.expires_at = now + ttl; return true; }
+            gone.push(w.clone());
+            false
+        });
+        gone
+    }
+
+    pub fn remove(&mut self, did: &str) -> Option<Waiter> {
+        let idx = self.waiters.iter().position(|w| w.did == did)?;
+        Some(self.waiters.remove(idx))
+    }
+
+    /// Drops every waiter for which `keep` returns false.
+    pub fn retain(&mut self, keep: impl FnMut(&Waiter) -> bool) {
+        self.waiters.retain(keep);
+    }
+
+    pub fn clear(&mut self) {
+        self.waiters.clear();
+        self.recent.clear();
+    }
+
+    pub fn len(&self) -> usize { self.waiters.len() }
+
+    pub fn is_empty(&self) -> bool { self.waiters.is_empty() }
+
+    /// Rating window for a player who has waited `waited`.
+    pub fn window(&self, waited: Duration) -> f64 {
+        let c = &self.config;
+        (c.base_window + c.widen_per_sec * waited.as_secs_f64()).min(c.max_window)
+    }
+
+    /// Tries to pair `did` with the best acceptable opponent. On success both
+    /// waiters are removed from the pool and returned as `(did's entry, opponent)`.
+    pub fn pair(&mut self, did: &str, now: Instant) -> Option<(Waiter, Waiter)> {
+        let me = self.waiters.iter().find(|w| w.did == did)?;
+        let mut best: Option<(usize, f64)> = None;
+        for (i, other) in self.waiters.iter().enumerate() {
+            if other.did == me.did || other.tid != me.tid { continue; }
+            if !self.acceptable(me, other, now) { continue; }
+            let diff = (me.rating - other.rating).abs();
+            // strictly-less keeps the longest waiter on ties (arrival order)
+            if best.map(|(_, d)| diff < d).unwrap_or(true) { best = Some((i, diff)); }
+        }
+        let (idx, _) = best?;
+        let other = self.waiters.remove(idx);
+        let me = self.remove(did)?;
+        self.record_pairing(&me, &other, now);
+        Some((me, other))
+    }
+
+    /// Pairs every waiter that currently has an acceptable opponent, oldest
+    /// first. Acceptability is symmetric, so a waiter skipped here cannot be
+    /// claimed by a later one.
+    pub fn pair_all(&mut self, now: Instant) -> Vec<(Waiter, Waiter)> {
+        let mut out = Vec::new();
+        let mut i = 0;
+        while i < self.waiters.len() {
+            let did = self.waiters[i].did.clone();
+            match self.pair(&did, now) {
+                Some(p) => out.push(p),
+                None => i += 1,
+            }
+        }
+        out
+    }
+
+    /// DIDs waiting in `tid`, oldest first.
+    pub fn dids(&self, tid: &str) -> Vec<String> {
+        self.waiters.iter().filter(|w| w.tid == tid).map(|w| w.did.clone()).collect()
+    }
+
+    /// Queue position and wait estimate for `did`, if queued.
+    pub fn wait_info(&self, did: &str, now: Instant) -> Option<WaitInfo> {
+        let me = self.waiters.iter().find(|w| w.did == did)?;
+        let same_tid = self.waiters.iter().filter(|w| w.tid == me.tid);
+        let waiting = same_tid.clone().count();
+        let position = same_tid.take_while(|w| w.did != did).count() + 1;
+        let typical = self.wait_ewma_ms.map(|ms| Duration::from_millis(ms as u64)).unwrap_or(self.config.default_wait);
+        let waited = now.saturating_duration_since(me.since);
+        Some(WaitInfo { position, waiting, estimated_wait: typical.saturating_sub(waited) })
+    }
+
+    fn acceptable(&self, a: &Waiter, b: &Waiter, now: Instant) -> bool {
+        let wa = now.saturating_duration_since(a.since);
+        let wb = now.saturating_duration_since(b.since);
+        let longest = wa.max(wb);
+        // the longer-waiting side's widened window decides
+        if (a.rating - b.rating).abs() > self.window(longest) { return false; }
+        if let (Some(ra), Some(rb)) = (&a.region, &b.region) {
+            if ra != rb && longest < self.config.region_relax_after { return false; }
+        }
+        if longest < self.config.rematch_relax_after && self.played_recently(&a.did, &b.did, now) { return false; }
+        (self.compatible)(&a.did, &b.did)
+    }
+
+    fn played_recently(&self, a: &str, b: &str, now: Instant) -> bool {
+        self.recent.get(a).map(|q| {
+            q.iter().any(|(opp, at)| opp == b && now.saturating_duration_since(*at) < self.config.rematch_cooldown)
+        }).unwrap_or(false)
+    }
+
+    fn record_pairing(&mut self, a: &Waiter, b: &Waiter, now: Instant) {
+        for (me, opp) in [(a, b), (b, a)] {
+            let q = self.recent.entry(me.did.clone()).or_default();
+            q.push_back((opp.did.clone(), now));
+            let cooldown = self.config.rematch_cooldown;
+            while q.front().map(|(_, at)| now.saturating_duration_since(*at) >= cooldown).unwrap_or(false) { q.pop_front(); }
+        }
+        for w in [a, b] {
+            let waited = now.saturating_duration_since(w.since).as_millis() as f64;
+            self.wait_ewma_ms = Some(match self.wait_ewma_ms { Some(avg) => avg * 0.8 + waited * 0.2, None => waited });
+        }
+    }
+}
+
+/// Standard Elo update with K=32. `score_a` is 1.0 for an A win, 0.5 for a
+/// draw and 0.0 for a loss. Returns the new `(a, b)` ratings.
+pub fn elo_update(a: f64, b: f64, score_a: f64) -> (f64, f64) {
+    const K: f64 = 32.0;
+    let expected_a = 1.0 / (1.0 + 10f64.powf((b - a) / 400.0));
+    let delta = K * (score_a - expected_a);
+    (a + delta, b - delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PoolConfig {
+        PoolConfig {
+            base_window: 100.0,
+            widen_per_sec: 25.0,
+            max_window: 1000.0,
+            region_relax_after: Duration::from_secs(10),
+            rematch_cooldown: Duration::from_secs(300),
+            rematch_relax_after: Duration::from_secs(30),
+            default_wait: Duration::from_secs(15),
+            waiter_ttl: Duration::from_secs(10),
+            requeue_grace: Duration::from_secs(45),
+        }
+    }
+
+    fn secs(s: u64) -> Duration { Duration::from_secs(s) }
+
+    #[test]
+    fn window_widens_with_wait_up_to_the_cap() {
+        let pool = MatchPool::new(config());
+        assert_eq!(pool.window(secs(0)), 100.0);
+        assert_eq!(pool.window(secs(4)), 200.0);
+        assert_eq!(pool.window(secs(3600)), 1000.0);
+
+        let t0 = Instant::now();
+        let mut pool = MatchPool::new(config());
+        pool.enqueue("t", "did:plc:a", 1500.0, None, t0);
+        pool.enqueue("t", "did:plc:b", 1800.0, None, t0);
+        // 300 apart: out of range until the older waiter's window reaches 300 after 8s
+        assert!(pool.pair("did:plc:b", t0 + secs(7)).is_none());
+        let (me, other) = pool.pair("did:plc:b", t0 + secs(8)).unwrap();
+        assert_eq!((me.did.as_str(), other.did.as_str()), ("did:plc:b", "did:plc:a"));
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn pairs_closest_rating_then_longest_waiter() {
+        let t0 = Instant::now();
+        let mut pool = MatchPool::new(config());
+        pool.enqueue("t", "did:plc:far", 1590.0, None, t0);
+        pool.enqueue("t", "did:plc:old", 1530.0, None, t0 + secs(1));
+        pool.enqueue("t", "did:plc:new", 1470.0, None, t0 + secs(2));
+        pool.enqueue("other", "did:plc:same", 1500.0, None, t0 + secs(2));
+        pool.enqueue("t", "did:plc:me", 1500.0, None, t0 + secs(3));
+        assert_eq!(pool.dids("t"), ["did:plc:far", "did:plc:old", "did:plc:new", "did:plc:me"]);
+        // old and new are both 30 away; old has waited longer. same is in another tid.
+        let (_, other) = pool.pair("did:plc:me", t0 + secs(3)).unwrap();
+        assert_eq!(other.did, "did:plc:old");
+        let (_, other) = pool.pair("did:plc:new", t0 + secs(3)).unwrap();
+        assert_eq!(other.did, "did:plc:far");
+        assert_eq!(pool.dids("other"), ["did:plc:same"]);
+    }
+
+    #[test]
+    fn rematch_cooldown_holds_until_relaxed() {
+        let t0 = Instant::now();
+        let mut pool = MatchPool::new(config());
+        pool.enqueue("t", "did:plc:a", 1500.0, None, t0);
+        pool.enqueue("t", "did:plc:b", 1500.0, None, t0);
+        assert!(pool.pair("did:plc:a", t0).is_some());
+
+        let t1 = t0 + secs(60);
+        pool.enqueue("t", "did:plc:a", 1500.0, None, t1);
+        pool.enqueue("t", "did:plc:b", 1500.0, None, t1);
+        assert!(pool.pair("did:plc:a", t1 + secs(29)).is_none());
+        assert!(pool.pair("did:plc:a", t1 + secs(30)).is_some());
+
+        // past the cooldown they are fresh opponents again
+        let t2 = t0 + secs(400);
+        pool.enqueue("t", "did:plc:a", 1500.0, None, t2);
+        pool.enqueue("t", "did:plc:b", 1500.0, None, t2);
+        assert!(pool.pair("did:plc:a", t2).is_some());
+    }
+
+    #[test]
+    fn regions_stay_apart_until_relaxed() {
+        let t0 = Instant::now();
+        let mut pool = MatchPool::new(config());
+        pool.enqueue("t", "did:plc:a", 1500.0, Some("eu".into()), t0);
+        pool.enqueue("t", "did:plc:b", 1500.0, Some("us".into()), t0);
+        assert!(pool.pair("did:plc:a", t0 + secs(9)).is_none());
+        assert!(pool.pair("did:plc:a", t0 + secs(10)).is_some());
+    }
+
+    #[test]
+    fn wait_estimate_follows_observed_waits() {
+        let t0 = Instant::now();
+        let mut pool = MatchPool::new(config());
+        pool.enqueue("t", "did:plc:a", 1500.0, None, t0);
+        pool.enqueue("t", "did:plc:b", 1500.0, Some("x".into()), t0 + secs(1));
+        let info = pool.wait_info("did:plc:b", t0 + secs(5)).unwrap();
+        assert_eq!((info.position, info.waiting), (2, 2));
+        // nothing observed yet: default wait minus the time already waited
+        assert_eq!(info.estimated_wait, secs(11));
+
+        pool.enqueue("t", "did:plc:c", 1500.0, None, t0 + secs(2));
+        // c waited 2s, then a waited 4s: average 2 * 0.8 + 4 * 0.2 = 2.4s
+        pool.pair("did:plc:c", t0 + secs(4)).unwrap();
+        let info = pool.wait_info("did:plc:b", t0 + secs(2)).unwrap();
+        assert_eq!((info.position, info.waiting), (1, 1));
+        assert_eq!(info.estimated_wait, Duration::from_millis(1400));
+        assert_eq!(pool.wait_info("did:plc:b", t0 + secs(4)).unwrap().estimated_wait, Duration::ZERO);
+    }
+
+    #[test]
+    fn pair_all_pairs_every_compatible_waiter_oldest_first() {
+        let t0 = Instant::now();
+        let mut pool = MatchPool::new(config());
+        pool.enqueue("t", "did:plc:a", 1500.0, None, t0);
+        pool.enqueue("t", "did:plc:b", 2000.0, None, t0 + secs(1));
+        pool.enqueue("t", "did:plc:c", 1520.0, None, t0 + secs(2));
+        pool.enqueue("t", "did:plc:d", 1990.0, None, t0 + secs(3));
+        pool.enqueue("t", "did:plc:lonely", 3000.0, None, t0 + secs(3));
+        let pairs: Vec<(String, String)> = pool.pair_all(t0 + secs(3)).into_iter().map(|(a, b)| (a.did, b.did)).collect();
+        assert_eq!(pairs, [("did:plc:a".to_string(), "did:plc:c".to_string()), ("did:plc:b".to_string(), "did:plc:d".to_string())]);
+        assert_eq!(pool.dids("t"), ["did:plc:lonely"]);
+        assert!(pool.pair_all(t0 + secs(3)).is_empty());
+    }
+
+    #[test]
+    fn expire_drops_stale_waiters_unless_kept_alive() {
+        let t0 = Instant::now();
+        let mut pool = MatchPool::new(config());
+        pool.enqueue("t", "did:plc:polling", 1500.0, None, t0);
+        pool.enqueue("t", "did:plc:streaming", 1500.0, Some("eu".into()), t0);
+        pool.enqueue("t", "did:plc:gone", 1500.0, Some("us".into()), t0);
+        // re-polling extends the keepalive
+        pool.enqueue("t", "did:plc:polling", 1500.0, None, t0 + secs(5));
+        assert!(pool.expire(t0 + secs(9), |_| false).is_empty());
+        let gone = pool.expire(t0 + secs(10), |did| did == "did:plc:streaming");
+        assert_eq!(gone.iter().map(|w| w.did.as_str()).collect::<Vec<_>>(), ["did:plc:gone"]);
+        assert_eq!(pool.dids("t"), ["did:plc:polling", "did:plc:streaming"]);
+        // the stream's keepalive lasts one more TTL
+        let gone = pool.expire(t0 + secs(20), |_| false);
+        assert_eq!(gone.iter().map(|w| w.did.as_str()).collect::<Vec<_>>(), ["did:plc:polling", "did:plc:streaming"]);
+        assert!(pool.is_empty());
+    }
+
+    #[test]
+    fn requeue_keeps_original_wait_and_grants_grace() {
+        let t0 = Instant::now();
+        let mut pool = MatchPool::new(config());
+        pool.enqueue("t", "did:plc:a", 1500.0, None, t0);
+        pool.enqueue("t", "did:plc:b", 1500.0, None, t0 + secs(1));
+        let (a, _) = pool.pair("did:plc:a", t0 + secs(1)).unwrap();
+        pool.enqueue("t", "did:plc:c", 9000.0, None, t0 + secs(5));
+        let t1 = t0 + secs(20);
+        pool.requeue(a.clone(), t1);
+        pool.requeue(a, t1);
+        // ahead of c, who joined later, and only once
+        assert_eq!(pool.dids("t"), ["did:plc:a", "did:plc:c"]);
+        assert_eq!(pool.wait_info("did:plc:a", t1).unwrap().position, 1);
+        // grace outlasts the normal TTL
+        let gone = pool.expire(t1 + secs(44), |_| false);
+        assert_eq!(gone.iter().map(|w| w.did.as_str()).collect::<Vec<_>>(), ["did:plc:c"]);
+        assert_eq!(pool.expire(t1 + secs(45), |_| false).len(), 1);
+    }
+
+    #[test]
+    fn elo_moves_by_k_32() {
+        assert_eq!(elo_update(1500.0, 1500.0, 1.0), (1516.0, 1484.0));
+        assert_eq!(elo_update(1500.0, 1500.0, 0.5), (1500.0, 1500.0));
+        // a 400-point favourite expects 10/11 of the score
+        let (a, b) = elo_update(1900.0, 1500.0, 0.0);
+        assert!((a - (1900.0 - 32.0 * 10.0 / 11.0)).abs() < 1e-9);
+        assert!((a + b - 3400.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,323 @@
+//! Tournament registration from `zone.peace.rps.registration` records.
+//!
+//! Players register by writing the record into their own repo. The coordinator
+//! follows those records as Jetstream commit events, either live from
+//! `JETSTREAM_URL` or from a local JSONL file of the same events
+//! (`REGISTRATION_LOG`), which is replayed from the start and then tailed.
+//! A record is only admitted from the repo of the DID it names, once per
+//! (tid, DID); with `REGISTRATION_VERIFY` it is also fetched back from the
+//! DID's PDS and its CID compared. Entrants pushed through `POST /register`
+//! (only with `ALLOW_DIRECT_REGISTER`) are recorded here too, marked `direct`,
+//! so `GET /registrations?tid=..` lists every entrant, with record URIs where
+//! there is a record to re-check.
+
+use axum::{extract::Query, Json};
+use futures::{SinkExt, StreamExt};
+use once_cell::sync::Lazy;
+use reqwest::Client as HttpClient;
+use rps_shared_types::jetstream::{read_new_events, CommitEvent, JetstreamEvent};
+use rps_shared_types::lexicon::{LexiconRecord, Registration};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio_tungstenite::tungstenite::Message;
+
+pub const NSID: &str = Registration::NSID;
+
+/// How an entrant got in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    /// A `zone.peace.rps.registration` record in the entrant's repo.
+    Record,
+    /// An unauthenticated `POST /register`, allowed for local dev.
+    Direct,
+}
+
+/// An admitted entrant and where its record lives.
+#[derive(Debug, Clone, Serialize)]
+pub struct Registered {
+    pub did: String,
+    pub source: Source,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cid: Option<String>,
+    pub created_at: String,
+    pub time_us: i64,
+}
+
+// tid -> admitted registrations in arrival order; dedupes replays and re-registrations
+static REGISTRATIONS: Lazy<Mutex<HashMap<String, Vec<Registered>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// time_us of the last event seen, resumed as the Jetstream cursor after a reconnect
+static CURSOR: Lazy<Mutex<Option<i64>>> = Lazy::new(|| Mutex::new(None));
+
+/// Checks a commit event against the lexicon and the repo it came from.
+pub fn validate(ev: &JetstreamEvent) -> Result<(Registration, &CommitEvent), String> {
+    let commit = ev.commit().ok_or("not a commit event")?;
+    if commit.collection != NSID { return Err(format!("unexpected collection {}", commit.collection)); }
+    if commit.operation != "create" { return Err(format!("ignoring {} operation", commit.operation)); }
+    let record = commit.record.clone().ok_or("commit has no record")?;
+    let reg = Registration::from_record(record).map_err(|e| e.to_string())?;
+    if reg.did != ev.did { return Err(format!("record for {} written by {}", reg.did, ev.did)); }
+    Ok((reg, commit))
+}
+
+/// Validates, optionally verifies, and admits one event. Returns `(tid, did)`
+/// when a DID was admitted.
+pub async fn ingest(http: &HttpClient, ev: &JetstreamEvent, verify: bool) -> Option<(String, String)> {
+    {
+        let mut cursor = CURSOR.lock().unwrap();
+        *cursor = Some(cursor.map_or(ev.time_us, |c| c.max(ev.time_us)));
+    }
+    let (reg, commit) = match validate(ev) {
+        Ok(v) => v,
+        Err(reason) => {
+            if ev.commit.as_ref().is_some_and(|c| c.collection == NSID) { tracing::warn!(did = %ev.did, %reason, "registration rejected"); }
+            return None;
+        }
+    };
+    if is_registered(&reg.tid, &reg.did) { return None; }
+    if verify {
+        let plc = std::env::var("PLC_DIRECTORY_URL").unwrap_or_else(|_| "https://plc.directory".to_string());
+        if let Err(reason) = verify_record(http, &plc, &reg.did, &reg.tid, &commit.rkey, commit.cid.as_deref()).await {
+            tracing::warn!(did = %reg.did, %reason, "registration failed verification");
+            return None;
+        }
+    }
+    let entry = Registered {
+        did: reg.did.clone(),
+        source: Source::Record,
+        uri: ev.uri(),
+        cid: commit.cid.clone(),
+        created_at: reg.created_at.clone(),
+        time_us: ev.time_us,
+    };
+    admit(&reg.tid, entry).then_some((reg.tid, reg.did))
+}
+
+pub fn is_registered(tid: &str, did: &str) -> bool {
+    REGISTRATIONS.lock().unwrap().get(tid).is_some_and(|l| l.iter().any(|r| r.did == did))
+}
+
+/// Adds the entrant unless the DID is already registered for `tid`.
+fn admit(tid: &str, entry: Registered) -> bool {
+    let mut all = REGISTRATIONS.lock().unwrap();
+    let list = all.entry(tid.to_string()).or_default();
+    if list.iter().any(|r| r.did == entry.did) { return false; }
+    tracing::info!(%tid, did = %entry.did, source = ?entry.source, uri = entry.uri.as_deref().unwrap_or(""), "registration admitted");
+    list.push(entry);
+    true
+}
+
+/// Records an entrant that came in through `POST /register` without a record.
+pub fn admit_direct(tid: &str, did: &str) -> bool {
+    let now = chrono::Utc::now();
+    admit(tid, Registered {
+        did: did.to_string(),
+        source: Source::Direct,
+        uri: None,
+        cid: None,
+        created_at: now.to_rfc3339(),
+        time_us: now.timestamp_micros(),
+    })
+}
+
+/// Resolves the DID's PDS and checks the record is there, for the same DID and
+/// tid, with the same CID.
+async fn verify_record(http: &HttpClient, plc: &str, did: &str, tid: &str, rkey: &str, cid: Option<&str>) -> Result<(), String> {
+    let doc_url = match did.strip_prefix("did:web:") {
+        Some(host) => format!("https://{}/.well-known/did.json", host.replace("%3A", ":")),
+        None if did.starts_with("did:plc:") => format!("{}/{}", plc.trim_end_matches('/'), did),
+        None => return Err("unsupported did method".into()),
+    };
+    let doc: serde_json::Value = get_json(http, &doc_url).await?;
+    let pds = doc["service"].as_array().into_iter().flatten()
+        .find(|s| s["id"].as_str().is_some_and(|id| id.ends_with("#atproto_pds")))
+        .and_then(|s| s["serviceEndpoint"].as_str())
+        .ok_or("did document has no #atproto_pds service")?;
+    let url = format!("{}/xrpc/com.atproto.repo.getRecord?repo={}&collection={}&rkey={}", pds.trim_end_matches('/'), did, NSID, rkey);
+    let rec: serde_json::Value = get_json(http, &url).await?;
+    if rec["value"]["did"].as_str() != Some(did) { return Err("record on PDS names a different did".into()); }
+    if rec["value"]["tid"].as_str() != Some(tid) { return Err("record on PDS names a different tid".into()); }
+    match cid {
+        Some(c) if rec["cid"].as_str() != Some(c) => Err("record on PDS has a different cid".into()),
+        _ => Ok(()),
+    }
+}
+
+async fn get_json(http: &HttpClient, url: &str) -> Result<serde_json::Value, String> {
+    let res = http.get(url).timeout(Duration::from_secs(5)).send().await.map_err(|e| e.to_string())?;
+    if !res.status().is_success() { return Err(format!("{} from {}", res.status(), url)); }
+    res.json().await.map_err(|e| e.to_string())
+}
+
+/// Starts the configured sources; `on_admit(tid, did)` adds the entrant to the tournament.
+pub fn spawn(on_admit: fn(&str, &str)) {
+    if let Ok(path) = std::env::var("REGISTRATION_LOG") {
+        let verify = env_flag("REGISTRATION_VERIFY", false);
+        let poll_ms: u64 = std::env::var("REGISTRATION_POLL_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(1_000);
+        tokio::spawn(async move {
+            let http = HttpClient::new();
+            let mut offset = 0u64;
+            let mut ticker = tokio::time::interval(Duration::from_millis(poll_ms));
+            loop {
+                ticker.tick().await;
+                match read_new_events(&path, offset) {
+                    Ok((events, next)) => {
+                        offset = next;
+                        for ev in events {
+                            let ev = match ev {
+                                Ok(ev) => ev,
+                                Err(err) => { tracing::warn!(%err, "skipping malformed event line"); continue }
+                            };
+                            if let Some((tid, did)) = ingest(&http, &ev, verify).await { on_admit(&tid, &did); }
+                        }
+                    }
+                    Err(err) => tracing::debug!(%err, %path, "registration log unreadable"),
+                }
+            }
+        });
+    }
+    if let Ok(url) = std::env::var("JETSTREAM_URL") {
+        let verify = env_flag("REGISTRATION_VERIFY", true);
+        tokio::spawn(follow_jetstream(url, verify, on_admit));
+    }
+}
+
+pub fn env_flag(name: &str, default: bool) -> bool {
+    std::env::var(name).ok().map(|v| v == "1" || v == "true").unwrap_or(default)
+}
+
+/// Subscribes to Jetstream for registration records, reconnecting with backoff
+/// and resuming from the last event seen.
+async fn follow_jetstream(url: String, verify: bool, on_admit: fn(&str, &str)) {
+    let http = HttpClient::new();
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        let mut sub = format!("{}{}wantedCollections={}", url, if url.contains('?') { '&' } else { '?' }, NSID);
+        if let Some(c) = *CURSOR.lock().unwrap() { sub.push_str(&format!("&cursor={}", c)); }
+        match tokio_tungstenite::connect_async(&sub).await {
+            Ok((mut ws, _)) => {
+                tracing::info!(%url, "following registrations on jetstream");
+                backoff = Duration::from_secs(1);
+                while let Some(msg) = ws.next().await {
+                    match msg {
+                        Ok(Message::Text(txt)) => {
+                            let Ok(ev) = serde_json::from_str::<JetstreamEvent>(&txt) else { continue };
+                            if let Some((tid, did)) = ingest(&http, &ev, verify).await { on_admit(&tid, &did); }
+                        }
+                        Ok(Message::Ping(p)) => { let _ = ws.send(Message::Pong(p)).await; }
+                        Ok(Message::Close(_)) | Err(_) => break,
+                        Ok(_) => {}
+                    }
+                }
+                tracing::warn!("jetstream connection closed");
+            }
+            Err(err) => tracing::warn!(%err, "jetstream connect failed"),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(Duration::from_secs(30));
+    }
+}
+
+pub fn clear(tid: Option<&str>) {
+    let mut all = REGISTRATIONS.lock().unwrap();
+    match tid {
+        Some(t) => { all.remove(t); }
+        None => all.clear(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RegistrationsQuery { tid: String }
+
+#[derive(Debug, Serialize)]
+pub struct RegistrationsResp { tid: String, entrants: Vec<Registered> }
+
+/// Lists a tournament's admitted registrations with their record URIs.
+pub async fn list(Query(q): Query<RegistrationsQuery>) -> Json<RegistrationsResp> {
+    let entrants = REGISTRATIONS.lock().unwrap().get(&q.tid).cloned().unwrap_or_default();
+    Json(RegistrationsResp { tid: q.tid, entrants })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{extract::Path, routing::get, Router};
+
+    fn event(repo: &str, tid: &str, did: &str, rkey: &str) -> JetstreamEvent {
+        serde_json::from_value(serde_json::json!({
+            "did": repo, "time_us": 1, "kind": "commit",
+            "commit": { "rev": "r", "operation": "create", "collection": NSID, "rkey": rkey, "cid": "bafyreg",
+                "record": { "$type": NSID, "tid": tid, "did": did, "createdAt": "2026-10-18T12:00:00Z" } }
+        })).unwrap()
+    }
+
+    #[test]
+    fn validates_against_lexicon_and_repo() {
+        assert!(validate(&event("did:plc:a", "t", "did:plc:a", "k")).is_ok());
+        // a record naming someone else is not a registration for them
+        assert!(validate(&event("did:plc:a", "t", "did:plc:b", "k")).is_err());
+        assert!(validate(&event("did:plc:A", "t", "did:plc:A", "k")).is_err());
+        let mut extra = event("did:plc:a", "t", "did:plc:a", "k");
+        extra.commit.as_mut().unwrap().record.as_mut().unwrap()["handle"] = "a.test".into();
+        assert!(validate(&extra).is_err());
+        let mut late = event("did:plc:a", "t", "did:plc:a", "k");
+        late.commit.as_mut().unwrap().record.as_mut().unwrap()["createdAt"] = "soon".into();
+        assert!(validate(&late).is_err());
+        let mut delete = event("did:plc:a", "t", "did:plc:a", "k");
+        delete.commit.as_mut().unwrap().operation = "delete".into();
+        assert!(validate(&delete).is_err());
+    }
+
+    #[tokio::test]
+    async fn admits_each_did_once_per_tid() {
+        let http = HttpClient::new();
+        assert!(ingest(&http, &event("did:plc:a", "dedupe", "did:plc:a", "1"), false).await.is_some());
+        assert!(ingest(&http, &event("did:plc:a", "dedupe", "did:plc:a", "2"), false).await.is_none());
+        assert!(ingest(&http, &event("did:plc:b", "dedupe", "did:plc:b", "1"), false).await.is_some());
+        assert!(ingest(&http, &event("did:plc:a", "dedupe-2", "did:plc:a", "3"), false).await.is_some());
+        let list = REGISTRATIONS.lock().unwrap()["dedupe"].clone();
+        assert_eq!(list.iter().map(|r| r.did.as_str()).collect::<Vec<_>>(), ["did:plc:a", "did:plc:b"]);
+        assert_eq!(list[0].uri.as_deref(), Some(format!("at://did:plc:a/{NSID}/1").as_str()));
+    }
+
+    #[tokio::test]
+    async fn lists_direct_entrants_alongside_records() {
+        let http = HttpClient::new();
+        assert!(ingest(&http, &event("did:plc:a", "mixed", "did:plc:a", "1"), false).await.is_some());
+        assert!(admit_direct("mixed", "did:plc:b"));
+        assert!(!admit_direct("mixed", "did:plc:a"));
+        let Json(resp) = list(Query(RegistrationsQuery { tid: "mixed".into() })).await;
+        let got: Vec<_> = resp.entrants.iter().map(|r| (r.did.as_str(), r.source, r.uri.is_some())).collect();
+        assert_eq!(got, [("did:plc:a", Source::Record, true), ("did:plc:b", Source::Direct, false)]);
+    }
+
+    /// Serves a PLC directory entry pointing back at itself and the records it knows.
+    async fn mock_pds(cid: &'static str) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let me = base.clone();
+        let app = Router::new()
+            .route("/:did", get(move |Path(did): Path<String>| async move {
+                Json(serde_json::json!({ "id": did, "service": [{ "id": "#atproto_pds", "type": "AtprotoPersonalDataServer", "serviceEndpoint": me }] }))
+            }))
+            .route("/xrpc/com.atproto.repo.getRecord", get(move |Query(q): Query<HashMap<String, String>>| async move {
+                Json(serde_json::json!({ "uri": "", "cid": cid, "value": { "tid": "t", "did": q["repo"], "createdAt": "2026-10-18T12:00:00Z" } }))
+            }));
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        base
+    }
+
+    #[tokio::test]
+    async fn verifies_record_on_the_dids_pds() {
+        let http = HttpClient::new();
+        let base = mock_pds("bafyreg").await;
+        assert!(verify_record(&http, &base, "did:plc:a", "t", "k", Some("bafyreg")).await.is_ok());
+        assert!(verify_record(&http, &base, "did:plc:a", "t", "k", Some("bafyother")).await.is_err());
+        assert!(verify_record(&http, &base, "did:plc:a", "other", "k", Some("bafyreg")).await.is_err());
+        assert!(verify_record(&http, &base, "did:key:z6Mk", "t", "k", None).await.is_err());
+    }
+}
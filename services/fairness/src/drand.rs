@@ -0,0 +1,376 @@
+//! drand beacon client.
+//!
+//! Fetches rounds over the public HTTP API, verifies each against the pinned
+//! chain key (see `rps_crypto::drand`) and keeps a bounded cache of verified
+//! rounds. Endpoints are plain base URLs, so tests point the client at a local
+//! fixture server instead of the network.
+
+use reqwest::Client as HttpClient;
+use rps_crypto::drand::{self as beacon, BeaconError, ChainKey, Network, Scheme};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_URLS: &str = "https://api.drand.sh,https://drand.cloudflare.com";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DrandError {
+    #[error("no drand endpoints configured")]
+    NoEndpoints,
+    #[error("fetch failed: {0}")]
+    Http(String),
+    #[error("malformed {0}")]
+    Malformed(&'static str),
+    #[error("asked for round {want}, got {got}")]
+    RoundMismatch { want: u64, got: u64 },
+    #[error("round {0} does not link to its cached neighbour")]
+    BrokenChain(u64),
+    #[error("chain info does not match pinned config: {0}")]
+    InfoMismatch(&'static str),
+    #[error(transparent)]
+    Invalid(#[from] BeaconError),
+}
+
+/// A drand chain we trust: its hash, scheme, group key and round clock.
+#[derive(Debug, Clone)]
+pub struct ChainConfig {
+    pub hash: String,
+    pub scheme: Scheme,
+    pub public_key: Vec<u8>,
+    pub period: u64,
+    pub genesis_time: u64,
+}
+
+impl ChainConfig {
+    /// League of Entropy quicknet: unchained, 3s rounds.
+    pub fn quicknet() -> Self { Self::pinned(beacon::QUICKNET) }
+
+    fn pinned(n: Network) -> Self {
+        ChainConfig {
+            hash: n.hash.into(),
+            scheme: n.scheme,
+            public_key: hex::decode(n.public_key).unwrap(),
+            period: n.period,
+            genesis_time: n.genesis_time,
+        }
+    }
+
+    /// Builds a config from a chain's `/info` document.
+    pub fn from_info(info: &ChainInfo) -> Result<Self, DrandError> {
+        Ok(ChainConfig {
+            hash: info.hash.clone(),
+            scheme: Scheme::from_id(&info.scheme_id)?,
+            public_key: hex::decode(&info.public_key).map_err(|_| DrandError::Malformed("public_key"))?,
+            period: info.period,
+            genesis_time: info.genesis_time,
+        })
+    }
+}
+
+/// `GET /{chain}/info` payload (fields we use).
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChainInfo {
+    pub public_key: String,
+    pub period: u64,
+    pub genesis_time: u64,
+    pub hash: String,
+    #[serde(rename = "schemeID")]
+    pub scheme_id: String,
+}
+
+/// `GET /{chain}/public/{round}` payload. Hex fields as served by drand.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Beacon {
+    pub round: u64,
+    pub randomness: String,
+    pub signature: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_signature: Option<String>,
+}
+
+impl Beacon {
+    /// The 32-byte randomness. Only meaningful on verified beacons.
+    pub fn randomness_bytes(&self) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        if let Ok(b) = hex::decode(&self.randomness) {
+            if b.len() == 32 { out.copy_from_slice(&b); }
+        }
+        out
+    }
+}
+
+/// Verifying, caching drand client for one chain.
+pub struct DrandClient {
+    chain: ChainConfig,
+    key: ChainKey,
+    urls: Vec<String>,
+    http: HttpClient,
+    cache: Mutex<BTreeMap<u64, Beacon>>,
+    cache_rounds: usize,
+}
+
+impl DrandClient {
+    pub fn new(chain: ChainConfig, urls: Vec<String>, timeout: Duration, cache_rounds: usize) -> Result<Self, DrandError> {
+        let key = ChainKey::from_bytes(chain.scheme, &chain.public_key)?;
+        let http = HttpClient::builder().timeout(timeout).build().map_err(|e| DrandError::Http(e.to_string()))?;
+        Ok(DrandClient { chain, key, urls, http, cache: Mutex::new(BTreeMap::new()), cache_rounds: cache_rounds.max(1) })
+    }
+
+    /// Reads `DRAND_CHAIN` (`quicknet` | `default`), `DRAND_URLS` (comma-separated,
+    /// empty disables fetching), `DRAND_TIMEOUT_MS` and `DRAND_CACHE_ROUNDS`.
+    pub fn from_env() -> Self {
+        let chain = match std::env::var("DRAND_CHAIN") {
+            Err(_) => ChainConfig::quicknet(),
+            Ok(name) => beacon::network(&name).map(ChainConfig::pinned).unwrap_or_else(|| {
+                tracing::warn!(chain = %name, "unknown DRAND_CHAIN, using quicknet");
+                ChainConfig::quicknet()
+            }),
+        };
+        let urls = std::env::var("DRAND_URLS").unwrap_or_else(|_| DEFAULT_URLS.to_string())
+            .split(',').map(|s| s.trim().trim_end_matches('/').to_string()).filter(|s| !s.is_empty()).collect();
+        let timeout = Duration::from_millis(std::env::var("DRAND_TIMEOUT_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(1_000));
+        let cache_rounds = std::env::var("DRAND_CACHE_ROUNDS").ok().and_then(|s| s.parse().ok()).unwrap_or(1_024);
+        DrandClient::new(chain, urls, timeout, cache_rounds).expect("pinned drand chain key decodes")
+    }
+
+    pub fn chain(&self) -> &ChainConfig { &self.chain }
+
+    /// Round in progress at `unix_secs` (0 before genesis).
+    pub fn round_at(&self, unix_secs: u64) -> u64 {
+        if unix_secs < self.chain.genesis_time { return 0; }
+        (unix_secs - self.chain.genesis_time) / self.chain.period + 1
+    }
+
+    /// Verified beacon for `round`, from cache when possible.
+    pub async fn round(&self, round: u64) -> Result<Beacon, DrandError> {
+        if let Some(b) = self.cache.lock().unwrap().get(&round).cloned() { return Ok(b); }
+        let b = self.fetch(&round.to_string()).await?;
+        if b.round != round { return Err(DrandError::RoundMismatch { want: round, got: b.round }); }
+        self.verify(&b)?;
+        self.remember(b.clone());
+        Ok(b)
+    }
+
+    /// Verified beacon for the round in progress at `unix_secs`.
+    pub async fn at_time(&self, unix_secs: u64) -> Result<Beacon, DrandError> {
+        self.round(self.round_at(unix_secs)).await
+    }
+
+    /// Verified beacon for the current round, falling back to the endpoint's
+    /// latest if the current round is not served yet (clock skew at a round boundary).
+    pub async fn current(&self) -> Result<Beacon, DrandError> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        let r = self.round_at(now);
+        match self.round(r).await {
+            Ok(b) => Ok(b),
+            Err(DrandError::Http(err)) => {
+                tracing::debug!(%err, round = r, "current drand round unavailable, using latest");
+                self.latest().await
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Latest beacon as reported by the endpoint, verified.
+    pub async fn latest(&self) -> Result<Beacon, DrandError> {
+        let b = self.fetch("latest").await?;
+        self.verify(&b)?;
+        self.remember(b.clone());
+        Ok(b)
+    }
+
+    /// Fetches `/info` and checks it against the pinned config.
+    pub async fn check_info(&self) -> Result<(), DrandError> {
+        let info: ChainInfo = self.get_json("info").await?;
+        let remote = ChainConfig::from_info(&info)?;
+        if remote.hash != self.chain.hash { return Err(DrandError::InfoMismatch("hash")); }
+        if remote.public_key != self.chain.public_key { return Err(DrandError::InfoMismatch("public_key")); }
+        if remote.scheme != self.chain.scheme { return Err(DrandError::InfoMismatch("schemeID")); }
+        if remote.period != self.chain.period || remote.genesis_time != self.chain.genesis_time {
+            return Err(DrandError::InfoMismatch("round clock"));
+        }
+        Ok(())
+    }
+
+    /// Checks the BLS signature and randomness and, on chained schemes, that
+    /// the beacon links to any cached neighbouring round.
+    pub fn verify(&self, b: &Beacon) -> Result<(), DrandError> {
+        let sig = hex::decode(&b.signature).map_err(|_| DrandError::Malformed("signature"))?;
+        let prev = match &b.previous_signature {
+            Some(p) => Some(hex::decode(p).map_err(|_| DrandError::Malformed("previous_signature"))?),
+            None => None,
+        };
+        let randomness = hex::decode(&b.randomness).map_err(|_| DrandError::Malformed("randomness"))?;
+        beacon::verify_beacon(self.chain.scheme, &self.key, b.round, &sig, prev.as_deref(), &randomness)?;
+        if self.chain.scheme.is_chained() {
+            let cache = self.cache.lock().unwrap();
+            if let Some(before) = b.round.checked_sub(1).and_then(|r| cache.get(&r)) {
+                if b.previous_signature.as_deref() != Some(before.signature.as_str()) { return Err(DrandError::BrokenChain(b.round)); }
+            }
+            if let Some(after) = cache.get(&(b.round + 1)) {
+                if after.previous_signature.as_deref() != Some(b.signature.as_str()) { return Err(DrandError::BrokenChain(b.round)); }
+            }
+        }
+        Ok(())
+    }
+
+    fn remember(&self, b: Beacon) {
+        let mut cache = self.cache.lock().unwrap();
+        cache.insert(b.round, b);
+        while cache.len() > self.cache_rounds { cache.pop_first(); }
+    }
+
+    async fn fetch(&self, round: &str) -> Result<Beacon, DrandError> {
+        self.get_json(&format!("public/{}", round)).await
+    }
+
+    // tries each endpoint in order; the first well-formed answer wins
+    async fn get_json<T: serde::de::DeserializeOwned>(&self, path: &str) -> Result<T, DrandError> {
+        let mut last = DrandError::NoEndpoints;
+        for base in &self.urls {
+            let url = format!("{}/{}/{}", base, self.chain.hash, path);
+            let res = match self.http.get(&url).send().await.and_then(|r| r.error_for_status()) {
+                Ok(r) => r,
+                Err(e) => { last = DrandError::Http(e.to_string()); continue; }
+            };
+            match res.json::<T>().await {
+                Ok(v) => return Ok(v),
+                Err(e) => last = DrandError::Http(e.to_string()),
+            }
+        }
+        Err(last)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    //! Fixtures under `fixtures/drand/` mirror the drand HTTP layout for two
+    //! test chains (one per scheme), signed with a fixed test key. They are
+    //! served by a local axum server so no test touches the network.
+
+    use super::*;
+    use axum::{extract::{Path, State}, http::StatusCode, routing::get, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    const CHAINED: &str = "17c613a1b4686108ba61ccf988ca1daac1500075c0175d140f046801722801e3";
+    const UNCHAINED: &str = "4a244ed292a54aee064e08230c917e1449db404ff9a033f9b6471c3e697af827";
+
+    fn fixture(path: &str) -> Option<String> {
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/drand");
+        std::fs::read_to_string(root.join(path)).ok()
+    }
+
+    /// Serves the fixture tree and counts requests.
+    async fn fixture_server() -> (String, Arc<AtomicUsize>) {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let app = Router::new()
+            .route("/*path", get(|State(hits): State<Arc<AtomicUsize>>, Path(path): Path<String>| async move {
+                hits.fetch_add(1, Ordering::SeqCst);
+                fixture(&path).ok_or(StatusCode::NOT_FOUND)
+            }))
+            .with_state(hits.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{}", addr), hits)
+    }
+
+    fn chain(hash: &str) -> ChainConfig {
+        let info: ChainInfo = serde_json::from_str(&fixture(&format!("{}/info", hash)).unwrap()).unwrap();
+        ChainConfig::from_info(&info).unwrap()
+    }
+
+    fn client(hash: &str, urls: Vec<String>) -> DrandClient {
+        DrandClient::new(chain(hash), urls, Duration::from_secs(2), 16).unwrap()
+    }
+
+    #[tokio::test]
+    async fn verifies_unchained_round() {
+        let (url, _) = fixture_server().await;
+        let c = client(UNCHAINED, vec![url]);
+        let b = c.round(102).await.unwrap();
+        assert_eq!(b.round, 102);
+        assert_eq!(b.randomness_bytes(), beacon::randomness(&hex::decode(&b.signature).unwrap()));
+        c.check_info().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn verifies_chained_rounds_and_links() {
+        let (url, _) = fixture_server().await;
+        let c = client(CHAINED, vec![url]);
+        let r101 = c.round(101).await.unwrap();
+        let r102 = c.round(102).await.unwrap();
+        assert_eq!(r102.previous_signature.as_deref(), Some(r101.signature.as_str()));
+        assert_eq!(c.latest().await.unwrap().round, 104);
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_beacons() {
+        let (url, _) = fixture_server().await;
+        for hash in [CHAINED, UNCHAINED] {
+            let c = client(hash, vec![url.clone()]);
+            let good = c.round(103).await.unwrap();
+
+            let mut wrong_round = good.clone();
+            wrong_round.round = 104;
+            assert!(matches!(c.verify(&wrong_round), Err(DrandError::Invalid(BeaconError::InvalidSignature))));
+
+            let mut wrong_randomness = good.clone();
+            wrong_randomness.randomness = hex::encode([7u8; 32]);
+            assert!(matches!(c.verify(&wrong_randomness), Err(DrandError::Invalid(BeaconError::RandomnessMismatch))));
+
+            let other = c.round(101).await.unwrap();
+            let mut swapped = good.clone();
+            swapped.signature = other.signature;
+            assert!(c.verify(&swapped).is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_beacons_from_another_key() {
+        let (url, _) = fixture_server().await;
+        let mut pinned = chain(UNCHAINED);
+        pinned.public_key = ChainConfig::quicknet().public_key;
+        let c = DrandClient::new(pinned, vec![url], Duration::from_secs(2), 16).unwrap();
+        assert!(matches!(c.round(100).await, Err(DrandError::Invalid(BeaconError::InvalidSignature))));
+        assert!(matches!(c.check_info().await, Err(DrandError::InfoMismatch("public_key"))));
+    }
+
+    #[tokio::test]
+    async fn caches_verified_rounds() {
+        let (url, hits) = fixture_server().await;
+        let c = client(UNCHAINED, vec![url]);
+        c.round(100).await.unwrap();
+        c.round(100).await.unwrap();
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn maps_time_to_round() {
+        let (url, _) = fixture_server().await;
+        let c = client(UNCHAINED, vec![url]);
+        let g = c.chain().genesis_time;
+        assert_eq!(c.round_at(g - 1), 0);
+        assert_eq!(c.round_at(g), 1);
+        assert_eq!(c.round_at(g + 3 * 101 + 2), 102);
+        assert_eq!(c.at_time(g + 3 * 101).await.unwrap().round, 102);
+    }
+
+    #[tokio::test]
+    async fn fails_over_to_next_endpoint() {
+        let (url, _) = fixture_server().await;
+        let c = client(UNCHAINED, vec!["http://127.0.0.1:1".into(), url]);
+        assert_eq!(c.round(104).await.unwrap().round, 104);
+        let none = client(UNCHAINED, vec![]);
+        assert!(matches!(none.round(104).await, Err(DrandError::NoEndpoints)));
+    }
+
+    #[test]
+    fn pinned_network_keys_decode() {
+        for c in [beacon::QUICKNET, beacon::MAINNET].map(ChainConfig::pinned) {
+            ChainKey::from_bytes(c.scheme, &c.public_key).unwrap();
+        }
+    }
+}
@@ -1,18 +1,57 @@
-use axum::{routing::{get, post}, Router, Json};
+mod drand;
+mod strategy;
+
+use axum::{extract::Query, http::{header::AUTHORIZATION, HeaderMap, StatusCode}, routing::{get, post}, Router, Json};
+use drand::{Beacon, DrandClient};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use rps_crypto::moves::{ai_move_alpha, move_from_output, substitution_alpha};
+use strategy::{Strategy, Turn, STRATEGY_NAMES};
+use rps_crypto::vrf::{self, PublicKey, SecretKey};
+use rps_shared_types::internal;
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use serde::{Deserialize, Serialize};
-use rand::{RngCore, SeedableRng};
-use rand::rngs::StdRng;
 
-/// Service entrypoint: exposes `/ai_move` that returns a seeded random R/P/S.
+// VRF key from VRF_SECRET_KEY (hex, 32-byte seed); ephemeral if unset
+static VRF_KEY: Lazy<SecretKey> = Lazy::new(|| {
+    let seed = std::env::var("VRF_SECRET_KEY").ok().and_then(|s| {
+        let bytes = hex::decode(s.trim().trim_start_matches("0x")).ok()?;
+        <[u8; 32]>::try_from(bytes.as_slice()).ok()
+    });
+    let seed = seed.unwrap_or_else(|| {
+        tracing::warn!("VRF_SECRET_KEY unset or invalid; using an ephemeral key (proofs will not verify after restart)");
+        let mut s = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut s);
+        s
+    });
+    SecretKey::from_seed(seed)
+});
+
+// drand client for the configured chain (DRAND_CHAIN / DRAND_URLS)
+static DRAND: Lazy<DrandClient> = Lazy::new(DrandClient::from_env);
+
+/// Service entrypoint: exposes `/ai_move` (VRF-derived R/P/S), `/vrf_pubkey`,
+/// `/substitute` (timeout substitutions), `/verify` and `/beacon` (verified drand rounds).
+/// `/ai_move` and `/substitute` are for signaling only (`INTERNAL_TOKEN`).
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt().with_env_filter("info").init();
+    tracing::info!(public_key = %hex::encode(VRF_KEY.public_key().to_bytes()), suite = vrf::SUITE_NAME, "VRF key loaded");
+    tokio::spawn(async {
+        match DRAND.check_info().await {
+            Ok(()) => tracing::info!(chain = %DRAND.chain().hash, "drand chain info matches"),
+            Err(err) => tracing::warn!(%err, chain = %DRAND.chain().hash, "drand chain info check failed"),
+        }
+    });
 
     let app = Router::new()
         .route("/healthz", get(|| async { "ok" }))
-        .route("/ai_move", post(ai_move));
+        .route("/ai_move", post(ai_move))
+        .route("/substitute", post(substitute))
+        .route("/vrf_pubkey", get(vrf_pubkey))
+        .route("/verify", post(verify))
+        .route("/beacon", get(beacon));
 
     let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8080);
     let addr = SocketAddr::from(([0, 0, 0, 0], port));
@@ -21,19 +60,219 @@ async fn main() {
     axum::serve(listener, app).await.unwrap();
 }
 
+/// Randomness bound into a VRF input for `round`; round 0 means "no beacon".
+async fn beacon_randomness(round: u64) -> Result<[u8; 32], drand::DrandError> {
+    if round == 0 { return Ok([0u8; 32]); }
+    Ok(DRAND.round(round).await?.randomness_bytes())
+}
+
 #[derive(Debug, Deserialize)]
-struct AiMoveReq { match_id: String, turn: u32 }
+struct AiMoveReq {
+    match_id: String,
+    turn: u32,
+    /// drand round to bind to; defaults to the current round.
+    #[serde(default)]
+    round: Option<u64>,
+    /// Strategy name (see `strategy::STRATEGY_NAMES`); defaults to uniform.
+    #[serde(default)]
+    strategy: Option<String>,
+    /// Finished turns of this match, oldest first, from the AI's side.
+    #[serde(default)]
+    history: Vec<Turn>,
+}
+
+#[derive(Debug, Serialize)]
+#[allow(non_snake_case)]
+struct AiMoveResp { rps: char, vrfOutput: String, vrfProof: String, drandEpoch: u64, strategy: String }
+
+/// Resolves a strategy name or answers 400 listing the valid ones.
+fn strategy_for(name: Option<&str>) -> Result<Box<dyn Strategy>, (StatusCode, String)> {
+    let name = name.unwrap_or("uniform");
+    strategy::by_name(name).ok_or_else(|| (StatusCode::BAD_REQUEST, format!("unknown strategy {:?}; expected one of {}", name, STRATEGY_NAMES.join(", "))))
+}
+
+/// Resolves the drand round a move binds to: the requested one (which must be
+/// fetchable), else the current one, else round 0 if drand is unreachable.
+async fn bind_round(requested: Option<u64>) -> Result<(u64, [u8; 32]), (StatusCode, String)> {
+    match requested {
+        Some(r) => Ok((r, beacon_randomness(r).await.map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, format!("drand round {}: {}", r, e)))?)),
+        None => match DRAND.current().await {
+            Ok(b) => Ok((b.round, b.randomness_bytes())),
+            Err(err) => {
+                tracing::warn!(%err, "drand unavailable, move not bound to a beacon");
+                Ok((0, [0u8; 32]))
+            }
+        },
+    }
+}
+
+/// Refuses callers without the internal token. Moves are deterministic in
+/// (match_id, turn, round), so a public `/ai_move` or `/substitute` would let a
+/// player preview the AI's move before committing.
+fn require_internal(headers: &HeaderMap) -> Result<(), (StatusCode, String)> {
+    if internal::authorized(headers.get(AUTHORIZATION).and_then(|v| v.to_str().ok())) { Ok(()) }
+    else { Err((StatusCode::UNAUTHORIZED, "internal route".to_string())) }
+}
+
+/// Proves `alpha` and returns the move, VRF output and proof.
+fn prove_move(alpha: &[u8]) -> (char, [u8; vrf::OUTPUT_LEN], [u8; vrf::PROOF_LEN]) {
+    let proof = VRF_KEY.prove(alpha);
+    let beta = vrf::proof_to_hash(&proof).expect("freshly generated proof decodes");
+    (move_from_output(&beta), beta, proof)
+}
+
+/// Returns the AI move for (match_id, turn, drand round) with its VRF proof.
+/// The strategy picks the move from the history using randomness seeded by the
+/// VRF output, so it is unpredictable without the secret key and reproducible
+/// from the proof. Without an explicit round the current beacon is used; if
+/// drand is unreachable the move is bound to round 0 rather than failing.
+async fn ai_move(headers: HeaderMap, Json(req): Json<AiMoveReq>) -> Result<Json<AiMoveResp>, (StatusCode, String)> {
+    require_internal(&headers)?;
+    let strategy = strategy_for(req.strategy.as_deref())?;
+    let (round, randomness) = bind_round(req.round).await?;
+    let (_, beta, proof) = prove_move(&ai_move_alpha(&req.match_id, req.turn, round, &randomness));
+    let rps = strategy.choose(&req.history, &beta);
+    Ok(Json(AiMoveResp { rps, vrfOutput: hex::encode(beta), vrfProof: hex::encode(proof), drandEpoch: round, strategy: strategy.name() }))
+}
+
+#[derive(Debug, Deserialize)]
+struct SubstituteReq {
+    match_id: String,
+    turn: u32,
+    /// DID of the player who missed the deadline.
+    did: String,
+    #[serde(default)]
+    round: Option<u64>,
+}
 
 #[derive(Debug, Serialize)]
-struct AiMoveResp { rps: char, vrfOutput: String, vrfProof: String, drandEpoch: u64 }
-
-/// Returns a pseudo‑random R/P/S for a (match_id, turn) using a deterministic
-/// seed, plus placeholder VRF/drand fields for future integration.
-async fn ai_move(Json(req): Json<AiMoveReq>) -> Json<AiMoveResp> {
-    // sample uniformly R/P/S from seeded RNG so it is not always 'R'
-    let seed = fxhash::hash64(format!("{}:{}", req.match_id, req.turn).as_bytes());
-    let mut rng: StdRng = SeedableRng::seed_from_u64(seed);
-    let idx = (rng.next_u32() % 3) as usize;
-    let rps = ['R','P','S'][idx];
-    Json(AiMoveResp { rps, vrfOutput: "0x00".into(), vrfProof: "0x00".into(), drandEpoch: 0 })
+#[serde(rename_all = "camelCase")]
+struct SubstituteResp { rps: char, vrf_output: String, vrf_proof: String, drand_epoch: u64, public_key: String }
+
+/// Uniform VRF move substituted for `did` on a missed deadline. Always the
+/// uniform strategy: the substituted player must not be helped or hurt.
+async fn substitute(headers: HeaderMap, Json(req): Json<SubstituteReq>) -> Result<Json<SubstituteResp>, (StatusCode, String)> {
+    require_internal(&headers)?;
+    let (round, randomness) = bind_round(req.round).await?;
+    let (rps, beta, proof) = prove_move(&substitution_alpha(&req.match_id, req.turn, &req.did, round, &randomness));
+    Ok(Json(SubstituteResp {
+        rps,
+        vrf_output: hex::encode(beta),
+        vrf_proof: hex::encode(proof),
+        drand_epoch: round,
+        public_key: hex::encode(VRF_KEY.public_key().to_bytes()),
+    }))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VrfPubkeyResp { suite: &'static str, public_key: String }
+
+/// Publishes the VRF public key so clients can verify AI moves offline.
+async fn vrf_pubkey() -> Json<VrfPubkeyResp> {
+    Json(VrfPubkeyResp { suite: vrf::SUITE_NAME, public_key: hex::encode(VRF_KEY.public_key().to_bytes()) })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyReq {
+    #[serde(alias = "match_id")]
+    match_id: String,
+    turn: u32,
+    #[serde(default)]
+    round: u64,
+    /// Hex proof as returned in `vrfProof`.
+    proof: String,
+    /// Expected move; when present it must match the proof's output.
+    #[serde(default)]
+    rps: Option<char>,
+    /// Hex public key; defaults to this service's key.
+    #[serde(default, alias = "public_key")]
+    public_key: Option<String>,
+    /// Set to check a timeout substitution for this DID instead of an AI move.
+    #[serde(default)]
+    did: Option<String>,
+    /// Strategy and history the AI move was chosen with (ignored with `did`).
+    #[serde(default)]
+    strategy: Option<String>,
+    #[serde(default)]
+    history: Vec<Turn>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerifyResp {
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    vrf_output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rps: Option<char>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Verifies an AI move (or, with `did`, a substitution) proof for
+/// (match_id, turn, round) and reports the move it commits to.
+async fn verify(Json(req): Json<VerifyReq>) -> Json<VerifyResp> {
+    let invalid = |e: String| Json(VerifyResp { valid: false, vrf_output: None, rps: None, error: Some(e) });
+    let pk = match &req.public_key {
+        Some(h) => match hex::decode(h.trim_start_matches("0x")).map_err(|e| e.to_string()).and_then(|b| PublicKey::from_bytes(&b).map_err(|e| e.to_string())) {
+            Ok(pk) => pk,
+            Err(e) => return invalid(format!("public key: {}", e)),
+        },
+        None => VRF_KEY.public_key(),
+    };
+    let proof = match hex::decode(req.proof.trim_start_matches("0x")) {
+        Ok(p) => p,
+        Err(e) => return invalid(format!("proof: {}", e)),
+    };
+    let randomness = match beacon_randomness(req.round).await {
+        Ok(r) => r,
+        Err(e) => return invalid(format!("drand round {}: {}", req.round, e)),
+    };
+    let strategy = match (&req.did, strategy_for(req.strategy.as_deref())) {
+        (Some(_), _) => None,
+        (None, Ok(s)) => Some(s),
+        (None, Err((_, e))) => return invalid(e),
+    };
+    let alpha = match &req.did {
+        Some(did) => substitution_alpha(&req.match_id, req.turn, did, req.round, &randomness),
+        None => ai_move_alpha(&req.match_id, req.turn, req.round, &randomness),
+    };
+    match pk.verify(&alpha, &proof) {
+        Ok(beta) => {
+            let rps = match &strategy {
+                Some(s) => s.choose(&req.history, &beta),
+                None => move_from_output(&beta),
+            };
+            if let Some(claimed) = req.rps.filter(|c| *c != rps) {
+                return Json(VerifyResp { valid: false, vrf_output: Some(hex::encode(beta)), rps: Some(rps), error: Some(format!("proof yields {} not {}", rps, claimed)) });
+            }
+            Json(VerifyResp { valid: true, vrf_output: Some(hex::encode(beta)), rps: Some(rps), error: None })
+        }
+        Err(e) => invalid(e.to_string()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BeaconQuery { round: Option<u64>, time: Option<u64> }
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BeaconResp {
+    chain_hash: String,
+    scheme: &'static str,
+    #[serde(flatten)]
+    beacon: Beacon,
+}
+
+/// Verified drand beacon by `round`, by unix `time`, or the current round.
+async fn beacon(Query(q): Query<BeaconQuery>) -> Result<Json<BeaconResp>, (StatusCode, String)> {
+    let res = match (q.round, q.time) {
+        (Some(r), _) => DRAND.round(r).await,
+        (None, Some(t)) => DRAND.at_time(t).await,
+        (None, None) => DRAND.current().await,
+    };
+    let beacon = res.map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, e.to_string()))?;
+    Ok(Json(BeaconResp { chain_hash: DRAND.chain().hash.clone(), scheme: DRAND.chain().scheme.id(), beacon }))
 }
@@ -0,0 +1,357 @@
+//! Selectable AI strategies.
+//!
+//! Every strategy is a pure function of the match history and a random
+//! stream. The stream is ChaCha20 seeded from the VRF output of the turn, so a
+//! move is reproducible from (proof, strategy, history) and unpredictable
+//! without the fairness key. Timeout substitutions never go through here; they
+//! always use the plain uniform VRF mapping.
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use rps_crypto::moves::move_from_output;
+use rps_crypto::vrf::OUTPUT_LEN;
+use serde::Deserialize;
+
+const MOVES: [char; 3] = ['R', 'P', 'S'];
+
+/// One finished turn from the AI's point of view.
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Turn {
+    /// The AI's move.
+    pub mine: char,
+    /// The opponent's move.
+    pub theirs: char,
+}
+
+/// A move-selection policy.
+pub trait Strategy: Send + Sync {
+    fn name(&self) -> String;
+    /// Picks the next move given past turns (oldest first).
+    fn choose(&self, history: &[Turn], beta: &[u8; OUTPUT_LEN]) -> char;
+}
+
+/// Names accepted by [`by_name`].
+pub const STRATEGY_NAMES: &[&str] = &["uniform", "frequency", "markov", "markov:<1-5>", "iocaine"];
+
+/// Looks up a strategy by name (case-insensitive). `markov` defaults to order 2.
+pub fn by_name(name: &str) -> Option<Box<dyn Strategy>> {
+    let name = name.trim().to_ascii_lowercase();
+    match name.as_str() {
+        "" | "uniform" | "uniform-vrf" => Some(Box::new(Uniform)),
+        "frequency" => Some(Box::new(Frequency)),
+        "markov" => Some(Box::new(Markov { order: 2 })),
+        "iocaine" | "iocaine-powder" => Some(Box::new(Iocaine)),
+        other => {
+            let order: usize = other.strip_prefix("markov:")?.parse().ok()?;
+            (1..=5).contains(&order).then(|| Box::new(Markov { order }) as Box<dyn Strategy>)
+        }
+    }
+}
+
+fn rng_from(beta: &[u8; OUTPUT_LEN]) -> ChaCha20Rng {
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&beta[32..]);
+    ChaCha20Rng::from_seed(seed)
+}
+
+fn beats(m: char) -> char {
+    match m { 'R' => 'P', 'P' => 'S', _ => 'R' }
+}
+
+fn pick(rng: &mut impl RngCore, from: &[char]) -> char {
+    *from.choose(rng).unwrap_or(&'R')
+}
+
+/// Plays the move with the highest count; ties broken by `rng`.
+fn argmax(counts: &[(char, f64)], rng: &mut impl RngCore) -> char {
+    let best = counts.iter().map(|(_, c)| *c).fold(f64::MIN, f64::max);
+    let top: Vec<char> = counts.iter().filter(|(_, c)| *c == best).map(|(m, _)| *m).collect();
+    pick(rng, &top)
+}
+
+/// Uniform over R/P/S straight from the VRF output; same mapping as substitutions.
+pub struct Uniform;
+
+impl Strategy for Uniform {
+    fn name(&self) -> String { "uniform".into() }
+    fn choose(&self, _history: &[Turn], beta: &[u8; OUTPUT_LEN]) -> char { move_from_output(beta) }
+}
+
+/// Counters the opponent's most frequent move, weighting recent turns higher.
+pub struct Frequency;
+
+impl Strategy for Frequency {
+    fn name(&self) -> String { "frequency".into() }
+    fn choose(&self, history: &[Turn], beta: &[u8; OUTPUT_LEN]) -> char {
+        let mut rng = rng_from(beta);
+        if history.is_empty() { return pick(&mut rng, &MOVES); }
+        let mut counts: Vec<(char, f64)> = MOVES.iter().map(|m| (*m, 0.0)).collect();
+        let mut w = 1.0;
+        for t in history.iter().rev() {
+            if let Some(c) = counts.iter_mut().find(|(m, _)| *m == t.theirs) { c.1 += w; }
+            w *= 0.9;
+        }
+        beats(argmax(&counts, &mut rng))
+    }
+}
+
+/// Order-N Markov chain over the opponent's moves: predicts the move that most
+/// often followed the last N, and counters it.
+pub struct Markov { pub order: usize }
+
+impl Strategy for Markov {
+    fn name(&self) -> String { format!("markov:{}", self.order) }
+    fn choose(&self, history: &[Turn], beta: &[u8; OUTPUT_LEN]) -> char {
+        let mut rng = rng_from(beta);
+        let theirs: Vec<char> = history.iter().map(|t| t.theirs).collect();
+        if theirs.len() <= self.order { return pick(&mut rng, &MOVES); }
+        let context = &theirs[theirs.len() - self.order..];
+        let mut counts: Vec<(char, f64)> = MOVES.iter().map(|m| (*m, 0.0)).collect();
+        for w in theirs.windows(self.order + 1) {
+            if &w[..self.order] == context {
+                if let Some(c) = counts.iter_mut().find(|(m, _)| *m == w[self.order]) { c.1 += 1.0; }
+            }
+        }
+        if counts.iter().all(|(_, c)| *c == 0.0) { return pick(&mut rng, &MOVES); }
+        beats(argmax(&counts, &mut rng))
+    }
+}
+
+/// Port of the web client's Iocaine Powder engine (`apps/web/src/app/ai/IocainePowderAI.ts`).
+/// State is rebuilt by replaying the history, with the engine's own past
+/// outputs pinned to the moves the AI actually played.
+pub struct Iocaine;
+
+impl Strategy for Iocaine {
+    fn name(&self) -> String { "iocaine".into() }
+    fn choose(&self, history: &[Turn], beta: &[u8; OUTPUT_LEN]) -> char { Iocaine::play(history, &mut rng_from(beta)) }
+}
+
+impl Iocaine {
+    fn play(history: &[Turn], rng: &mut impl RngCore) -> char {
+        let mut engine = IocaineEngine::new(rng);
+        let mut predict = None;
+        for t in history {
+            predict = Some(engine.step(t.mine, t.theirs, rng));
+        }
+        match predict {
+            // notLose: the counter twice, the predicted move once
+            Some(p) => pick(rng, &[beats(p), beats(p), p]),
+            None => pick(rng, &MOVES),
+        }
+    }
+}
+
+const NUM_PREDICTOR: usize = 27;
+const LEN_RFIND: usize = 20;
+const LIMIT: [usize; 3] = [10, 20, 60];
+
+// pair <-> digit tables from the original (first char, second char)
+const PAIRS: [&str; 9] = ["PP", "PR", "PS", "RP", "RR", "RS", "SP", "SR", "SS"];
+
+fn pair_code(a: char, b: char) -> u8 {
+    let idx = PAIRS.iter().position(|p| p.as_bytes() == [a as u8, b as u8]).unwrap_or(0);
+    b'1' + idx as u8
+}
+
+fn code_pair(c: u8) -> (char, char) {
+    let p = PAIRS[(c - b'1') as usize].as_bytes();
+    (p[0] as char, p[1] as char)
+}
+
+// +1 if the first move wins
+fn who_win(a: char, b: char) -> i32 {
+    if a == b { 0 } else if beats(b) == a { 1 } else { -1 }
+}
+
+struct IocaineEngine {
+    my_his: Vec<u8>,
+    your_his: Vec<u8>,
+    both_his: Vec<u8>,
+    list_predictor: Vec<Vec<u8>>,
+    predictors: [char; NUM_PREDICTOR],
+}
+
+impl IocaineEngine {
+    fn new(rng: &mut impl RngCore) -> Self {
+        let first = pick(rng, &MOVES);
+        IocaineEngine {
+            my_his: Vec::new(),
+            your_his: Vec::new(),
+            both_his: Vec::new(),
+            list_predictor: vec![Vec::new(); NUM_PREDICTOR],
+            predictors: [first; NUM_PREDICTOR],
+        }
+    }
+
+    /// Feeds one turn (`output` = our move, `input` = theirs) and returns the
+    /// predicted next opponent move.
+    fn step(&mut self, output: char, input: char, rng: &mut impl RngCore) -> char {
+        let front = if self.list_predictor[0].len() < 5 { 0 } else { 1 };
+        for i in 0..NUM_PREDICTOR {
+            let hit = if self.predictors[i] == input { b'1' } else { b'0' };
+            let lp = &mut self.list_predictor[i];
+            let end = lp.len().min(5);
+            let mut next = lp[front.min(end)..end].to_vec();
+            next.push(hit);
+            *lp = next;
+        }
+
+        self.my_his.push(output as u8);
+        self.your_his.push(input as u8);
+        self.both_his.push(pair_code(input, output));
+        let length = self.both_his.len();
+        let len_size = length.min(LEN_RFIND);
+
+        // history matching predictors 0-5: both, yours, mine
+        for (slot, his) in [(0, &self.both_his), (2, &self.your_his), (4, &self.my_his)] {
+            match longest_recent_match(his, len_size) {
+                Some(at) => {
+                    self.predictors[slot] = self.your_his.get(at).map(|b| *b as char).unwrap_or_else(|| pick(rng, &MOVES));
+                    self.predictors[slot + 1] = self.my_his.get(at).map(|b| beats(*b as char)).unwrap_or_else(|| pick(rng, &MOVES));
+                }
+                None => {
+                    self.predictors[slot] = pick(rng, &MOVES);
+                    self.predictors[slot + 1] = pick(rng, &MOVES);
+                }
+            }
+        }
+
+        // frequency analysis predictors 6-8
+        let search = pair_code(output, input);
+        for (i, limit) in LIMIT.iter().enumerate() {
+            let mut follow = Vec::new();
+            for start in 2..(*limit).min(length) {
+                if self.both_his[length - start] == search {
+                    follow.push(self.both_his[length - start + 1]);
+                }
+            }
+            if follow.is_empty() {
+                self.predictors[6 + i] = pick(rng, &MOVES);
+                continue;
+            }
+            let mut collect: Vec<(char, f64)> = vec![('P', 0.0), ('R', 0.0), ('S', 0.0)];
+            for code in follow {
+                let (a, b) = code_pair(code);
+                let (target, add) = match who_win(a, b) {
+                    -1 => (b, 3.0),
+                    0 => (b, 1.0),
+                    _ => (beats(a), 1.0),
+                };
+                if let Some(c) = collect.iter_mut().find(|(m, _)| *m == target) { c.1 += add; }
+            }
+            self.predictors[6 + i] = argmax(&collect, rng);
+        }
+
+        // rotations 9-26
+        for i in 9..NUM_PREDICTOR {
+            self.predictors[i] = beats(beats(self.predictors[i - 9]));
+        }
+
+        let scores: Vec<i64> = self.list_predictor.iter().map(|lp| {
+            lp.iter().enumerate().map(|(j, hit)| {
+                let w = ((j + 1) * (j + 1)) as i64;
+                if *hit == b'1' { w } else { -w }
+            }).sum()
+        }).collect();
+        let max = scores.iter().copied().max().unwrap_or(0);
+        if max > 0 {
+            self.predictors[scores.iter().position(|s| *s == max).unwrap_or(0)]
+        } else {
+            self.your_his[rng.gen_range(0..self.your_his.len())] as char
+        }
+    }
+}
+
+/// Longest suffix (up to `len_size`) of `his` that also occurs earlier; returns
+/// the index just past its last earlier occurrence, as in the original's `j + k`.
+fn longest_recent_match(his: &[u8], len_size: usize) -> Option<usize> {
+    let length = his.len();
+    let prefix = &his[..length - 1];
+    for j in (1..=len_size).rev() {
+        let suffix = &his[length - j..];
+        if let Some(k) = prefix.windows(j).rposition(|w| w == suffix) {
+            return Some(j + k);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn beta(n: u8) -> [u8; OUTPUT_LEN] { std::array::from_fn(|i| n.wrapping_mul(31).wrapping_add(i as u8)) }
+
+    fn history(mine: &str, theirs: &str) -> Vec<Turn> {
+        mine.chars().zip(theirs.chars()).map(|(mine, theirs)| Turn { mine, theirs }).collect()
+    }
+
+    #[test]
+    fn same_beta_same_move() {
+        let h = history("RPSSPRRPSPRS", "RRPSPPRSSRPR");
+        for name in ["uniform", "frequency", "markov:1", "markov:3", "iocaine"] {
+            let s = by_name(name).unwrap();
+            for n in 0..20 {
+                assert_eq!(s.choose(&h, &beta(n)), s.choose(&h, &beta(n)), "{name}");
+                assert_eq!(s.choose(&h[..5], &beta(n)), by_name(name).unwrap().choose(&h[..5], &beta(n)), "{name}");
+            }
+        }
+        let uniform: std::collections::BTreeSet<char> = (0..30).map(|n| Uniform.choose(&[], &beta(n))).collect();
+        assert_eq!(uniform.len(), 3);
+    }
+
+    #[test]
+    fn markov_orders_one_to_five() {
+        for order in 1..=5 {
+            assert_eq!(by_name(&format!("markov:{order}")).unwrap().name(), format!("markov:{order}"));
+        }
+        assert!(by_name("markov:0").is_none());
+        assert!(by_name("markov:6").is_none());
+        assert_eq!(by_name("MARKOV").unwrap().name(), "markov:2");
+        assert!(by_name("minimax").is_none());
+    }
+
+    #[test]
+    fn frequency_counters_the_most_common_move() {
+        // R weighs 0.9 + 0.81 + 0.729 against S's 1.0 for the latest turn
+        let h = history("PPPP", "RRRS");
+        for n in 0..20 {
+            assert_eq!(Frequency.choose(&h, &beta(n)), 'P');
+            assert_eq!(Frequency.choose(&history("RRRRR", "SSPSS"), &beta(n)), 'R');
+        }
+    }
+
+    #[test]
+    fn markov_counters_the_usual_follow_up() {
+        // after R the opponent always played P
+        let h = history("SSSSSS", "RPRPRP");
+        let m = Markov { order: 1 };
+        for n in 0..20 {
+            assert_eq!(m.choose(&h[..5], &beta(n)), 'S');
+        }
+    }
+
+    /// Always draws the first option, like `Math.random = () => 0`.
+    struct First;
+
+    impl RngCore for First {
+        fn next_u32(&mut self) -> u32 { 0 }
+        fn next_u64(&mut self) -> u64 { 0 }
+        fn fill_bytes(&mut self, dest: &mut [u8]) { dest.fill(0) }
+        fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> { dest.fill(0); Ok(()) }
+    }
+
+    #[test]
+    fn iocaine_replays_like_the_web_engine() {
+        // apps/web/src/app/ai/IocainePowderAI.ts with Math.random = () => 0, fed
+        // `theirs` one move at a time: getNextMove(null), then getNextMove(theirs[i])
+        let theirs = "RSSRPSRSPSSPSSSPRSPSPSRRPSPRSSSPRSRPPRPPPSRPSRSRRSPPPSSRPSRPSRRPRSRPPPPPPPSSSSRSRSPRPSRSPRRSSSSRRPPSPRPPPSSSSPSRRSRPPSSR";
+        let web = "RPRRSRPRPRSRSSSRSRPSRSSPSPRSPSPRPRRPSPRPPSRSPSRRRSRRSRSPSPSRRPSRRRRSSRRSSSSPSSRRSPPSPSRPPPRRPPSPPRPSSSRRSSPRPRPPPPRRRPSSR";
+        let h = history(web, theirs);
+        for t in 0..=theirs.len() {
+            assert_eq!(Iocaine::play(&h[..t], &mut First), web.as_bytes()[t] as char, "after {t} turns");
+        }
+    }
+}
@@ -0,0 +1,204 @@
+//! Feeds Jetstream events into the [`Store`].
+//!
+//! Events come live from `JETSTREAM_URL` (subscribed to posts, round anchors
+//! and match digests, resuming from the stored cursor) or from a local JSONL
+//! file of the same events (`FEED_EVENT_LOG`), replayed and then tailed.
+//! Posts are kept when they carry one of the feed's hashtags; anchors and
+//! digests only from the publisher's repo, when one is configured.
+
+use crate::store::Store;
+use futures::{SinkExt, StreamExt};
+use rps_shared_types::jetstream::{read_new_events, JetstreamEvent};
+use rps_shared_types::lexicon::{LexiconRecord, MatchDigest, RoundAnchor};
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
+
+pub const POST: &str = "app.bsky.feed.post";
+
+/// Which events are indexed.
+#[derive(Debug, Clone)]
+pub struct Filter {
+    /// Lowercase, without `#`.
+    pub hashtags: Vec<String>,
+    /// Repo of the tournament records and announcement posts; `None` trusts any repo.
+    pub publisher: Option<String>,
+}
+
+impl Filter {
+    fn trusted(&self, did: &str) -> bool { self.publisher.as_deref().is_none_or(|p| p == did) }
+
+    /// Whether a post record carries one of the hashtags, as a tag facet, in
+    /// `tags`, or as a `#word` in its text.
+    pub fn tagged(&self, post: &Value) -> bool {
+        let wanted = |t: &str| self.hashtags.iter().any(|h| h.eq_ignore_ascii_case(t.trim_start_matches('#')));
+        let facet_tags = post["facets"].as_array().into_iter().flatten()
+            .flat_map(|f| f["features"].as_array().into_iter().flatten())
+            .filter(|f| f["$type"] == "app.bsky.richtext.facet#tag")
+            .filter_map(|f| f["tag"].as_str());
+        let self_tags = post["tags"].as_array().into_iter().flatten().filter_map(Value::as_str);
+        let text_tags = post["text"].as_str().unwrap_or_default().split_whitespace()
+            .filter(|w| w.starts_with('#'))
+            .map(|w| w.trim_end_matches(|c: char| !c.is_alphanumeric() && c != '_'));
+        facet_tags.chain(self_tags).chain(text_tags).any(wanted)
+    }
+}
+
+/// Indexes one event. Returns whether the store changed.
+pub fn apply(store: &Store, filter: &Filter, ev: &JetstreamEvent) -> rusqlite::Result<bool> {
+    let (Some(commit), Some(uri)) = (ev.commit(), ev.uri()) else { return Ok(false) };
+    let record = commit.record.as_ref().filter(|_| commit.operation != "delete");
+    match (commit.collection.as_str(), record) {
+        (POST, None) => store.delete_post(&uri),
+        (POST, Some(post)) if filter.tagged(post) => {
+            let cid = commit.cid.as_deref().unwrap_or_default();
+            store.add_post(&uri, cid, &ev.did, filter.publisher.as_deref() == Some(ev.did.as_str()), ev.time_us)?;
+            Ok(true)
+        }
+        (RoundAnchor::NSID, Some(rec)) if filter.trusted(&ev.did) => match RoundAnchor::from_record(rec.clone()) {
+            Ok(a) => store.add_record(&uri, RoundAnchor::NSID, &a.tid, &[], rec, ev.time_us).map(|_| true),
+            Err(err) => { tracing::warn!(%uri, %err, "skipping off-schema round anchor"); Ok(false) }
+        },
+        (MatchDigest::NSID, Some(rec)) if filter.trusted(&ev.did) => match MatchDigest::from_record(rec.clone()) {
+            Ok(d) => {
+                let players: Vec<&str> = [d.p1.as_str(), d.p2.as_str()].into_iter().filter(|p| p.starts_with("did:")).collect();
+                store.add_record(&uri, MatchDigest::NSID, &d.tid, &players, rec, ev.time_us).map(|_| true)
+            }
+            Err(err) => { tracing::warn!(%uri, %err, "skipping off-schema match digest"); Ok(false) }
+        },
+        _ => Ok(false),
+    }
+}
+
+fn apply_logged(store: &Store, filter: &Filter, ev: &JetstreamEvent) {
+    match apply(store, filter, ev) {
+        Ok(true) => tracing::debug!(uri = ?ev.uri(), "indexed"),
+        Ok(false) => {}
+        Err(err) => tracing::error!(%err, "index write failed"),
+    }
+}
+
+/// Starts the configured sources.
+pub fn spawn(store: &'static Store, filter: Filter) {
+    if let Ok(path) = std::env::var("FEED_EVENT_LOG") {
+        let poll_ms: u64 = std::env::var("FEED_POLL_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(1_000);
+        let filter = filter.clone();
+        tokio::spawn(async move {
+            let mut offset = 0u64;
+            let mut ticker = tokio::time::interval(Duration::from_millis(poll_ms));
+            loop {
+                ticker.tick().await;
+                match read_new_events(&path, offset) {
+                    Ok((events, next)) => {
+                        offset = next;
+                        for ev in events {
+                            match ev {
+                                Ok(ev) => apply_logged(store, &filter, &ev),
+                                Err(err) => tracing::warn!(%err, "skipping malformed event line"),
+                            }
+                        }
+                    }
+                    Err(err) => tracing::debug!(%err, %path, "event log unreadable"),
+                }
+            }
+        });
+    }
+    if let Ok(url) = std::env::var("JETSTREAM_URL") {
+        tokio::spawn(follow_jetstream(url, store, filter));
+    }
+}
+
+/// Subscribes to Jetstream, reconnecting with backoff and resuming from the
+/// stored cursor, which is saved at most once a second.
+async fn follow_jetstream(url: String, store: &'static Store, filter: Filter) {
+    let mut backoff = Duration::from_secs(1);
+    let mut last = store.cursor().ok().flatten();
+    loop {
+        let mut sub = format!("{}{}wantedCollections={}&wantedCollections={}&wantedCollections={}",
+            url, if url.contains('?') { '&' } else { '?' }, POST, RoundAnchor::NSID, MatchDigest::NSID);
+        if let Some(c) = last { sub.push_str(&format!("&cursor={}", c)); }
+        match tokio_tungstenite::connect_async(&sub).await {
+            Ok((mut ws, _)) => {
+                tracing::info!(%url, cursor = ?last, "following jetstream");
+                backoff = Duration::from_secs(1);
+                let mut saved = Instant::now();
+                while let Some(msg) = ws.next().await {
+                    match msg {
+                        Ok(Message::Text(txt)) => {
+                            let Ok(ev) = serde_json::from_str::<JetstreamEvent>(&txt) else { continue };
+                            apply_logged(store, &filter, &ev);
+                            last = Some(ev.time_us);
+                            if saved.elapsed() >= Duration::from_secs(1) {
+                                if let Err(err) = store.set_cursor(ev.time_us) { tracing::error!(%err, "cursor write failed"); }
+                                saved = Instant::now();
+                            }
+                        }
+                        Ok(Message::Ping(p)) => { let _ = ws.send(Message::Pong(p)).await; }
+                        Ok(Message::Close(_)) | Err(_) => break,
+                        Ok(_) => {}
+                    }
+                }
+                if let Some(c) = last { let _ = store.set_cursor(c); }
+                tracing::warn!("jetstream connection closed");
+            }
+            Err(err) => tracing::warn!(%err, "jetstream connect failed"),
+        }
+        tokio::time::sleep(backoff).await;
+        backoff = (backoff * 2).min(Duration::from_secs(30));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PUB: &str = "did:plc:pub";
+
+    fn filter() -> Filter { Filter { hashtags: vec!["tournamentrps".into()], publisher: Some(PUB.into()) } }
+
+    fn event(repo: &str, collection: &str, rkey: &str, time_us: i64, record: Option<Value>) -> JetstreamEvent {
+        let op = if record.is_some() { "create" } else { "delete" };
+        serde_json::from_value(json!({
+            "did": repo, "time_us": time_us, "kind": "commit",
+            "commit": { "rev": "r", "operation": op, "collection": collection, "rkey": rkey, "cid": format!("c{rkey}"), "record": record }
+        })).unwrap()
+    }
+
+    fn post(text: &str) -> Value { json!({ "$type": POST, "text": text, "createdAt": "2026-10-18T12:00:00Z" }) }
+
+    fn digest(tid: &str, p1: &str, p2: &str) -> Value {
+        json!({ "tid": tid, "round": 1, "mid": "m", "p1": p1, "p2": p2, "winner": p1, "commitsRoot": "cc", "turns": 3, "postedAt": "2026-10-18T12:00:00Z" })
+    }
+
+    #[test]
+    fn finds_hashtags_in_facets_tags_and_text() {
+        let f = filter();
+        assert!(f.tagged(&post("gg #TournamentRPS!")));
+        assert!(f.tagged(&post("#tournamentrps")));
+        assert!(!f.tagged(&post("TournamentRPS without the hash")));
+        assert!(!f.tagged(&post("#TournamentRPSx")));
+        assert!(f.tagged(&json!({ "text": "gg", "tags": ["TournamentRPS"] })));
+        let faceted = json!({ "text": "gg", "facets": [{ "index": { "byteStart": 0, "byteEnd": 2 }, "features": [{ "$type": "app.bsky.richtext.facet#tag", "tag": "TournamentRPS" }] }] });
+        assert!(f.tagged(&faceted));
+    }
+
+    #[test]
+    fn indexes_players_from_published_digests_only() {
+        let store = Store::open(":memory:").unwrap();
+        let f = filter();
+        let feed = || store.feed(0, None, 10).unwrap().into_iter().map(|i| i.uri).collect::<Vec<_>>();
+        assert!(apply(&store, &f, &event("did:plc:alice", POST, "1", 10, Some(post("my match #TournamentRPS")))).unwrap());
+        assert!(!apply(&store, &f, &event("did:plc:alice", POST, "2", 11, Some(post("lunch")))).unwrap());
+        assert!(feed().is_empty());
+        // a digest from someone else's repo does not make alice a player
+        assert!(!apply(&store, &f, &event("did:plc:mallory", MatchDigest::NSID, "d", 12, Some(digest("cup", "did:plc:alice", "did:plc:bob")))).unwrap());
+        assert!(feed().is_empty());
+        assert!(!apply(&store, &f, &event(PUB, MatchDigest::NSID, "bad", 13, Some(json!({ "tid": "cup" })))).unwrap());
+        assert!(apply(&store, &f, &event(PUB, MatchDigest::NSID, "d", 14, Some(digest("cup", "did:plc:alice", "did:plc:bob")))).unwrap());
+        assert_eq!(feed(), ["at://did:plc:alice/app.bsky.feed.post/1"]);
+        assert!(apply(&store, &f, &event(PUB, POST, "3", 15, Some(post("Round 2 #TournamentRPS")))).unwrap());
+        assert!(apply(&store, &f, &event("did:plc:alice", POST, "1", 16, None)).unwrap());
+        assert_eq!(feed(), [format!("at://{PUB}/app.bsky.feed.post/3")]);
+    }
+}
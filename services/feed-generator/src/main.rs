@@ -0,0 +1,138 @@
+use axum::{routing::get, Router, Json, extract::Query};
+use axum::http::StatusCode;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::SocketAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::TcpListener;
+
+mod ingest;
+mod store;
+
+use store::Store;
+
+const GENERATOR: &str = "app.bsky.feed.generator";
+
+/// Feed settings from env.
+struct Config {
+    /// Public host name; the default service DID is `did:web:{hostname}`.
+    hostname: String,
+    service_did: String,
+    /// Account that publishes the feed record and the tournament records.
+    publisher: Option<String>,
+    rkey: String,
+    hashtags: Vec<String>,
+    live_hours: i64,
+}
+
+impl Config {
+    fn from_env() -> Self {
+        let var = |k: &str| std::env::var(k).ok().filter(|v| !v.is_empty());
+        let hostname = var("FEED_HOSTNAME").unwrap_or_else(|| "localhost:8086".to_string());
+        Config {
+            service_did: var("FEED_SERVICE_DID").unwrap_or_else(|| format!("did:web:{}", hostname.replace(':', "%3A"))),
+            hostname,
+            publisher: var("FEED_PUBLISHER_DID"),
+            rkey: var("FEED_RKEY").unwrap_or_else(|| "tournamentrps-live".to_string()),
+            hashtags: var("FEED_HASHTAGS").unwrap_or_else(|| "TournamentRPS".to_string())
+                .split(',').map(|t| t.trim().trim_start_matches('#').to_lowercase()).filter(|t| !t.is_empty()).collect(),
+            live_hours: var("FEED_LIVE_HOURS").and_then(|v| v.parse().ok()).unwrap_or(24),
+        }
+    }
+
+    /// `at://` URI of the feed's `app.bsky.feed.generator` record.
+    fn feed_uri(&self) -> String {
+        format!("at://{}/{}/{}", self.publisher.as_deref().unwrap_or(&self.service_did), GENERATOR, self.rkey)
+    }
+}
+
+static CONFIG: Lazy<Config> = Lazy::new(Config::from_env);
+// feed index at FEED_DB_PATH
+static STORE: Lazy<Store> = Lazy::new(|| {
+    let path = std::env::var("FEED_DB_PATH").unwrap_or_else(|_| "feed-index.sqlite3".to_string());
+    Store::open(&path).unwrap_or_else(|e| panic!("cannot open feed index {path}: {e}"))
+});
+
+/// Service entrypoint: indexes tournament activity from Jetstream and serves
+/// the "TournamentRPS live" feed skeleton.
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt().with_env_filter("info").init();
+    if CONFIG.publisher.is_none() {
+        tracing::warn!("FEED_PUBLISHER_DID not set; tournament records are trusted from any repo");
+    }
+    ingest::spawn(&STORE, ingest::Filter { hashtags: CONFIG.hashtags.clone(), publisher: CONFIG.publisher.clone() });
+
+    let app = Router::new()
+        .route("/healthz", get(|| async { "ok" }))
+        .route("/.well-known/did.json", get(did_document))
+        .route("/xrpc/app.bsky.feed.describeFeedGenerator", get(describe))
+        .route("/xrpc/app.bsky.feed.getFeedSkeleton", get(feed_skeleton));
+
+    let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8086);
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    tracing::info!(%addr, feed = %CONFIG.feed_uri(), "listening");
+    let listener = TcpListener::bind(addr).await.unwrap();
+    axum::serve(listener, app).await.unwrap();
+}
+
+/// `did:web` document naming this host as the feed generator service.
+async fn did_document() -> Result<Json<serde_json::Value>, StatusCode> {
+    if !CONFIG.service_did.starts_with("did:web:") { return Err(StatusCode::NOT_FOUND); }
+    Ok(Json(json!({
+        "@context": ["https://www.w3.org/ns/did/v1"],
+        "id": CONFIG.service_did,
+        "service": [{ "id": "#bsky_fg", "type": "BskyFeedGenerator", "serviceEndpoint": format!("https://{}", CONFIG.hostname) }],
+    })))
+}
+
+async fn describe() -> Json<serde_json::Value> {
+    Json(json!({ "did": CONFIG.service_did, "feeds": [{ "uri": CONFIG.feed_uri() }] }))
+}
+
+#[derive(Debug, Deserialize)]
+struct SkeletonQuery {
+    feed: String,
+    limit: Option<usize>,
+    cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct SkeletonPost { post: String }
+
+#[derive(Debug, Serialize)]
+struct Skeleton {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cursor: Option<String>,
+    feed: Vec<SkeletonPost>,
+}
+
+type XrpcError = (StatusCode, Json<serde_json::Value>);
+
+fn xrpc_error(code: StatusCode, error: &str, message: impl std::fmt::Display) -> XrpcError {
+    (code, Json(json!({ "error": error, "message": message.to_string() })))
+}
+
+/// Newest tagged posts by players of tournaments active in the last
+/// `FEED_LIVE_HOURS`, plus the publisher's announcements. `limit` is 1..=100
+/// (default 50); the cursor is `{indexed_us}::{cid}` of the last item.
+async fn feed_skeleton(Query(q): Query<SkeletonQuery>) -> Result<Json<Skeleton>, XrpcError> {
+    if q.feed != CONFIG.feed_uri() {
+        return Err(xrpc_error(StatusCode::BAD_REQUEST, "UnknownFeed", format!("unknown feed {}", q.feed)));
+    }
+    let limit = q.limit.unwrap_or(50);
+    if !(1..=100).contains(&limit) {
+        return Err(xrpc_error(StatusCode::BAD_REQUEST, "InvalidRequest", "limit must be between 1 and 100"));
+    }
+    let cursor = match q.cursor.as_deref() {
+        Some(c) => Some(store::parse_cursor(c).ok_or_else(|| xrpc_error(StatusCode::BAD_REQUEST, "InvalidRequest", "malformed cursor"))?),
+        None => None,
+    };
+    let now_us = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_micros() as i64).unwrap_or(0);
+    let live_since = now_us - CONFIG.live_hours * 3_600_000_000;
+    let items = STORE.feed(live_since, cursor.as_ref(), limit)
+        .map_err(|e| xrpc_error(StatusCode::INTERNAL_SERVER_ERROR, "InternalServerError", e))?;
+    let cursor = (items.len() == limit).then(|| items.last().map(|i| i.cursor())).flatten();
+    Ok(Json(Skeleton { cursor, feed: items.into_iter().map(|i| SkeletonPost { post: i.uri }).collect() }))
+}
@@ -0,0 +1,176 @@
+//! SQLite index behind the feed (`FEED_DB_PATH`).
+//!
+//! Holds the tagged posts that may appear in the feed, the tournament records
+//! they are judged against (round anchors and match digests), which DIDs played
+//! in which tournament, and the Jetstream cursor to resume from.
+
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::Mutex;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS posts (
+    uri TEXT PRIMARY KEY,
+    cid TEXT NOT NULL,
+    author TEXT NOT NULL,
+    announcement INTEGER NOT NULL,
+    indexed_us INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS posts_order ON posts (indexed_us DESC, cid DESC);
+CREATE INDEX IF NOT EXISTS posts_author ON posts (author);
+CREATE TABLE IF NOT EXISTS records (
+    uri TEXT PRIMARY KEY,
+    collection TEXT NOT NULL,
+    tid TEXT NOT NULL,
+    record TEXT NOT NULL,
+    indexed_us INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS tournaments (
+    tid TEXT PRIMARY KEY,
+    last_us INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS players (
+    tid TEXT NOT NULL,
+    did TEXT NOT NULL,
+    PRIMARY KEY (tid, did)
+);
+CREATE TABLE IF NOT EXISTS state (
+    key TEXT PRIMARY KEY,
+    value INTEGER NOT NULL
+);
+";
+
+/// A post in feed order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeedItem { pub uri: String, pub cid: String, pub indexed_us: i64 }
+
+impl FeedItem {
+    /// Cursor for the page after this item.
+    pub fn cursor(&self) -> String { format!("{}::{}", self.indexed_us, self.cid) }
+}
+
+/// Parses a cursor made by [`FeedItem::cursor`].
+pub fn parse_cursor(cursor: &str) -> Option<(i64, String)> {
+    let (us, cid) = cursor.split_once("::")?;
+    Some((us.parse().ok()?, cid.to_string()))
+}
+
+pub struct Store { db: Mutex<Connection> }
+
+impl Store {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let db = Connection::open(path)?;
+        db.execute_batch("PRAGMA journal_mode = WAL;")?;
+        db.execute_batch(SCHEMA)?;
+        Ok(Store { db: Mutex::new(db) })
+    }
+
+    /// Indexes a tagged post; `announcement` posts come from the publisher and
+    /// are shown whoever they mention.
+    pub fn add_post(&self, uri: &str, cid: &str, author: &str, announcement: bool, indexed_us: i64) -> rusqlite::Result<()> {
+        self.db.lock().unwrap().execute(
+            "INSERT OR IGNORE INTO posts (uri, cid, author, announcement, indexed_us) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![uri, cid, author, announcement, indexed_us],
+        ).map(|_| ())
+    }
+
+    pub fn delete_post(&self, uri: &str) -> rusqlite::Result<bool> {
+        self.db.lock().unwrap().execute("DELETE FROM posts WHERE uri = ?1", params![uri]).map(|n| n > 0)
+    }
+
+    /// Indexes a round anchor or match digest: marks the tournament active at
+    /// `indexed_us` and records `players` as having played in it.
+    pub fn add_record(&self, uri: &str, collection: &str, tid: &str, players: &[&str], record: &serde_json::Value, indexed_us: i64) -> rusqlite::Result<()> {
+        let mut db = self.db.lock().unwrap();
+        let tx = db.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO records (uri, collection, tid, record, indexed_us) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![uri, collection, tid, record.to_string(), indexed_us],
+        )?;
+        tx.execute(
+            "INSERT INTO tournaments (tid, last_us) VALUES (?1, ?2) ON CONFLICT (tid) DO UPDATE SET last_us = max(last_us, excluded.last_us)",
+            params![tid, indexed_us],
+        )?;
+        for did in players {
+            tx.execute("INSERT OR IGNORE INTO players (tid, did) VALUES (?1, ?2)", params![tid, did])?;
+        }
+        tx.commit()
+    }
+
+    /// The feed, newest first: announcements plus posts by anyone who played in
+    /// a tournament with activity since `live_since_us`. Starts after `cursor`.
+    pub fn feed(&self, live_since_us: i64, cursor: Option<&(i64, String)>, limit: usize) -> rusqlite::Result<Vec<FeedItem>> {
+        let db = self.db.lock().unwrap();
+        let mut stmt = db.prepare_cached(
+            "SELECT p.uri, p.cid, p.indexed_us FROM posts p
+             WHERE (p.announcement = 1 OR EXISTS (
+                 SELECT 1 FROM players pl JOIN tournaments t ON t.tid = pl.tid WHERE pl.did = p.author AND t.last_us >= ?1))
+               AND (?2 IS NULL OR p.indexed_us < ?2 OR (p.indexed_us = ?2 AND p.cid < ?3))
+             ORDER BY p.indexed_us DESC, p.cid DESC LIMIT ?4",
+        )?;
+        let (us, cid) = cursor.map(|(us, cid)| (Some(*us), Some(cid.as_str()))).unwrap_or_default();
+        let rows = stmt.query_map(params![live_since_us, us, cid, limit as i64], |r| {
+            Ok(FeedItem { uri: r.get(0)?, cid: r.get(1)?, indexed_us: r.get(2)? })
+        })?;
+        rows.collect()
+    }
+
+    /// Jetstream `time_us` to resume from.
+    pub fn cursor(&self) -> rusqlite::Result<Option<i64>> {
+        self.db.lock().unwrap().query_row("SELECT value FROM state WHERE key = 'cursor'", [], |r| r.get(0)).optional()
+    }
+
+    pub fn set_cursor(&self, time_us: i64) -> rusqlite::Result<()> {
+        self.db.lock().unwrap().execute("INSERT OR REPLACE INTO state (key, value) VALUES ('cursor', ?1)", params![time_us]).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn store() -> Store { Store::open(":memory:").unwrap() }
+
+    fn uris(items: &[FeedItem]) -> Vec<&str> { items.iter().map(|i| i.uri.as_str()).collect() }
+
+    #[test]
+    fn shows_announcements_and_posts_by_live_players() {
+        let s = store();
+        s.add_post("at://pub/p/1", "c1", "did:plc:pub", true, 10).unwrap();
+        s.add_post("at://alice/p/1", "c2", "did:plc:alice", false, 20).unwrap();
+        s.add_post("at://carol/p/1", "c3", "did:plc:carol", false, 30).unwrap();
+        assert_eq!(uris(&s.feed(0, None, 10).unwrap()), ["at://pub/p/1"]);
+        s.add_record("at://pub/d/1", "zone.peace.rps.matchDigest", "cup", &["did:plc:alice", "did:plc:bob"], &json!({}), 100).unwrap();
+        assert_eq!(uris(&s.feed(0, None, 10).unwrap()), ["at://alice/p/1", "at://pub/p/1"]);
+        // once the tournament has gone quiet, only announcements remain
+        assert_eq!(uris(&s.feed(101, None, 10).unwrap()), ["at://pub/p/1"]);
+        assert!(s.delete_post("at://alice/p/1").unwrap());
+        assert_eq!(uris(&s.feed(0, None, 10).unwrap()), ["at://pub/p/1"]);
+    }
+
+    #[test]
+    fn pages_with_cursors() {
+        let s = store();
+        for (i, us) in [5, 7, 7, 9].into_iter().enumerate() {
+            s.add_post(&format!("at://pub/p/{i}"), &format!("c{i}"), "did:plc:pub", true, us).unwrap();
+        }
+        let first = s.feed(0, None, 2).unwrap();
+        assert_eq!(uris(&first), ["at://pub/p/3", "at://pub/p/2"]);
+        let after = parse_cursor(&first[1].cursor()).unwrap();
+        assert_eq!(after, (7, "c2".to_string()));
+        assert_eq!(uris(&s.feed(0, Some(&after), 2).unwrap()), ["at://pub/p/1", "at://pub/p/0"]);
+        assert!(parse_cursor("7").is_none());
+    }
+
+    #[test]
+    fn keeps_the_latest_activity_and_cursor() {
+        let s = store();
+        s.add_record("at://pub/a/2", "zone.peace.rps.roundAnchor", "cup", &[], &json!({}), 50).unwrap();
+        s.add_record("at://pub/d/1", "zone.peace.rps.matchDigest", "cup", &["did:plc:alice"], &json!({}), 20).unwrap();
+        s.add_post("at://alice/p/1", "c1", "did:plc:alice", false, 1).unwrap();
+        assert_eq!(s.feed(50, None, 10).unwrap().len(), 1);
+        assert_eq!(s.cursor().unwrap(), None);
+        s.set_cursor(42).unwrap();
+        assert_eq!(s.cursor().unwrap(), Some(42));
+    }
+}
@@ -0,0 +1,250 @@
+//! Whole-match verification for auditors.
+//!
+//! A [`MatchTranscript`] lists every turn's commits and openings (or the
+//! substituted move and its VRF proof when a player timed out). [`verify_match`]
+//! re-checks each opening and proof, recomputes turn results and the score, and
+//! returns the Merkle root of all commits. Turns are checked in parallel, and [`verify_matches`] spreads
+//! whole tournaments across the rayon pool.
+
+use crate::{verify_reveal, CommitError, Move, Opening, Version};
+use rayon::prelude::*;
+use rps_crypto::merkle::{self, Mode};
+use rps_crypto::moves::{move_from_output, substitution_alpha};
+use rps_crypto::vrf::PublicKey;
+use rps_shared_types::Substitution;
+use serde::{Deserialize, Serialize};
+
+/// Wins needed to take a match.
+pub const WINS_TO_MATCH: u32 = 5;
+
+/// Everything needed to re-verify one match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MatchTranscript {
+    pub match_id: String,
+    /// P1's DID.
+    pub p1: String,
+    /// P2's DID.
+    pub p2: String,
+    /// Commit encoding the match was played under.
+    #[serde(default)]
+    pub version: Version,
+    pub turns: Vec<TurnRecord>,
+}
+
+/// One turn as recorded by signaling.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TurnRecord {
+    pub turn: u32,
+    #[serde(default)]
+    pub p1: Option<Play>,
+    #[serde(default)]
+    pub p2: Option<Play>,
+    /// Result the server announced ("P1", "P2" or "DRAW"); checked if present.
+    #[serde(default)]
+    pub result: Option<String>,
+}
+
+/// What a player contributed to a turn.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Play {
+    /// A committed move and its opening.
+    Revealed { commit: String, move_: String, nonce: String },
+    /// The player missed the deadline and the server substituted a move;
+    /// without a proof the play counts as invalid.
+    Substituted {
+        substituted: String,
+        #[serde(default)]
+        proof: Option<SubstitutionProof>,
+    },
+}
+
+/// VRF proof of a substituted move, as in [`Substitution`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubstitutionProof {
+    pub vrf_output: String,
+    pub vrf_proof: String,
+    /// Key the move was proven with; compare it to the fairness or signaling `/vrf_pubkey`.
+    pub public_key: String,
+    pub drand_round: u64,
+    /// Randomness of `drand_round` (hex); all zeros for round 0.
+    pub drand_randomness: String,
+}
+
+impl From<&Substitution> for SubstitutionProof {
+    fn from(s: &Substitution) -> Self {
+        SubstitutionProof {
+            vrf_output: s.vrf_output.clone(),
+            vrf_proof: s.vrf_proof.clone(),
+            public_key: s.public_key.clone(),
+            drand_round: s.drand_round,
+            drand_randomness: s.drand_randomness.clone(),
+        }
+    }
+}
+
+/// Per-player outcome for one turn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayStatus {
+    Valid,
+    Substituted,
+    Invalid,
+    Missing,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TurnAudit {
+    pub turn: u32,
+    pub p1: PlayStatus,
+    pub p2: PlayStatus,
+    /// Recomputed result; `None` when either side is invalid or missing.
+    pub result: Option<String>,
+    /// Whether the announced result matches the recomputed one (`None` if none was announced).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_matches: Option<bool>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+impl TurnAudit {
+    pub fn valid(&self) -> bool {
+        self.result.is_some() && self.result_matches != Some(false)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Score { pub p1: u32, pub p2: u32 }
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchAudit {
+    pub match_id: String,
+    /// True when every turn verifies and turn numbers are 1, 2, 3, ...
+    pub valid: bool,
+    pub turns: Vec<TurnAudit>,
+    pub score: Score,
+    /// "P1" or "P2" once a side reached [`WINS_TO_MATCH`].
+    pub winner: Option<String>,
+    /// Positional Merkle root (hex, `rps_crypto::merkle`) over the commits in
+    /// turn order, P1 before P2.
+    pub commits_root: String,
+    /// VRF keys that proved the substituted moves, for checking against the published ones.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub vrf_keys: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<String>,
+}
+
+/// Verifies a single match; its turns are checked in parallel.
+pub fn verify_match(t: &MatchTranscript) -> MatchAudit {
+    let turns: Vec<TurnAudit> = t.turns.par_iter().map(|r| verify_turn(t, r)).collect();
+    let mut errors = Vec::new();
+    for (i, r) in t.turns.iter().enumerate() {
+        if r.turn != i as u32 + 1 {
+            errors.push(format!("turn {} recorded at position {}", r.turn, i + 1));
+            break;
+        }
+    }
+    let mut score = Score::default();
+    let mut winner = None;
+    for a in &turns {
+        match a.result.as_deref() {
+            Some("P1") => score.p1 += 1,
+            Some("P2") => score.p2 += 1,
+            _ => {}
+        }
+        if winner.is_none() && (score.p1 >= WINS_TO_MATCH || score.p2 >= WINS_TO_MATCH) {
+            winner = Some(if score.p1 >= WINS_TO_MATCH { "P1" } else { "P2" }.to_string());
+        } else if winner.is_some() && a.result.is_some() {
+            errors.push(format!("turn {} played after the match was decided", a.turn));
+        }
+    }
+    let mut commits = Vec::new();
+    let mut vrf_keys: Vec<String> = Vec::new();
+    for r in &t.turns {
+        for (label, play) in [("p1", &r.p1), ("p2", &r.p2)] {
+            match play {
+                Some(Play::Revealed { commit, .. }) => match hex::decode(commit.trim_start_matches("0x")) {
+                    Ok(c) => commits.push(c),
+                    Err(_) => errors.push(format!("turn {} {label}: commit is not hex, left out of commits_root", r.turn)),
+                },
+                Some(Play::Substituted { proof: Some(p), .. }) if !vrf_keys.contains(&p.public_key) => vrf_keys.push(p.public_key.clone()),
+                _ => {}
+            }
+        }
+    }
+    MatchAudit {
+        match_id: t.match_id.clone(),
+        valid: errors.is_empty() && turns.iter().all(TurnAudit::valid),
+        turns,
+        score,
+        winner,
+        commits_root: hex::encode(merkle::root(Mode::Positional, &commits)),
+        vrf_keys,
+        errors,
+    }
+}
+
+/// Verifies many matches across the rayon pool, preserving order.
+pub fn verify_matches(ts: &[MatchTranscript]) -> Vec<MatchAudit> {
+    ts.par_iter().map(verify_match).collect()
+}
+
+fn verify_turn(t: &MatchTranscript, r: &TurnRecord) -> TurnAudit {
+    let mut errors = Vec::new();
+    let mut side = |did: &str, label: &str, play: &Option<Play>| -> (PlayStatus, Option<Move>) {
+        let res = match play {
+            None => { errors.push(format!("{label}: no move recorded")); return (PlayStatus::Missing, None); }
+            Some(Play::Substituted { substituted, proof }) =>
+                verify_substitution(&t.match_id, r.turn, did, substituted, proof.as_ref()).map(|m| (PlayStatus::Substituted, m)),
+            Some(Play::Revealed { commit, move_, nonce }) => move_.parse::<Move>().and_then(|mv| {
+                let o = Opening { match_id: &t.match_id, did, turn: r.turn, mv, nonce };
+                verify_reveal(t.version, commit, &o).map(|()| (PlayStatus::Valid, mv))
+            }).map_err(|e| describe(&e)),
+        };
+        match res {
+            Ok((status, mv)) => (status, Some(mv)),
+            Err(e) => { errors.push(format!("{label}: {e}")); (PlayStatus::Invalid, None) }
+        }
+    };
+    let (p1, m1) = side(&t.p1, "p1", &r.p1);
+    let (p2, m2) = side(&t.p2, "p2", &r.p2);
+    let result = match (m1, m2) {
+        (Some(a), Some(b)) => Some(if a.beats(b) { "P1" } else if b.beats(a) { "P2" } else { "DRAW" }.to_string()),
+        _ => None,
+    };
+    let result_matches = r.result.as_ref().map(|claimed| Some(claimed) == result.as_ref());
+    if result_matches == Some(false) {
+        errors.push(format!("announced result {:?} differs from recomputed {:?}", r.result.as_deref().unwrap_or(""), result.as_deref().unwrap_or("none")));
+    }
+    TurnAudit { turn: r.turn, p1, p2, result, result_matches, errors }
+}
+
+/// Checks that `proof` verifies over the substitution input for this match,
+/// turn and DID, and that `substituted` is the move its output maps to.
+fn verify_substitution(mid: &str, turn: u32, did: &str, substituted: &str, proof: Option<&SubstitutionProof>) -> Result<Move, String> {
+    let mv = substituted.parse::<Move>().map_err(|e| describe(&e))?;
+    let p = proof.ok_or("substituted move has no VRF proof")?;
+    let key = hex::decode(&p.public_key).ok().and_then(|b| PublicKey::from_bytes(&b).ok()).ok_or("malformed VRF public key")?;
+    let randomness: [u8; 32] = hex::decode(&p.drand_randomness).ok().and_then(|b| b.try_into().ok()).ok_or("malformed drand randomness")?;
+    if p.drand_round == 0 && randomness != [0u8; 32] {
+        return Err("drand round 0 must use zero randomness".into());
+    }
+    let pi = hex::decode(&p.vrf_proof).map_err(|_| "malformed VRF proof")?;
+    let beta = key.verify(&substitution_alpha(mid, turn, did, p.drand_round, &randomness), &pi).map_err(|_| "VRF proof does not verify")?;
+    if hex::encode(beta) != p.vrf_output.trim_start_matches("0x") {
+        return Err("VRF output does not match its proof".into());
+    }
+    let proven = move_from_output(&beta);
+    if mv.as_char() != proven {
+        return Err(format!("substituted {mv} but the proof gives {proven}"));
+    }
+    Ok(mv)
+}
+
+fn describe(e: &CommitError) -> String {
+    match e {
+        CommitError::Mismatch => "opening does not match commit".into(),
+        other => other.to_string(),
+    }
+}
@@ -0,0 +1,185 @@
+//! Commit–reveal primitives shared by the match engine service, signaling and
+//! auditors.
+//!
+//! Two commitment encodings exist:
+//! - **v1** (legacy): `sha256(move || nonce || u32be(turn) || match_id || did)`.
+//!   Fields are concatenated without delimiters, so e.g. `match_id="m1", did="did:x"`
+//!   hashes like `match_id="m1d", did="id:x"`. Kept so existing clients keep working.
+//! - **v2**: `sha256("rps-commit-v2" || u32be(len(match_id)) || match_id || u32be(len(did)) || did
+//!   || u32be(turn) || move || nonce)`, where `move` is one ASCII byte and `nonce` is exactly
+//!   32 random bytes (64 hex chars on the wire).
+//!
+//! Published test vectors live in `test-vectors/commit.json`. Whole-match
+//! audits are in [`audit`], signed match logs in [`transcript`].
+
+pub mod audit;
+pub mod transcript;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::str::FromStr;
+
+/// Domain separator for v2 commitments; bump on any encoding change.
+pub const COMMIT_DOMAIN_V2: &[u8] = b"rps-commit-v2";
+/// v2 nonce length in bytes.
+pub const NONCE_LEN: usize = 32;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum CommitError {
+    #[error("invalid move {0:?}; expected R, P or S")]
+    InvalidMove(String),
+    #[error("v2 nonce must be {NONCE_LEN} bytes of hex")]
+    InvalidNonce,
+    #[error("malformed commit hash")]
+    MalformedCommit,
+    #[error("reveal does not match commit")]
+    Mismatch,
+}
+
+/// A rock-paper-scissors move, written `R`, `P` or `S` on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Move {
+    Rock,
+    Paper,
+    Scissors,
+}
+
+impl Move {
+    pub const ALL: [Move; 3] = [Move::Rock, Move::Paper, Move::Scissors];
+
+    pub fn as_char(self) -> char {
+        match self { Move::Rock => 'R', Move::Paper => 'P', Move::Scissors => 'S' }
+    }
+
+    pub fn from_char(c: char) -> Option<Move> {
+        match c { 'R' => Some(Move::Rock), 'P' => Some(Move::Paper), 'S' => Some(Move::Scissors), _ => None }
+    }
+
+    /// True if `self` beats `other`.
+    pub fn beats(self, other: Move) -> bool {
+        matches!((self, other), (Move::Rock, Move::Scissors) | (Move::Paper, Move::Rock) | (Move::Scissors, Move::Paper))
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}", self.as_char()) }
+}
+
+impl FromStr for Move {
+    type Err = CommitError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.trim().chars();
+        match (chars.next().and_then(Move::from_char), chars.next()) {
+            (Some(m), None) => Ok(m),
+            _ => Err(CommitError::InvalidMove(s.to_string())),
+        }
+    }
+}
+
+impl TryFrom<String> for Move {
+    type Error = CommitError;
+    fn try_from(s: String) -> Result<Self, Self::Error> { s.parse() }
+}
+
+impl From<Move> for String {
+    fn from(m: Move) -> String { m.to_string() }
+}
+
+/// Commitment encoding version.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Version {
+    #[default]
+    V1,
+    V2,
+}
+
+impl Version {
+    /// All versions, oldest first.
+    pub const ALL: [Version; 2] = [Version::V1, Version::V2];
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self { Version::V1 => "v1", Version::V2 => "v2" })
+    }
+}
+
+impl FromStr for Version {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "v1" | "1" => Ok(Version::V1),
+            "v2" | "2" => Ok(Version::V2),
+            other => Err(format!("unknown commit version {:?}", other)),
+        }
+    }
+}
+
+/// Newest version the server `accepts` that every client also offered, if any.
+pub fn negotiate(accepts: &[Version], offers: &[&[Version]]) -> Option<Version> {
+    accepts.iter().copied().filter(|v| offers.iter().all(|o| o.contains(v))).max()
+}
+
+/// The revealed fields a commitment binds.
+#[derive(Debug, Clone, Copy)]
+pub struct Opening<'a> {
+    pub match_id: &'a str,
+    pub did: &'a str,
+    pub turn: u32,
+    pub mv: Move,
+    /// Free-form string for v1; 64 hex chars for v2.
+    pub nonce: &'a str,
+}
+
+/// Legacy v1 commitment.
+pub fn commit_v1(o: &Opening) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update([o.mv.as_char() as u8]);
+    h.update(o.nonce.as_bytes());
+    h.update(o.turn.to_be_bytes());
+    h.update(o.match_id.as_bytes());
+    h.update(o.did.as_bytes());
+    h.finalize().into()
+}
+
+/// Domain-separated, length-prefixed v2 commitment over a 32-byte nonce.
+pub fn commit_v2(match_id: &str, did: &str, turn: u32, mv: Move, nonce: &[u8; NONCE_LEN]) -> [u8; 32] {
+    let mut h = Sha256::new();
+    h.update(COMMIT_DOMAIN_V2);
+    h.update((match_id.len() as u32).to_be_bytes());
+    h.update(match_id.as_bytes());
+    h.update((did.len() as u32).to_be_bytes());
+    h.update(did.as_bytes());
+    h.update(turn.to_be_bytes());
+    h.update([mv.as_char() as u8]);
+    h.update(nonce);
+    h.finalize().into()
+}
+
+/// Decodes a v2 wire nonce (64 hex chars, optional `0x`).
+pub fn parse_nonce_v2(nonce: &str) -> Result<[u8; NONCE_LEN], CommitError> {
+    let bytes = hex::decode(nonce.trim_start_matches("0x")).map_err(|_| CommitError::InvalidNonce)?;
+    bytes.try_into().map_err(|_| CommitError::InvalidNonce)
+}
+
+fn digest(version: Version, o: &Opening) -> Result<[u8; 32], CommitError> {
+    Ok(match version {
+        Version::V1 => commit_v1(o),
+        Version::V2 => commit_v2(o.match_id, o.did, o.turn, o.mv, &parse_nonce_v2(o.nonce)?),
+    })
+}
+
+/// Computes the hex commitment for `o` under `version`.
+pub fn commit(version: Version, o: &Opening) -> Result<String, CommitError> {
+    digest(version, o).map(hex::encode)
+}
+
+/// Checks that `o` opens the hex commitment `commit_hex` under `version`.
+pub fn verify_reveal(version: Version, commit_hex: &str, o: &Opening) -> Result<(), CommitError> {
+    let expected = hex::decode(commit_hex.trim_start_matches("0x")).map_err(|_| CommitError::MalformedCommit)?;
+    if expected.len() != 32 { return Err(CommitError::MalformedCommit); }
+    if digest(version, o)?[..] == expected[..] { Ok(()) } else { Err(CommitError::Mismatch) }
+}
@@ -2,8 +2,9 @@ use axum::{routing::{get, post}, Router, Json};
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use hex::ToHex;
+use axum::http::StatusCode;
+use rps_match_engine::audit::{self, MatchAudit, MatchTranscript};
+use rps_match_engine::{commit as commit_hash, verify_reveal, CommitError, Move, Opening, Version};
 
 /// Service entrypoint: exposes commit/reveal helpers for testing commit‑reveal.
 #[tokio::main]
@@ -13,41 +14,90 @@ async fn main() {
     let app = Router::new()
         .route("/healthz", get(|| async { "ok" }))
         .route("/commit", post(commit))
-        .route("/reveal", post(reveal));
+        .route("/reveal", post(reveal))
+        .route("/verify_match", post(verify_match));
 #[derive(Debug, Deserialize)]
-struct CommitReq { match_id: String, did: String, turn: u32, move_: String, nonce: String }
+struct CommitReq {
+    match_id: String,
+    did: String,
+    turn: u32,
+    move_: Move,
+    nonce: String,
+    /// `v1` (default) or `v2`; v2 needs a 64-hex-char nonce.
+    #[serde(default)]
+    version: Version,
+}
 
 #[derive(Debug, Serialize)]
-struct CommitResp { ok: bool, commit: String }
+struct CommitResp { ok: bool, commit: String, version: Version }
 
-/// Computes the canonical commit hash for (move, nonce, turn, match_id, did).
-async fn commit(Json(req): Json<CommitReq>) -> Json<CommitResp> {
-    let mut hasher = Sha256::new();
-    hasher.update(req.move_.as_bytes());
-    hasher.update(req.nonce.as_bytes());
-    hasher.update(req.turn.to_be_bytes());
-    hasher.update(req.match_id.as_bytes());
-    hasher.update(req.did.as_bytes());
-    let commit = hasher.finalize().encode_hex::<String>();
-    Json(CommitResp { ok: true, commit })
+/// Computes the commit hash for (move, nonce, turn, match_id, did) under the
+/// requested encoding version.
+async fn commit(Json(req): Json<CommitReq>) -> Result<Json<CommitResp>, (StatusCode, String)> {
+    let o = Opening { match_id: &req.match_id, did: &req.did, turn: req.turn, mv: req.move_, nonce: &req.nonce };
+    let commit = commit_hash(req.version, &o).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    Ok(Json(CommitResp { ok: true, commit, version: req.version }))
 }
 
 #[derive(Debug, Deserialize)]
-struct RevealReq { commit: String, match_id: String, did: String, turn: u32, move_: String, nonce: String }
+struct RevealReq {
+    commit: String,
+    match_id: String,
+    did: String,
+    turn: u32,
+    move_: Move,
+    nonce: String,
+    #[serde(default)]
+    version: Version,
+}
 
 #[derive(Debug, Serialize)]
-struct RevealResp { ok: bool, valid: bool }
+struct RevealResp {
+    ok: bool,
+    valid: bool,
+    version: Version,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
 
 /// Verifies a reveal against a provided commit by recomputing the hash.
+/// `ok` is false when the input is malformed (bad nonce or commit).
 async fn reveal(Json(req): Json<RevealReq>) -> Json<RevealResp> {
-    let mut hasher = Sha256::new();
-    hasher.update(req.move_.as_bytes());
-    hasher.update(req.nonce.as_bytes());
-    hasher.update(req.turn.to_be_bytes());
-    hasher.update(req.match_id.as_bytes());
-    hasher.update(req.did.as_bytes());
-    let computed = hasher.finalize().encode_hex::<String>();
-    Json(RevealResp { ok: true, valid: computed == req.commit })
+    let o = Opening { match_id: &req.match_id, did: &req.did, turn: req.turn, mv: req.move_, nonce: &req.nonce };
+    let (ok, valid, error) = match verify_reveal(req.version, &req.commit, &o) {
+        Ok(()) => (true, true, None),
+        Err(CommitError::Mismatch) => (true, false, None),
+        Err(e) => (false, false, Some(e.to_string())),
+    };
+    Json(RevealResp { ok, valid, version: req.version, error })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum VerifyMatchReq {
+    Batch { matches: Vec<MatchTranscript> },
+    One(MatchTranscript),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum VerifyMatchResp {
+    Batch { matches: Vec<MatchAudit>, valid: usize, invalid: usize },
+    One(MatchAudit),
+}
+
+/// Audits a full match transcript, or `{"matches": [...]}` for a batch.
+/// Verification runs on the rayon pool, off the async runtime.
+async fn verify_match(Json(req): Json<VerifyMatchReq>) -> Result<Json<VerifyMatchResp>, (StatusCode, String)> {
+    let resp = tokio::task::spawn_blocking(move || match req {
+        VerifyMatchReq::One(t) => VerifyMatchResp::One(audit::verify_match(&t)),
+        VerifyMatchReq::Batch { matches } => {
+            let audits = audit::verify_matches(&matches);
+            let valid = audits.iter().filter(|a| a.valid).count();
+            VerifyMatchResp::Batch { invalid: audits.len() - valid, valid, matches: audits }
+        }
+    }).await.map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    Ok(Json(resp))
 }
 
     let port: u16 = std::env::var("PORT").ok().and_then(|p| p.parse().ok()).unwrap_or(8080);
@@ -0,0 +1,213 @@
+//! Append-only, hash-chained match transcripts signed by signaling.
+//!
+//! Each [`Entry`] carries the hash of the previous one (`prev`) and its own
+//! `hash`: `sha256` of the entry's JSON without the `hash` field, keys sorted
+//! and no whitespace. The first entry's `prev` is `sha256("rps-transcript-v1" || match_id)`.
+//! When the match ends signaling signs `"rps-transcript-v1" || head` with
+//! Ed25519, where `head` is the last entry's hash.
+//!
+//! A reveal's `commit` is computed by signaling from the reveal itself, so on
+//! its own it only shows what was logged. It binds the move in advance only
+//! when the player sent `COMMIT_HASHES` first: those are logged as
+//! [`Event::CommitHashes`], and [`Transcript::to_audit`] checks reveals against them.
+//!
+//! Players fetch the log from signaling's `/matches/{mid}/transcript`, check it
+//! with [`Transcript::verify`] against the key from `/transcript_pubkey`, and
+//! re-verify the moves with [`Transcript::to_audit`] and [`crate::audit::verify_match`].
+
+use crate::audit::{MatchTranscript, Play, SubstitutionProof, TurnRecord};
+use crate::Version;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use rps_shared_types::Substitution;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Domain separator for chain genesis and signatures.
+pub const TRANSCRIPT_DOMAIN: &[u8] = b"rps-transcript-v1";
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum TranscriptError {
+    #[error("entry {0} does not link to the previous entry")]
+    BrokenChain(u64),
+    #[error("entry {0} hash does not match its contents")]
+    BadHash(u64),
+    #[error("entry {0} is out of sequence")]
+    BadSequence(u64),
+    #[error("transcript is not signed")]
+    Unsigned,
+    #[error("malformed public key or signature")]
+    MalformedSignature,
+    #[error("signature does not verify")]
+    BadSignature,
+    #[error("transcript has no open entry")]
+    NotOpened,
+}
+
+/// Something that happened in a match.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Event {
+    /// The match began; P1/P2 are unknown for ad-hoc match ids.
+    Open { p1: Option<String>, p2: Option<String>, commit_version: Version },
+    /// A player joined with these ticket claims.
+    Ticket { did: String, claims: serde_json::Value },
+    TurnStart { turn: u32, deadline_ms_epoch: i64 },
+    /// Per-turn commits a player sent before their first reveal (`COMMIT_HASHES`), turn 1 first.
+    CommitHashes { did: String, hashes: Vec<String> },
+    /// A reveal and the commit signaling computed from it under the match's version;
+    /// it matches the player's `CommitHashes` entry for the turn when there is one.
+    /// The AI seat's reveals say how its move was drawn.
+    Reveal {
+        turn: u32,
+        did: String,
+        move_: String,
+        nonce: String,
+        commit: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ai: Option<AiMove>,
+    },
+    /// A move substituted for a missed deadline, with its VRF proof.
+    Substitution { turn: u32, #[serde(flatten)] substitution: Substitution },
+    TurnResult { turn: u32, result: String, p1_move: String, p2_move: String },
+    MatchResult { winner: String, commits_root: Option<String> },
+    /// The match ended without a result (a player left or it went stale).
+    Abandoned,
+}
+
+/// Where an AI seat's move came from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum AiMove {
+    /// Fairness `/ai_move`: the VRF output and proof over the move's input,
+    /// bound to drand round `drand_epoch`.
+    Vrf { vrf_output: String, vrf_proof: String, drand_epoch: u64 },
+    /// Drawn locally while fairness was unreachable; nothing proves it.
+    Unproven,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub seq: u64,
+    pub ts_ms: i64,
+    #[serde(flatten)]
+    pub event: Event,
+    pub prev: String,
+    pub hash: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transcript {
+    pub match_id: String,
+    pub entries: Vec<Entry>,
+    /// Ed25519 key (hex) that signed the finished log.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_key: Option<String>,
+    /// Signature (hex) over `TRANSCRIPT_DOMAIN || head`; absent while the match runs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+impl Transcript {
+    pub fn new(match_id: &str) -> Self {
+        Transcript { match_id: match_id.to_string(), entries: Vec::new(), public_key: None, signature: None }
+    }
+
+    pub fn is_finished(&self) -> bool { self.signature.is_some() }
+
+    /// Hash of the last entry, or the genesis hash when empty.
+    pub fn head(&self) -> [u8; 32] {
+        match self.entries.last().and_then(|e| hex::decode(&e.hash).ok()).and_then(|h| h.try_into().ok()) {
+            Some(h) => h,
+            None => genesis(&self.match_id),
+        }
+    }
+
+    /// Appends `event` and returns the new entry.
+    pub fn append(&mut self, ts_ms: i64, event: Event) -> &Entry {
+        let mut e = Entry { seq: self.entries.len() as u64, ts_ms, event, prev: hex::encode(self.head()), hash: String::new() };
+        e.hash = hex::encode(entry_hash(&e));
+        self.entries.push(e);
+        self.entries.last().unwrap()
+    }
+
+    /// The bytes signed when the log is finished.
+    pub fn signing_message(&self) -> Vec<u8> {
+        [TRANSCRIPT_DOMAIN, &self.head()].concat()
+    }
+
+    /// Checks sequence numbers, hashes and links from genesis to head.
+    pub fn verify_chain(&self) -> Result<(), TranscriptError> {
+        let mut prev = hex::encode(genesis(&self.match_id));
+        for (i, e) in self.entries.iter().enumerate() {
+            if e.seq != i as u64 { return Err(TranscriptError::BadSequence(e.seq)); }
+            if e.prev != prev { return Err(TranscriptError::BrokenChain(e.seq)); }
+            if e.hash != hex::encode(entry_hash(e)) { return Err(TranscriptError::BadHash(e.seq)); }
+            prev = e.hash.clone();
+        }
+        Ok(())
+    }
+
+    /// Checks the chain and the signature under `public_key` (32 bytes).
+    pub fn verify(&self, public_key: &[u8]) -> Result<(), TranscriptError> {
+        self.verify_chain()?;
+        let sig = self.signature.as_deref().ok_or(TranscriptError::Unsigned)?;
+        let key = <[u8; 32]>::try_from(public_key).ok()
+            .and_then(|k| VerifyingKey::from_bytes(&k).ok())
+            .ok_or(TranscriptError::MalformedSignature)?;
+        let sig = hex::decode(sig).ok()
+            .and_then(|s| Signature::from_slice(&s).ok())
+            .ok_or(TranscriptError::MalformedSignature)?;
+        key.verify(&self.signing_message(), &sig).map_err(|_| TranscriptError::BadSignature)
+    }
+
+    /// Rebuilds the audit input from the logged reveals, substitutions and results.
+    pub fn to_audit(&self) -> Result<MatchTranscript, TranscriptError> {
+        let (p1, p2, version) = self.entries.iter().find_map(|e| match &e.event {
+            Event::Open { p1, p2, commit_version } => Some((p1.clone().unwrap_or_default(), p2.clone().unwrap_or_default(), *commit_version)),
+            _ => None,
+        }).ok_or(TranscriptError::NotOpened)?;
+        let mut turns: BTreeMap<u32, TurnRecord> = BTreeMap::new();
+        let mut precommits: BTreeMap<&str, &[String]> = BTreeMap::new();
+        for e in &self.entries {
+            let (turn, did, play) = match &e.event {
+                Event::CommitHashes { did, hashes } => {
+                    precommits.entry(did).or_insert(hashes);
+                    continue;
+                }
+                Event::Reveal { turn, did, move_, nonce, commit, .. } => {
+                    // reveals are checked against the player's own commit when they sent one
+                    let pre = precommits.get(did.as_str()).and_then(|h| h.get((*turn as usize).wrapping_sub(1)));
+                    (*turn, did, Play::Revealed { commit: pre.unwrap_or(commit).clone(), move_: move_.clone(), nonce: nonce.clone() })
+                }
+                Event::Substitution { turn, substitution } =>
+                    (*turn, &substitution.did, Play::Substituted { substituted: substitution.move_.clone(), proof: Some(SubstitutionProof::from(substitution)) }),
+                Event::TurnResult { turn, result, .. } => {
+                    turn_record(&mut turns, *turn).result = Some(result.clone());
+                    continue;
+                }
+                _ => continue,
+            };
+            let r = turn_record(&mut turns, turn);
+            if *did == p1 { r.p1 = Some(play); } else if *did == p2 { r.p2 = Some(play); }
+        }
+        // turns that started but never resolved are not part of the result
+        turns.retain(|_, r| r.result.is_some());
+        Ok(MatchTranscript { match_id: self.match_id.clone(), p1, p2, version, turns: turns.into_values().collect() })
+    }
+}
+
+fn turn_record(turns: &mut BTreeMap<u32, TurnRecord>, turn: u32) -> &mut TurnRecord {
+    turns.entry(turn).or_insert_with(|| TurnRecord { turn, p1: None, p2: None, result: None })
+}
+
+fn genesis(match_id: &str) -> [u8; 32] {
+    Sha256::new().chain_update(TRANSCRIPT_DOMAIN).chain_update(match_id).finalize().into()
+}
+
+/// `sha256` of the entry's canonical JSON (sorted keys, no `hash` field).
+fn entry_hash(e: &Entry) -> [u8; 32] {
+    let mut v = serde_json::to_value(e).expect("entries serialize");
+    if let Some(o) = v.as_object_mut() { o.remove("hash"); }
+    Sha256::digest(serde_json::to_vec(&v).expect("values serialize")).into()
+}
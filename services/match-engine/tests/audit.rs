@@ -0,0 +1,122 @@
+//! Whole-match audits.
+
+use rps_crypto::moves::{move_from_output, substitution_alpha};
+use rps_crypto::vrf::{self, SecretKey};
+use rps_match_engine::audit::{verify_match, verify_matches, MatchTranscript, Play, PlayStatus, SubstitutionProof, TurnRecord};
+use rps_match_engine::{commit, Move, Opening, Version};
+use sha2::{Digest, Sha256};
+
+const P1: &str = "did:plc:alice";
+const P2: &str = "did:plc:bob";
+
+fn play(mid: &str, did: &str, turn: u32, mv: Move) -> Play {
+    let nonce = format!("{:064x}", turn as u64 * 31 + did.len() as u64);
+    let c = commit(Version::V2, &Opening { match_id: mid, did, turn, mv, nonce: &nonce }).unwrap();
+    Play::Revealed { commit: c, move_: mv.to_string(), nonce }
+}
+
+/// A move substituted for `did` at drand round 0, proven with a fixed key.
+fn substituted(mid: &str, did: &str, turn: u32) -> Play {
+    let key = SecretKey::from_seed([5; 32]);
+    let proof = key.prove(&substitution_alpha(mid, turn, did, 0, &[0; 32]));
+    let beta = vrf::proof_to_hash(&proof).unwrap();
+    Play::Substituted {
+        substituted: move_from_output(&beta).to_string(),
+        proof: Some(SubstitutionProof {
+            vrf_output: hex::encode(beta),
+            vrf_proof: hex::encode(proof),
+            public_key: hex::encode(key.public_key().to_bytes()),
+            drand_round: 0,
+            drand_randomness: hex::encode([0u8; 32]),
+        }),
+    }
+}
+
+/// P1 plays rock every turn; P2's moves are given.
+fn transcript(mid: &str, p2_moves: &[Move]) -> MatchTranscript {
+    let turns = p2_moves.iter().enumerate().map(|(i, m)| {
+        let turn = i as u32 + 1;
+        TurnRecord { turn, p1: Some(play(mid, P1, turn, Move::Rock)), p2: Some(play(mid, P2, turn, *m)), result: None }
+    }).collect();
+    MatchTranscript { match_id: mid.into(), p1: P1.into(), p2: P2.into(), version: Version::V2, turns }
+}
+
+#[test]
+fn recomputes_results_score_and_winner() {
+    let t = transcript("m1", &[Move::Scissors, Move::Rock, Move::Paper, Move::Scissors, Move::Scissors, Move::Scissors, Move::Scissors]);
+    let a = verify_match(&t);
+    assert!(a.valid, "{:?}", a);
+    let results: Vec<_> = a.turns.iter().map(|t| t.result.clone().unwrap()).collect();
+    assert_eq!(results, ["P1", "DRAW", "P2", "P1", "P1", "P1", "P1"]);
+    assert_eq!((a.score.p1, a.score.p2), (5, 1));
+    assert_eq!(a.winner.as_deref(), Some("P1"));
+}
+
+#[test]
+fn flags_tampered_openings_and_wrong_announcements() {
+    let mut t = transcript("m2", &[Move::Scissors, Move::Paper]);
+    if let Some(Play::Revealed { move_, .. }) = &mut t.turns[0].p2 { *move_ = "P".into(); }
+    t.turns[1].result = Some("P1".into());
+    let a = verify_match(&t);
+    assert!(!a.valid);
+    assert_eq!(a.turns[0].p2, PlayStatus::Invalid);
+    assert_eq!(a.turns[0].result, None);
+    assert_eq!(a.turns[1].result.as_deref(), Some("P2"));
+    assert_eq!(a.turns[1].result_matches, Some(false));
+}
+
+#[test]
+fn substitutions_need_a_valid_proof() {
+    let mut t = transcript("m4", &[Move::Paper]);
+    t.turns[0].p2 = Some(substituted("m4", P2, 1));
+    let a = verify_match(&t);
+    assert!(a.valid, "{:?}", a);
+    assert_eq!(a.turns[0].p2, PlayStatus::Substituted);
+    assert_eq!(a.vrf_keys, [hex::encode(SecretKey::from_seed([5; 32]).public_key().to_bytes())]);
+
+    let unproven = |t: &MatchTranscript| {
+        let a = verify_match(t);
+        !a.valid && a.turns[0].p2 == PlayStatus::Invalid && a.turns[0].result.is_none()
+    };
+    let mut other_turn = transcript("m4", &[Move::Paper]);
+    other_turn.turns[0].p2 = Some(substituted("m4", P2, 2));
+    assert!(unproven(&other_turn));
+    for edit in [0, 1, 2] {
+        let mut bad = t.clone();
+        if let Some(Play::Substituted { substituted, proof }) = &mut bad.turns[0].p2 {
+            match edit {
+                0 => *proof = None,
+                1 => *substituted = ["R", "P", "S"].into_iter().find(|m| m != substituted).unwrap().into(),
+                _ => proof.as_mut().unwrap().drand_round = 7,
+            }
+        }
+        assert!(unproven(&bad), "edit {edit}");
+    }
+}
+
+#[test]
+fn malformed_commits_are_errors() {
+    let mut t = transcript("m5", &[Move::Scissors]);
+    if let Some(Play::Revealed { commit, .. }) = &mut t.turns[0].p2 { *commit = "zz".into(); }
+    let a = verify_match(&t);
+    assert!(!a.valid);
+    assert!(a.errors.iter().any(|e| e.contains("not hex")), "{:?}", a.errors);
+}
+
+#[test]
+fn commits_root_is_rfc6962_over_commits() {
+    let t = transcript("m3", &[Move::Paper]);
+    let leaf = |p: &Option<Play>| match p {
+        Some(Play::Revealed { commit, .. }) => Sha256::new().chain_update([0u8]).chain_update(hex::decode(commit).unwrap()).finalize(),
+        _ => unreachable!(),
+    };
+    let root = Sha256::new().chain_update([1u8]).chain_update(leaf(&t.turns[0].p1)).chain_update(leaf(&t.turns[0].p2)).finalize();
+    assert_eq!(verify_match(&t).commits_root, hex::encode(root));
+}
+
+#[test]
+fn batch_preserves_order() {
+    let ts: Vec<_> = (0..64).map(|i| transcript(&format!("m{i}"), &[Move::Scissors; 5])).collect();
+    let audits = verify_matches(&ts);
+    assert!(audits.iter().zip(&ts).all(|(a, t)| a.match_id == t.match_id && a.valid));
+}
@@ -0,0 +1,21 @@
+//! Commit version negotiation.
+
+use rps_match_engine::{negotiate, Version};
+
+#[test]
+fn picks_newest_common_version() {
+    let both = [Version::V1, Version::V2];
+    assert_eq!(negotiate(&both, &[&both, &both]), Some(Version::V2));
+    assert_eq!(negotiate(&both, &[&both, &[Version::V1]]), Some(Version::V1));
+    assert_eq!(negotiate(&[Version::V1], &[&both]), Some(Version::V1));
+    assert_eq!(negotiate(&[Version::V2], &[&[Version::V1]]), None);
+}
+
+#[test]
+fn versions_parse_and_print() {
+    for v in Version::ALL {
+        assert_eq!(v.to_string().parse::<Version>(), Ok(v));
+    }
+    assert_eq!(serde_json::to_string(&Version::V2).unwrap(), "\"v2\"");
+    assert!("v3".parse::<Version>().is_err());
+}
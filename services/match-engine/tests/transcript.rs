@@ -0,0 +1,129 @@
+//! Signed, hash-chained match transcripts.
+
+use ed25519_dalek::{Signer, SigningKey};
+use rps_crypto::moves::{move_from_output, substitution_alpha};
+use rps_crypto::vrf::{self, SecretKey};
+use rps_match_engine::audit::verify_match;
+use rps_match_engine::transcript::{AiMove, Event, Transcript, TranscriptError};
+use rps_match_engine::{commit, Move, Opening, Version};
+use rps_shared_types::Substitution;
+
+const P1: &str = "did:plc:alice";
+const P2: &str = "did:plc:bob";
+
+fn reveal(mid: &str, did: &str, turn: u32, mv: Move) -> Event {
+    let nonce = format!("{:064x}", turn as u64 * 7 + did.len() as u64);
+    let c = commit(Version::V2, &Opening { match_id: mid, did, turn, mv, nonce: &nonce }).unwrap();
+    Event::Reveal { turn, did: did.into(), move_: mv.to_string(), nonce, commit: c, ai: None }
+}
+
+/// A local (drand round 0) substitution for `did`; key seed 0 draws scissors here.
+fn substitution(mid: &str, did: &str, turn: u32) -> Substitution {
+    let key = SecretKey::from_seed([0; 32]);
+    let proof = key.prove(&substitution_alpha(mid, turn, did, 0, &[0; 32]));
+    let beta = vrf::proof_to_hash(&proof).unwrap();
+    Substitution {
+        did: did.into(),
+        move_: move_from_output(&beta).to_string(),
+        vrf_output: hex::encode(beta),
+        vrf_proof: hex::encode(proof),
+        public_key: hex::encode(key.public_key().to_bytes()),
+        drand_round: 0,
+        drand_randomness: hex::encode([0u8; 32]),
+        source: "local".into(),
+    }
+}
+
+/// P1 plays rock and P2 scissors for five turns; P2 misses turn 3.
+fn finished(key: &SigningKey) -> Transcript {
+    let mid = "t1-r1-did_plc_alice-did_plc_bob";
+    let mut t = Transcript::new(mid);
+    t.append(1, Event::Open { p1: Some(P1.into()), p2: Some(P2.into()), commit_version: Version::V2 });
+    t.append(2, Event::Ticket { did: P1.into(), claims: serde_json::json!({ "sub": P1, "mid": mid }) });
+    for turn in 1..=5 {
+        t.append(10 * turn as i64, Event::TurnStart { turn, deadline_ms_epoch: 30_000 });
+        t.append(10 * turn as i64 + 1, reveal(mid, P1, turn, Move::Rock));
+        if turn == 3 {
+            let s = substitution(mid, P2, turn);
+            t.append(10 * turn as i64 + 2, Event::Substitution { turn, substitution: s });
+        } else {
+            t.append(10 * turn as i64 + 2, reveal(mid, P2, turn, Move::Scissors));
+        }
+        t.append(10 * turn as i64 + 3, Event::TurnResult { turn, result: "P1".into(), p1_move: "R".into(), p2_move: "S".into() });
+    }
+    t.append(99, Event::MatchResult { winner: "P1".into(), commits_root: None });
+    t.signature = Some(hex::encode(key.sign(&t.signing_message()).to_bytes()));
+    t
+}
+
+#[test]
+fn signed_log_verifies_after_a_json_round_trip() {
+    let key = SigningKey::from_bytes(&[9; 32]);
+    let t: Transcript = serde_json::from_str(&serde_json::to_string(&finished(&key)).unwrap()).unwrap();
+    assert_eq!(t.verify(key.verifying_key().as_bytes()), Ok(()));
+    assert_eq!(t.verify(SigningKey::from_bytes(&[8; 32]).verifying_key().as_bytes()), Err(TranscriptError::BadSignature));
+}
+
+#[test]
+fn edits_break_the_chain() {
+    let key = SigningKey::from_bytes(&[9; 32]);
+    let mut edited = finished(&key);
+    if let Event::TurnResult { result, .. } = &mut edited.entries[5].event { *result = "P2".into(); }
+    assert_eq!(edited.verify_chain(), Err(TranscriptError::BadHash(5)));
+
+    let mut dropped = finished(&key);
+    dropped.entries.remove(4);
+    assert_eq!(dropped.verify_chain(), Err(TranscriptError::BadSequence(5)));
+
+    let mut unsigned = finished(&key);
+    unsigned.entries.truncate(3);
+    unsigned.signature = None;
+    assert_eq!(unsigned.verify(key.verifying_key().as_bytes()), Err(TranscriptError::Unsigned));
+}
+
+#[test]
+fn audit_replays_the_logged_moves() {
+    let t = finished(&SigningKey::from_bytes(&[9; 32]));
+    let a = verify_match(&t.to_audit().unwrap());
+    assert!(a.valid, "{:?}", a);
+    assert_eq!(a.turns[2].p2, rps_match_engine::audit::PlayStatus::Substituted);
+    assert_eq!(a.winner.as_deref(), Some("P1"));
+}
+
+#[test]
+fn audit_checks_reveals_against_commit_hashes() {
+    let mid = "t1-r2-did_plc_alice-did_plc_bob";
+    let log = |committed: Move| {
+        let mut t = Transcript::new(mid);
+        t.append(1, Event::Open { p1: Some(P1.into()), p2: Some(P2.into()), commit_version: Version::V2 });
+        let Event::Reveal { commit, .. } = reveal(mid, P1, 1, committed) else { unreachable!() };
+        t.append(2, Event::CommitHashes { did: P1.into(), hashes: vec![commit] });
+        t.append(3, reveal(mid, P1, 1, Move::Rock));
+        t.append(4, reveal(mid, P2, 1, Move::Scissors));
+        t.append(5, Event::TurnResult { turn: 1, result: "P1".into(), p1_move: "R".into(), p2_move: "S".into() });
+        verify_match(&t.to_audit().unwrap())
+    };
+    assert!(log(Move::Rock).valid);
+    let a = log(Move::Paper);
+    assert!(!a.valid);
+    assert_eq!(a.turns[0].p1, rps_match_engine::audit::PlayStatus::Invalid);
+}
+
+#[test]
+fn ai_reveals_log_how_the_move_was_drawn() {
+    let mid = "bye-did_plc_alice";
+    let mut t = Transcript::new(mid);
+    t.append(1, reveal(mid, P1, 1, Move::Rock));
+    let proven = AiMove::Vrf { vrf_output: "ab".into(), vrf_proof: "cd".into(), drand_epoch: 7 };
+    for (turn, ai) in [(1, proven.clone()), (2, AiMove::Unproven)] {
+        let Event::Reveal { turn, did, move_, nonce, commit, .. } = reveal(mid, P2, turn, Move::Paper) else { unreachable!() };
+        t.append(2 * turn as i64, Event::Reveal { turn, did, move_, nonce, commit, ai: Some(ai) });
+    }
+    let json = serde_json::to_value(&t).unwrap();
+    assert!(json["entries"][0].get("ai").is_none());
+    assert_eq!(json["entries"][1]["ai"], serde_json::json!({ "source": "vrf", "vrf_output": "ab", "vrf_proof": "cd", "drand_epoch": 7 }));
+    assert_eq!(json["entries"][2]["ai"], serde_json::json!({ "source": "unproven" }));
+    let back: Transcript = serde_json::from_value(json).unwrap();
+    assert_eq!(back.verify_chain(), Ok(()));
+    assert!(matches!(&back.entries[1].event, Event::Reveal { ai: Some(a), .. } if *a == proven));
+}
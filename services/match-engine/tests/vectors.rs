@@ -0,0 +1,64 @@
+//! Checks the library against the published vectors in `test-vectors/commit.json`.
+
+use rps_match_engine::{commit, commit_v1, verify_reveal, CommitError, Move, Opening, Version};
+use serde::Deserialize;
+
+#[derive(Deserialize)]
+struct Vectors { valid: Vec<Valid>, invalid: Vec<Invalid> }
+
+#[derive(Deserialize)]
+struct Valid { description: String, version: Version, match_id: String, did: String, turn: u32, #[serde(rename = "move")] mv: Move, nonce: String, commit: String }
+
+#[derive(Deserialize)]
+struct Invalid { description: String, version: Version, match_id: String, did: String, turn: u32, #[serde(rename = "move")] mv: String, nonce: String, error: String }
+
+fn vectors() -> Vectors {
+    serde_json::from_str(include_str!("../test-vectors/commit.json")).expect("vectors parse")
+}
+
+#[test]
+fn valid_vectors_commit_and_verify() {
+    let v = vectors();
+    assert!(v.valid.iter().any(|t| t.version == Version::V1) && v.valid.iter().any(|t| t.version == Version::V2));
+    for t in &v.valid {
+        let o = Opening { match_id: &t.match_id, did: &t.did, turn: t.turn, mv: t.mv, nonce: &t.nonce };
+        assert_eq!(commit(t.version, &o).unwrap(), t.commit, "{}", t.description);
+        assert_eq!(verify_reveal(t.version, &t.commit, &o), Ok(()), "{}", t.description);
+        for other in Move::ALL.into_iter().filter(|m| *m != t.mv) {
+            let forged = Opening { mv: other, ..o };
+            assert_eq!(verify_reveal(t.version, &t.commit, &forged), Err(CommitError::Mismatch), "{}", t.description);
+        }
+    }
+}
+
+#[test]
+fn invalid_vectors_are_rejected() {
+    for t in vectors().invalid {
+        let err = match t.mv.parse::<Move>() {
+            Err(e) => e,
+            Ok(mv) => {
+                let o = Opening { match_id: &t.match_id, did: &t.did, turn: t.turn, mv, nonce: &t.nonce };
+                commit(t.version, &o).expect_err(&t.description)
+            }
+        };
+        let name = format!("{:?}", err);
+        assert!(name.starts_with(&t.error), "{}: got {}", t.description, name);
+    }
+}
+
+#[test]
+fn v1_is_ambiguous_and_v2_is_not() {
+    let a = Opening { match_id: "m1", did: "did:x", turn: 1, mv: Move::Rock, nonce: "n" };
+    let b = Opening { match_id: "m1d", did: "id:x", ..a };
+    assert_eq!(commit_v1(&a), commit_v1(&b));
+    let nonce = "11".repeat(32);
+    let (a, b) = (Opening { nonce: &nonce, ..a }, Opening { nonce: &nonce, ..b });
+    assert_ne!(commit(Version::V2, &a).unwrap(), commit(Version::V2, &b).unwrap());
+}
+
+#[test]
+fn verify_rejects_malformed_commits() {
+    let o = Opening { match_id: "m", did: "did:plc:x", turn: 1, mv: Move::Paper, nonce: "n" };
+    assert_eq!(verify_reveal(Version::V1, "xyz", &o), Err(CommitError::MalformedCommit));
+    assert_eq!(verify_reveal(Version::V1, "abcd", &o), Err(CommitError::MalformedCommit));
+}
@@ -0,0 +1,114 @@
+//! In-process AI participant for bye and AI-seat matches.
+//!
+//! The seat joins the match mailbox like a second socket would. On every
+//! TURN_START it asks the fairness service for a move (with the match history
+//! and the ticket's strategy), records a commit and reveal under its own DID,
+//! and, if the human already revealed, pokes the human session so the turn
+//! resolves immediately instead of at the deadline.
+
+use crate::{commit_hash, transcript, InternalEvent, COMMITS, MAILBOXES, PARTICIPANTS, REVEALS, SESSION_EVENTS};
+use rps_match_engine::transcript::{AiMove, Event};
+use reqwest::Client as HttpClient;
+use rps_match_engine::{Move, Version as CommitVersion, NONCE_LEN};
+use rps_shared_types::internal;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AiMoveResp { rps: Move, vrf_output: String, vrf_proof: String, drand_epoch: u64 }
+
+/// A finished turn as fairness expects it: the seat's move and the human's.
+#[derive(Debug, Clone, Copy, Serialize)]
+struct Turn { mine: char, theirs: char }
+
+/// Registers the AI seat as a participant of `mid` and starts its task. Must
+/// be called before the first TURN_START is broadcast so the seat receives it.
+pub fn spawn(mid: &str, ai_did: &str, strategy: Option<String>, commit_version: CommitVersion) {
+    let (relay_tx, relay_rx) = mpsc::unbounded_channel::<String>();
+    MAILBOXES.lock().unwrap().entry(mid.to_string()).or_default().push(relay_tx);
+    PARTICIPANTS.lock().unwrap().entry(mid.to_string()).or_default().insert(ai_did.to_string());
+    tracing::info!(match_id = %mid, ai = %ai_did, "AI seat joined");
+    tokio::spawn(run(mid.to_string(), ai_did.to_string(), strategy, commit_version, relay_rx));
+}
+
+/// Seat loop: ends when the match finishes or its mailbox is cleared.
+async fn run(mid: String, ai_did: String, strategy: Option<String>, commit_version: CommitVersion, mut relay_rx: mpsc::UnboundedReceiver<String>) {
+    let http = HttpClient::new();
+    let fairness = std::env::var("FAIRNESS_HTTP").unwrap_or_else(|_| "http://localhost:8084".to_string());
+    let think_ms: u64 = std::env::var("AI_THINK_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(400);
+    // keyed by turn: TURN_RESULT can arrive more than once per turn
+    let mut history: BTreeMap<u32, Turn> = BTreeMap::new();
+    while let Some(txt) = relay_rx.recv().await {
+        let Ok(j) = serde_json::from_str::<serde_json::Value>(&txt) else { continue };
+        match j["type"].as_str() {
+            Some("TURN_START") => {
+                let turn = j["turn"].as_u64().unwrap_or(0) as u32;
+                if turn == 0 { continue; }
+                let past: Vec<Turn> = history.values().copied().collect();
+                let (mv, source) = choose_move(&http, &fairness, &mid, turn, strategy.as_deref(), &past).await;
+                // short pause so the bot plays at a human-like pace
+                sleep(Duration::from_millis(think_ms)).await;
+                reveal(&mid, &ai_did, turn, mv, source, commit_version);
+            }
+            Some("TURN_RESULT") => {
+                // the seat is always P2
+                let mv = |k: &str| j[k].as_str().and_then(|m| m.chars().next());
+                if let (Some(turn), Some(mine), Some(theirs)) = (j["turn"].as_u64(), mv("p2_move"), mv("p1_move")) {
+                    history.insert(turn as u32, Turn { mine, theirs });
+                }
+            }
+            Some("MATCH_RESULT") | Some("OPPONENT_LEFT") => break,
+            _ => {}
+        }
+    }
+    tracing::info!(match_id = %mid, ai = %ai_did, "AI seat left");
+}
+
+/// Asks the fairness service for this turn's move and its VRF proof. Falls
+/// back to a local uniform draw, logged as unproven, so a fairness outage does
+/// not stall bye matches.
+async fn choose_move(http: &HttpClient, fairness: &str, mid: &str, turn: u32, strategy: Option<&str>, history: &[Turn]) -> (Move, AiMove) {
+    let mut req = http.post(format!("{}/ai_move", fairness))
+        .json(&serde_json::json!({ "match_id": mid, "turn": turn, "strategy": strategy, "history": history }))
+        .timeout(Duration::from_secs(2));
+    if let Some(b) = internal::bearer() { req = req.header(reqwest::header::AUTHORIZATION, b); }
+    let res = req.send().await;
+    match res.and_then(|r| r.error_for_status()) {
+        Ok(r) => match r.json::<AiMoveResp>().await {
+            Ok(m) => (m.rps, AiMove::Vrf { vrf_output: m.vrf_output, vrf_proof: m.vrf_proof, drand_epoch: m.drand_epoch }),
+            Err(err) => {
+                tracing::warn!(%err, "unreadable fairness move, AI seat using local move");
+                local_move()
+            }
+        },
+        Err(err) => {
+            tracing::warn!(%err, "fairness unreachable, AI seat using local move");
+            local_move()
+        }
+    }
+}
+
+fn local_move() -> (Move, AiMove) { (Move::ALL[rand::random::<usize>() % 3], AiMove::Unproven) }
+
+/// Records the AI commit and reveal, then asks the human session to resolve
+/// if its reveal is already in.
+fn reveal(mid: &str, ai_did: &str, turn: u32, mv: Move, source: AiMove, version: CommitVersion) {
+    let nonce = hex::encode(rand::random::<[u8; NONCE_LEN]>());
+    let commit = commit_hash(version, mv, &nonce, turn, mid, ai_did).expect("32-byte nonce is valid for every version");
+    transcript::record(mid, Event::Reveal { turn, did: ai_did.to_string(), move_: mv.to_string(), nonce, commit: commit.clone(), ai: Some(source) });
+    COMMITS.lock().unwrap().entry(mid.to_string()).or_default().entry(turn).or_default().insert(ai_did.to_string(), commit);
+    let human_in = {
+        let mut all = REVEALS.lock().unwrap();
+        let per_player = all.entry(mid.to_string()).or_default().entry(turn).or_default();
+        per_player.insert(ai_did.to_string(), mv.as_char());
+        per_player.len() >= 2
+    };
+    if human_in {
+        let sessions = SESSION_EVENTS.lock().unwrap().get(mid).cloned().unwrap_or_default();
+        for s in sessions { let _ = s.send(InternalEvent::PeerRevealed(turn, mid.to_string())); }
+    }
+}
@@ -0,0 +1,108 @@
+//! HELLO/WELCOME exchange at the start of a match connection.
+//!
+//! Before a socket joins its match, signaling waits up to `HELLO_WAIT_MS` for
+//! the first frame. A `HELLO` in a supported version is answered with
+//! `WELCOME`; an unsupported one gets an `UNSUPPORTED_VERSION` error and the
+//! socket is closed without touching the match. Clients that send something
+//! else first, or nothing, are treated as protocol version 1 (while that is
+//! still supported) unless `PROTOCOL_REQUIRE_HELLO` is set; their first frame
+//! is handed back to be processed as usual.
+//!
+//! Connections negotiated to a binary [`Encoding`] exchange MessagePack or CBOR
+//! frames; [`inbound`] and [`frame`] convert at the socket edge so the match
+//! loop keeps working on JSON text.
+
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use futures::StreamExt;
+use rps_shared_types::wire::Encoding;
+use rps_shared_types::{ClientToServer, ErrorMsg, ServerToClient, MIN_PROTOCOL_VERSION};
+use std::time::Duration;
+
+/// Version assumed for clients that do not send `HELLO`.
+const IMPLICIT_VERSION: u32 = 1;
+
+/// Optional features this server offers in `WELCOME`.
+pub const CAPABILITIES: &[&str] = &["commit_v2", "substitution_proofs", "transcripts"];
+
+/// A connection that passed the handshake.
+pub struct Accepted {
+    pub protocol_version: u32,
+    /// First frame of a client that skipped `HELLO`, still to be processed.
+    pub pending: Option<Message>,
+}
+
+/// A serialized `ERROR` message.
+pub fn error_frame(code: &str, msg: &str) -> String {
+    serde_json::to_string(&ServerToClient::Error(ErrorMsg { code: code.into(), msg: msg.into() })).unwrap_or_default()
+}
+
+/// Wraps a serialized server message in a frame of the connection's encoding.
+pub fn frame(encoding: Encoding, txt: String) -> Message {
+    if !encoding.is_binary() { return Message::Text(txt) }
+    match encoding.transcode_json(&txt) {
+        Ok(bytes) => Message::Binary(bytes),
+        Err(err) => {
+            tracing::error!(%err, encoding = encoding.name(), "cannot encode frame; sending JSON");
+            Message::Text(txt)
+        }
+    }
+}
+
+/// Turns a binary frame of the connection's encoding into the equivalent JSON
+/// text frame. Anything else, including undecodable bytes, passes through.
+pub fn inbound(encoding: Encoding, msg: Message) -> Message {
+    match msg {
+        Message::Binary(bytes) if encoding.is_binary() => match encoding.decode::<serde_json::Value>(&bytes) {
+            Ok(v) => Message::Text(v.to_string()),
+            Err(_) => Message::Binary(bytes),
+        },
+        msg => msg,
+    }
+}
+
+/// Runs the handshake; `None` means the client was rejected or went away.
+pub async fn run(socket: &mut WebSocket, did: &str, encoding: Encoding) -> Option<Accepted> {
+    let wait_ms: u64 = std::env::var("HELLO_WAIT_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(500);
+    let require = std::env::var("PROTOCOL_REQUIRE_HELLO").is_ok_and(|v| v == "1" || v == "true");
+    let first = match tokio::time::timeout(Duration::from_millis(wait_ms), socket.next()).await {
+        Ok(Some(Ok(msg))) => Some(inbound(encoding, msg)),
+        Ok(_) => return None,
+        Err(_) => None,
+    };
+    let hello = match &first {
+        Some(Message::Text(txt)) => match serde_json::from_str::<ClientToServer>(txt) {
+            Ok(ClientToServer::Hello(h)) => Some(h),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(hello) = hello else {
+        if require || IMPLICIT_VERSION < MIN_PROTOCOL_VERSION {
+            tracing::warn!(%did, "rejecting client without HELLO");
+            reject(socket, encoding, "HELLO_REQUIRED", "send HELLO first").await;
+            return None;
+        }
+        tracing::info!(%did, protocol_version = IMPLICIT_VERSION, "client skipped HELLO");
+        return Some(Accepted { protocol_version: IMPLICIT_VERSION, pending: first });
+    };
+    match hello.accept("rps-signaling", CAPABILITIES) {
+        Ok(welcome) => {
+            tracing::info!(%did, protocol_version = welcome.protocol_version, encoding = encoding.name(), client = %hello.client_name, capabilities = ?welcome.capabilities, "client said HELLO");
+            let version = welcome.protocol_version;
+            let txt = serde_json::to_string(&ServerToClient::Welcome(welcome)).ok()?;
+            socket.send(frame(encoding, txt)).await.ok()?;
+            Some(Accepted { protocol_version: version, pending: None })
+        }
+        Err(err) => {
+            tracing::warn!(%did, client = %hello.client_name, protocol_version = hello.protocol_version, "unsupported protocol version");
+            reject(socket, encoding, &err.code, &err.msg).await;
+            None
+        }
+    }
+}
+
+/// Sends the error, then closes with 1002 (protocol error).
+async fn reject(socket: &mut WebSocket, encoding: Encoding, code: &str, msg: &str) {
+    let _ = socket.send(frame(encoding, error_frame(code, msg))).await;
+    let _ = socket.send(Message::Close(Some(CloseFrame { code: 1002, reason: code.to_string().into() }))).await;
+}
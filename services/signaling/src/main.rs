@@ -7,9 +7,13 @@ use axum::{
 use std::net::SocketAddr;
 use tokio::net::TcpListener;
 use futures::StreamExt;
+use rps_shared_types::internal;
+use rps_shared_types::lexicon::MatchDigest;
+use rps_shared_types::wire::Encoding;
 use rps_shared_types::{ClientToServer, ServerToClient, Assign as AssignMsg, Peer, RtcConfig, TurnStart, TurnResult, MatchResult, OpponentLeft};
-use sha2::{Digest, Sha256};
-use hex::ToHex;
+use rps_match_engine::{commit as commit_with, CommitError, Move, Opening, Version as CommitVersion};
+use rps_crypto::merkle;
+use rps_match_engine::transcript::Event as LogEvent;
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use jsonwebtoken::{DecodingKey, Validation, Algorithm};
 use serde::{Deserialize, Serialize};
@@ -22,24 +26,70 @@ use once_cell::sync::Lazy;
 use std::sync::Mutex;
 use std::time::{Instant};
 
+mod ai_seat;
+mod handshake;
+mod precommit;
+mod substitute;
+mod transcript;
+
 /// Health probe for container and local dev. Returns "ok".
 async fn health() -> &'static str { "ok" }
 
 /// Authenticates the WebSocket upgrade using a JWT `ticket` and upgrades to the
 /// match relay socket. Rejects with 401 if the ticket is missing or invalid.
+/// The frame encoding is the `rps.*` subprotocol the client offers, else the
+/// `encoding` query parameter (400 if unknown), else JSON.
 async fn ws_handler(Query(q): Query<WsAuth>, ws: WebSocketUpgrade) -> axum::response::Response {
+    let requested = match q.encoding.as_deref() {
+        None => Encoding::Json,
+        Some(e) => match Encoding::parse(e) {
+            Some(enc) => enc,
+            None => return (axum::http::StatusCode::BAD_REQUEST, "unknown encoding").into_response(),
+        },
+    };
     let Some(t) = q.ticket else { return (axum::http::StatusCode::UNAUTHORIZED, "missing ticket").into_response() };
     let Some(claims) = verify_ticket(&t) else { return (axum::http::StatusCode::UNAUTHORIZED, "invalid ticket").into_response() };
     let did = claims.sub.clone();
     let mid = claims.mid.clone();
-    ws.on_upgrade(move |socket| handle_socket(socket, did, mid)).into_response()
+    let bot = claims.bot.clone();
+    let strategy = claims.strategy.clone();
+    let practice = claims.practice;
+    let commit_version = claims.commit_version;
+    let bracket = claims.tid.clone().zip(claims.round);
+    let ticket_claims = serde_json::to_value(&claims).unwrap_or_default();
+    ws.protocols(Encoding::ALL.map(Encoding::subprotocol)).on_upgrade(move |socket| {
+        let encoding = socket.protocol().and_then(|p| p.to_str().ok()).and_then(Encoding::parse).unwrap_or(requested);
+        handle_socket(socket, encoding, did, mid, bot, strategy, practice, commit_version, bracket, ticket_claims)
+    }).into_response()
 }
 
 #[derive(Debug, serde::Deserialize)]
-struct WsAuth { ticket: Option<String> }
+struct WsAuth { ticket: Option<String>, encoding: Option<String> }
 
 #[derive(Debug, Serialize, Deserialize)]
-struct Claims { sub: String, mid: String, exp: usize, iat: usize }
+struct Claims {
+    sub: String,
+    mid: String,
+    exp: usize,
+    iat: usize,
+    // DID of a server-side AI opponent; the ticket holder plays P1 against it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    bot: Option<String>,
+    // fairness strategy the AI seat plays
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    strategy: Option<String>,
+    // practice match: the result is not reported for rating
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    practice: bool,
+    // commit encoding negotiated by the coordinator
+    #[serde(default)]
+    commit_version: CommitVersion,
+    // tournament and round of a bracket match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    tid: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    round: Option<u32>,
+}
 
 /// Verifies an HS256 ticket using `TICKET_SECRET`. Returns JWT claims if valid.
 fn verify_ticket(ticket: &str) -> Option<Claims> {
@@ -47,12 +97,25 @@ fn verify_ticket(ticket: &str) -> Option<Claims> {
     jsonwebtoken::decode::<Claims>(ticket, &DecodingKey::from_secret(key.as_bytes()), &Validation::new(Algorithm::HS256)).ok().map(|d| d.claims)
 }
 
-enum InternalEvent { Timeout(u32, String) }
+enum InternalEvent {
+    // turn deadline passed: resolve, substituting missing reveals
+    Timeout(u32, String),
+    // another in-process participant revealed: resolve only if both reveals are in
+    PeerRevealed(u32, String),
+}
 
 // per-match relay mailboxes (demo only). In production use Redis
 static MAILBOXES: Lazy<Mutex<HashMap<String, Vec<mpsc::UnboundedSender<String>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 // per-match, per-turn reveals: match_id -> turn -> did -> move_char
-static REVEALS: Lazy<Mutex<HashMap<String, HashMap<u32, HashMap<String, char>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+type TurnReveals = HashMap<u32, HashMap<String, char>>;
+static REVEALS: Lazy<Mutex<HashMap<String, TurnReveals>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// per-match, per-turn commits: match_id -> turn -> did -> commit hex
+type TurnCommits = HashMap<u32, HashMap<String, String>>;
+static COMMITS: Lazy<Mutex<HashMap<String, TurnCommits>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// internal event senders of the sockets in each match, so in-process seats can trigger resolution
+static SESSION_EVENTS: Lazy<Mutex<HashMap<String, Vec<mpsc::UnboundedSender<InternalEvent>>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// matches that already have an AI seat running
+static AI_SEATS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
 // track participant DIDs per match
 static PARTICIPANTS: Lazy<Mutex<HashMap<String, HashSet<String>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 // prevent duplicate turn starts per match
@@ -66,8 +129,13 @@ static MATCH_LAST_SEEN: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(|| Mut
 
 /// Clears all in-memory state for a given match id. Used on disconnect/timeouts.
 fn clear_match_state(mid: &str) {
+    transcript::abandon(mid);
     MAILBOXES.lock().unwrap().remove(mid);
     REVEALS.lock().unwrap().remove(mid);
+    COMMITS.lock().unwrap().remove(mid);
+    precommit::remove(mid);
+    SESSION_EVENTS.lock().unwrap().remove(mid);
+    AI_SEATS.lock().unwrap().remove(mid);
     PARTICIPANTS.lock().unwrap().remove(mid);
     MATCH_STARTED.lock().unwrap().remove(mid.to_string().as_str());
     TURN_STATE.lock().unwrap().remove(mid);
@@ -79,16 +147,114 @@ fn clear_match_state(mid: &str) {
     for k in keys { tr.remove(&k); }
 }
 
-/// Core per-connection loop. Registers the socket with the per-match mailbox,
-/// manages turn state, resolves reveals, broadcasts TURN_START/RESULT, and
-/// ends the match on first to 5 or disconnect.
-async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: String) {
+/// Commit hash under the match's negotiated encoding (see `rps-match-engine`).
+/// Fails only for a v2 nonce that is not 32 bytes of hex.
+fn commit_hash(version: CommitVersion, mv: Move, nonce: &str, turn: u32, match_id: &str, did: &str) -> Result<String, CommitError> {
+    commit_with(version, &Opening { match_id, did, turn, mv, nonce })
+}
+
+/// Merkle root of the match's commits in turn order, P1 before P2; the same
+/// `commits_root` that `/verify_match` recomputes from the transcript.
+fn commits_root(mid: &str, p1: Option<&str>, p2: Option<&str>) -> Option<String> {
+    let (p1, p2) = (p1?, p2?);
+    let commits = COMMITS.lock().unwrap();
+    let turns = commits.get(mid)?;
+    let mut order: Vec<_> = turns.keys().copied().collect();
+    order.sort_unstable();
+    let leaves: Vec<Vec<u8>> = order.iter()
+        .flat_map(|t| [p1, p2].map(|d| turns[t].get(d)))
+        .filter_map(|c| c.and_then(|c| hex::decode(c).ok()))
+        .collect();
+    Some(hex::encode(merkle::root(merkle::Mode::Positional, &leaves)))
+}
+
+/// Reports a finished PvP match to the coordinator so ratings can be updated.
+/// Fire-and-forget: a failed report only means the ratings stay unchanged.
+fn report_match_result(p1: &str, p2: &str, winner: &str) {
+    let coord = std::env::var("COORDINATOR_HTTP").unwrap_or_else(|_| "http://localhost:8082".to_string());
+    let body = serde_json::json!({ "p1": p1, "p2": p2, "winner": winner });
+    tokio::spawn(async move {
+        let mut req = HttpClient::new().post(format!("{}/report_result", coord)).json(&body);
+        if let Some(b) = internal::bearer() { req = req.header(reqwest::header::AUTHORIZATION, b); }
+        match req.send().await {
+            Ok(res) if !res.status().is_success() => tracing::warn!(status = %res.status(), "report_result rejected"),
+            Ok(_) => {}
+            Err(err) => tracing::warn!(%err, "report_result failed"),
+        }
+    });
+}
+
+/// Tells the coordinator the player showed up for their assignment, so it is
+/// not swept as unclaimed. Fire-and-forget like `report_match_result`.
+fn claim_assignment(did: &str, match_id: &str) {
+    let coord = std::env::var("COORDINATOR_HTTP").unwrap_or_else(|_| "http://localhost:8082".to_string());
+    let body = serde_json::json!({ "did": did, "match_id": match_id });
+    tokio::spawn(async move {
+        let mut req = HttpClient::new().post(format!("{}/claim", coord)).json(&body);
+        if let Some(b) = internal::bearer() { req = req.header(reqwest::header::AUTHORIZATION, b); }
+        match req.send().await {
+            Ok(res) if !res.status().is_success() => tracing::warn!(status = %res.status(), "claim rejected"),
+            Ok(_) => {}
+            Err(err) => tracing::warn!(%err, "claim failed"),
+        }
+    });
+}
+
+/// Publishes a `zone.peace.rps.matchDigest` record through atproto-writer, with
+/// `logUri` pointing at the signed transcript. Called for bracket matches, whose
+/// tickets carry the tournament and round.
+fn publish_match_digest(mid: &str, (tid, round): (&str, u32), p1: &str, p2: &str, winner: &str, commits_root: Option<String>) {
+    let (turns, ai_turns) = transcript::turn_summary(mid).unwrap_or_default();
+    let public = std::env::var("SIGNALING_PUBLIC_HTTP").unwrap_or_else(|_| "http://localhost:8081".to_string());
+    let atw = std::env::var("ATPROTO_WRITER_HTTP").unwrap_or_else(|_| "http://localhost:8085".to_string());
+    let body = MatchDigest {
+        tid: tid.to_string(),
+        round: round.into(),
+        mid: mid.to_string(),
+        p1: p1.to_string(),
+        p2: p2.to_string(),
+        winner: if winner == "P1" { p1 } else { p2 }.to_string(),
+        commits_root: commits_root.unwrap_or_default(),
+        turns: turns.into(),
+        ai_turns: Some(ai_turns.into_iter().map(u64::from).collect()),
+        log_uri: Some(format!("{}/matches/{}/transcript", public.trim_end_matches('/'), mid)),
+        posted_at: chrono::Utc::now().to_rfc3339(),
+    };
+    tokio::spawn(async move {
+        let mut req = HttpClient::new().post(format!("{}/match_digest", atw)).json(&body);
+        if let Some(b) = internal::bearer() { req = req.header(reqwest::header::AUTHORIZATION, b); }
+        match req.send().await {
+            Ok(res) if !res.status().is_success() => tracing::warn!(status = %res.status(), "match_digest rejected"),
+            Ok(_) => {}
+            Err(err) => tracing::warn!(%err, "match_digest failed"),
+        }
+    });
+}
+
+/// Next inbound frame: a frame held back by the handshake, then the socket's.
+/// Binary frames of the connection's encoding arrive as JSON text.
+async fn next_frame(socket: &mut WebSocket, pending: &mut Option<Message>, encoding: Encoding) -> Option<Result<Message, axum::Error>> {
+    match pending.take() {
+        Some(msg) => Some(Ok(msg)),
+        None => socket.next().await.map(|res| res.map(|msg| handshake::inbound(encoding, msg))),
+    }
+}
+
+/// Core per-connection loop. After the HELLO handshake, registers the socket
+/// with the per-match mailbox, manages turn state, resolves reveals, broadcasts
+/// TURN_START/RESULT, and ends the match on first to 5 or disconnect. Practice
+/// matches are never reported.
+#[allow(clippy::too_many_arguments)]
+async fn handle_socket(mut socket: WebSocket, encoding: Encoding, did: String, mid_from_ticket: String, bot: Option<String>, strategy: Option<String>, practice: bool, commit_version: CommitVersion, bracket: Option<(String, u32)>, ticket_claims: serde_json::Value) {
+    let Some(hs) = handshake::run(&mut socket, &did, encoding).await else { return };
+    tracing::info!(%did, mid = %mid_from_ticket, protocol_version = hs.protocol_version, "joining match");
+    let mut pending = hs.pending;
     let mut p1_score: u32 = 0;
     let mut p2_score: u32 = 0;
     let mut current_turn: u32 = 1;
-    let _http = HttpClient::new();
+    let http = HttpClient::new();
     let _match_engine = std::env::var("MATCH_ENGINE_HTTP").unwrap_or_else(|_| "http://localhost:8083".to_string());
-    let _fairness_http = std::env::var("FAIRNESS_HTTP").unwrap_or_else(|_| "http://localhost:8084".to_string());
+    let fairness_http = std::env::var("FAIRNESS_HTTP").unwrap_or_else(|_| "http://localhost:8084".to_string());
     let (tx, mut rx) = mpsc::unbounded_channel::<InternalEvent>();
     let (relay_tx, mut relay_rx) = mpsc::unbounded_channel::<String>();
     let mut match_id_for_session: Option<String> = Some(mid_from_ticket.clone());
@@ -107,6 +273,14 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
             }
         }
     }
+    // Matches against an AI seat (a practice bot or the bye AI) are neither
+    // rated nor published as match digests
+    let rated = !practice && bot.is_none();
+    // AI-seat tickets fix the roles: ticket holder is P1, the bot is P2
+    if let Some(b) = &bot {
+        p1_did_from_mid = Some(did.clone());
+        p2_did_from_mid = Some(b.clone());
+    }
     let turn_deadline_ms: u64 = std::env::var("TURN_DEADLINE_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(30_000);
     tracing::info!(%turn_deadline_ms, "turn deadline configured");
     let mut turn_started = false;
@@ -116,6 +290,8 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
         let needs_init = { TURN_STATE.lock().unwrap().get(&mid_from_ticket).is_none() };
         if needs_init { clear_match_state(&mid_from_ticket); }
     }
+    transcript::open(&mid_from_ticket, p1_did_from_mid.clone(), p2_did_from_mid.clone(), commit_version);
+    transcript::record(&mid_from_ticket, LogEvent::Ticket { did: did.clone(), claims: ticket_claims });
 
     // register this connection to the mailbox for this match
     {
@@ -127,6 +303,13 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
         let mut p = PARTICIPANTS.lock().unwrap();
         p.entry(mid_from_ticket.clone()).or_default().insert(did.clone());
     }
+    claim_assignment(&did, &mid_from_ticket);
+    SESSION_EVENTS.lock().unwrap().entry(mid_from_ticket.clone()).or_default().push(tx.clone());
+    // seat the AI opponent once per match, before the first TURN_START goes out
+    if let Some(b) = &bot {
+        let first = AI_SEATS.lock().unwrap().insert(mid_from_ticket.clone());
+        if first { ai_seat::spawn(&mid_from_ticket, b, strategy.clone(), commit_version); }
+    }
 
     // touch last seen for this match
     MATCH_LAST_SEEN.lock().unwrap().insert(mid_from_ticket.clone(), Instant::now());
@@ -152,6 +335,7 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
             }
         };
         current_turn = t;
+        if turn_started { transcript::record(&mid, LogEvent::TurnStart { turn: t, deadline_ms_epoch: d }); }
         let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_millis(0)).as_millis() as i64;
         let ts_msg = TurnStart { match_id: mid.clone(), turn: t, deadline_ms_epoch: d, now_ms_epoch: now_ms };
         if let Ok(txt) = serde_json::to_string(&ServerToClient::TurnStart(ts_msg)) {
@@ -167,7 +351,7 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
     let heartbeat_timeout = Duration::from_secs(6);
     loop {
         tokio::select! {
-            maybe_msg = socket.next() => {
+            maybe_msg = next_frame(&mut socket, &mut pending, encoding) => {
                 let Some(res) = maybe_msg else { break };
                 let Ok(msg) = res else { break };
                 // any inbound frame counts as liveness
@@ -179,7 +363,19 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                 // Try to parse a client message
                 match serde_json::from_str::<ClientToServer>(&txt) {
                     Ok(ClientToServer::Heartbeat(_)) => {
-                        let _ = socket.send(Message::Text("{\"type\":\"ERROR\",\"data\":{\"code\":\"OK\",\"msg\":\"pong\"}}".into())).await;
+                        let _ = socket.send(handshake::frame(encoding, handshake::error_frame("OK", "pong"))).await;
+                    }
+                    Ok(ClientToServer::ReadyForRound(_)) if bot.is_some() => {
+                        // AI-seat ticket: the match is already set up, just describe it
+                        let ai = bot.clone().unwrap_or_default();
+                        let assign = AssignMsg {
+                            match_id: mid_from_ticket.clone(),
+                            role: "P1".to_string(),
+                            peer: Peer { did: ai.clone(), handle: "AI_BYE".into() },
+                            rtc: RtcConfig { turns: vec![] },
+                            commit_version: Some(commit_version.to_string()),
+                        };
+                        if let Ok(txt) = serde_json::to_string(&ServerToClient::Assign(assign)) { let _ = socket.send(handshake::frame(encoding, txt)).await; }
                     }
                     Ok(ClientToServer::ReadyForRound(req)) => {
                         // Send ASSIGN stub
@@ -188,9 +384,10 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                             role: "P1".to_string(),
                             peer: Peer { did: "did:plc:peer".into(), handle: "opponent.example".into() },
                             rtc: RtcConfig { turns: vec![] },
+                            commit_version: Some(commit_version.to_string()),
                         };
                         match_id_for_session = Some(assign.match_id.clone());
-                        if let Ok(txt) = serde_json::to_string(&ServerToClient::Assign(assign)) { let _ = socket.send(Message::Text(txt)).await; }
+                        if let Ok(txt) = serde_json::to_string(&ServerToClient::Assign(assign)) { let _ = socket.send(handshake::frame(encoding, txt)).await; }
                         // NOTE: For full P2P, this handler would relay SDP/ICE between both sides via a mailbox keyed by match_id.
                         // Send TURN_START stub only if we haven't already started based on ticket
                         if !turn_started {
@@ -240,19 +437,29 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                         }
                     }
                     Ok(ClientToServer::Reveal(rev)) => {
-                        // Compute commit like match-engine
-                        let mut hasher = Sha256::new();
-                        hasher.update(rev.move_.as_bytes());
-                        hasher.update(rev.nonce.as_bytes());
                         // trust client turn index for consistency across sockets
                         let turn_idx = if rev.turn == 0 { current_turn } else { rev.turn };
-                        hasher.update(turn_idx.to_be_bytes());
-                        hasher.update(rev.match_id.as_bytes());
-                        hasher.update(did.as_bytes());
-                        let _commit = hasher.finalize().encode_hex::<String>();
-                        // Record user's reveal
+                        let Ok(user_mv) = rev.move_.parse::<Move>() else {
+                            let _ = socket.send(handshake::frame(encoding, handshake::error_frame("BAD_MOVE", "move must be R, P or S"))).await;
+                            continue;
+                        };
                         let mid_now = match_id_for_session.clone().unwrap_or_default();
-                        let user_move = rev.move_.chars().next().unwrap_or('R');
+                        if rev.match_id != mid_now {
+                            let _ = socket.send(handshake::frame(encoding, handshake::error_frame("BAD_REQUEST", "match_id is not this session's match"))).await;
+                            continue;
+                        }
+                        let Ok(commit) = commit_hash(commit_version, user_mv, &rev.nonce, turn_idx, &mid_now, &did) else {
+                            let _ = socket.send(handshake::frame(encoding, handshake::error_frame("BAD_NONCE", "v2 nonce must be 64 hex chars"))).await;
+                            continue;
+                        };
+                        if let Err((code, msg)) = precommit::check(&mid_now, &did, turn_idx, &commit) {
+                            let _ = socket.send(handshake::frame(encoding, handshake::error_frame(code, msg))).await;
+                            continue;
+                        }
+                        // Record user's reveal
+                        transcript::record(&mid_now, LogEvent::Reveal { turn: turn_idx, did: did.clone(), move_: user_mv.to_string(), nonce: rev.nonce.clone(), commit: commit.clone(), ai: None });
+                        COMMITS.lock().unwrap().entry(mid_now.clone()).or_default().entry(turn_idx).or_default().insert(did.clone(), commit);
+                        let user_move = user_mv.as_char();
                         {
                             let mut all = REVEALS.lock().unwrap();
                             let per_turn = all.entry(mid_now.clone()).or_default();
@@ -279,7 +486,7 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                                 let parts = PARTICIPANTS.lock().unwrap();
                                 let mut v: Vec<String> = parts.get(&mid_now).cloned().unwrap_or_default().into_iter().collect();
                                 v.sort();
-                                let p1d = p1_did_from_mid.clone().unwrap_or_else(|| v.get(0).cloned().unwrap_or_default());
+                                let p1d = p1_did_from_mid.clone().unwrap_or_else(|| v.first().cloned().unwrap_or_default());
                                 let p2d = p2_did_from_mid.clone().unwrap_or_else(|| v.get(1).cloned().unwrap_or_default());
                                 let revs = REVEALS.lock().unwrap();
                                 let pt = revs.get(&mid_now).and_then(|pt| pt.get(&turn_idx)).cloned().unwrap_or_default();
@@ -287,14 +494,15 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                                 let m2 = pt.get(&p2d).copied().unwrap_or('R');
                                 (m1, m2)
                             };
-                            let beats = |a: char, b: char| match (a, b) { ('R','S')|('S','P')|('P','R') => true, _ => false };
+                            let beats = |a: char, b: char| matches!((a, b), ('R','S')|('S','P')|('P','R'));
                             let winner = if um == om { "DRAW" } else if beats(um, om) { "P1" } else { "P2" };
                             if winner == "P1" { p1_score += 1; } else if winner == "P2" { p2_score += 1; }
                             // Broadcast one canonical result to all peers
-                            let tr_all = TurnResult { match_id: mid_now.clone(), turn: turn_idx, result: winner.into(), ai: Some(false), ai_for_dids: Some(vec![]), p1_move: Some(um.to_string()), p2_move: Some(om.to_string()) };
+                            let tr_all = TurnResult { match_id: mid_now.clone(), turn: turn_idx, result: winner.into(), ai: Some(false), ai_for_dids: Some(vec![]), p1_move: Some(um.to_string()), p2_move: Some(om.to_string()), substitutions: vec![] };
+                            transcript::record(&mid_now, LogEvent::TurnResult { turn: turn_idx, result: winner.into(), p1_move: um.to_string(), p2_move: om.to_string() });
                             if let Ok(txt_all) = serde_json::to_string(&ServerToClient::TurnResult(tr_all)) {
                                 // Send to this socket and broadcast via mailbox. Client de-dups.
-                                let _ = socket.send(Message::Text(txt_all.clone())).await;
+                                let _ = socket.send(handshake::frame(encoding, txt_all.clone())).await;
                                 let peers = MAILBOXES.lock().unwrap().get(&mid_now).cloned().unwrap_or_default();
                                 for p in peers { let _ = p.send(txt_all.clone()); }
                             }
@@ -307,10 +515,15 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                             // If someone reached 5 wins, end match now
                             if p1_score >= 5 || p2_score >= 5 {
                                 let winner_id = if p1_score >= 5 { "P1" } else { "P2" };
-                                let mr = MatchResult { match_id: mid_now.clone(), winner: winner_id.into() };
+                                let mr = MatchResult { match_id: mid_now.clone(), winner: winner_id.into(), commits_root: commits_root(&mid_now, p1_did_from_mid.as_deref(), p2_did_from_mid.as_deref()) };
+                                transcript::finish(&mid_now, LogEvent::MatchResult { winner: winner_id.into(), commits_root: mr.commits_root.clone() });
+                                if let (Some(p1), Some(p2), true) = (&p1_did_from_mid, &p2_did_from_mid, rated) {
+                                    report_match_result(p1, p2, winner_id);
+                                    if let Some((tid, round)) = &bracket { publish_match_digest(&mid_now, (tid, *round), p1, p2, winner_id, mr.commits_root.clone()); }
+                                }
                                 if let Ok(txt) = serde_json::to_string(&ServerToClient::MatchResult(mr)) {
                                     // Send to this socket first so the player who triggered it logs the final win
-                                    let _ = socket.send(Message::Text(txt.clone())).await;
+                                    let _ = socket.send(handshake::frame(encoding, txt.clone())).await;
                                     let peers = MAILBOXES.lock().unwrap().get(&mid_now).cloned().unwrap_or_default();
                                     for p in peers { let _ = p.send(txt.clone()); }
                                 }
@@ -330,6 +543,7 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                             }
                             let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_millis(0)).as_millis() as i64;
                             let ts = TurnStart { match_id: mid_now.clone(), turn: current_turn, deadline_ms_epoch: next_deadline, now_ms_epoch: now_ms };
+                            transcript::record(&mid_now, LogEvent::TurnStart { turn: current_turn, deadline_ms_epoch: next_deadline });
                             if let Ok(txt_ts) = serde_json::to_string(&ServerToClient::TurnStart(ts)) {
                                 let peers = { MAILBOXES.lock().unwrap().get(&mid_now).cloned().unwrap_or_default() };
                                 for p in peers { let _ = p.send(txt_ts.clone()); }
@@ -344,18 +558,36 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                             });
                         }
                     }
-                    Ok(other) => {
-                        tracing::info!(?other, "unhandled client message");
-                        let _ = socket.send(Message::Text("{\"type\":\"ERROR\",\"data\":{\"code\":\"UNIMPLEMENTED\",\"msg\":\"not yet implemented\"}}".into())).await;
+                    Ok(ClientToServer::CommitHashes(ch)) => {
+                        let mid_now = match_id_for_session.clone().unwrap_or_default();
+                        if ch.match_id != mid_now {
+                            let _ = socket.send(handshake::frame(encoding, handshake::error_frame("BAD_REQUEST", "match_id is not this session's match"))).await;
+                            continue;
+                        }
+                        let revealed = COMMITS.lock().unwrap().get(&mid_now).is_some_and(|turns| turns.values().any(|c| c.contains_key(&did)));
+                        let (code, msg) = match precommit::accept(&mid_now, &did, &ch.hashes, revealed) {
+                            Ok(hashes) => {
+                                transcript::record(&mid_now, LogEvent::CommitHashes { did: did.clone(), hashes });
+                                ("OK", "commit hashes recorded")
+                            }
+                            Err(e) => e,
+                        };
+                        let _ = socket.send(handshake::frame(encoding, handshake::error_frame(code, msg))).await;
+                    }
+                    Ok(ClientToServer::Hello(_)) => {
+                        let _ = socket.send(handshake::frame(encoding, handshake::error_frame("UNEXPECTED_HELLO", "HELLO must be the first message"))).await;
                     }
                     Err(err) => {
                         tracing::warn!(%err, "failed to parse client message");
-                        let _ = socket.send(Message::Text("{\"type\":\"ERROR\",\"data\":{\"code\":\"BAD_REQUEST\",\"msg\":\"invalid message\"}}".into())).await;
+                        let _ = socket.send(handshake::frame(encoding, handshake::error_frame("BAD_REQUEST", "invalid message"))).await;
                     }
                 }
             }
+            Message::Binary(_) if encoding.is_binary() => {
+                let _ = socket.send(handshake::frame(encoding, handshake::error_frame("BAD_REQUEST", &format!("undecodable {} frame", encoding.name())))).await;
+            }
             Message::Binary(_) => {
-                let _ = socket.send(Message::Text("{\"type\":\"ERROR\",\"data\":{\"code\":\"UNSUPPORTED\",\"msg\":\"binary not supported\"}}".into())).await;
+                let _ = socket.send(handshake::frame(encoding, handshake::error_frame("UNSUPPORTED", "binary frames need encoding=msgpack or cbor"))).await;
             }
             Message::Close(_) => {
                 // on close, if this match still exists and now <2 participants, notify remaining and clear state
@@ -400,11 +632,14 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
             }
             // relay messages destined to this client
             Some(relay_txt) = relay_rx.recv() => {
-                let _ = socket.send(Message::Text(relay_txt)).await;
+                let _ = socket.send(handshake::frame(encoding, relay_txt)).await;
             }
-            // Handle internal timeout events
+            // Handle internal timeout / peer-reveal events
             Some(evt) = rx.recv() => {
-                let InternalEvent::Timeout(tn, mid_now) = evt;
+                let (tn, mid_now, timed_out) = match evt {
+                    InternalEvent::Timeout(tn, mid) => (tn, mid, true),
+                    InternalEvent::PeerRevealed(tn, mid) => (tn, mid, false),
+                };
                 if tn != current_turn { continue; }
                 // ensure not already resolved
                 // If we lost a participant before resolution, notify and end match loop
@@ -417,48 +652,57 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                     clear_match_state(&mid_now);
                     break;
                 }
-                let already_resolved = {
-                    let resolved = TURN_RESOLVED.lock().unwrap();
-                    let key = format!("{}#{}", mid_now, current_turn);
-                    resolved.contains(&key)
-                };
-                if already_resolved { continue; }
-
-                // Determine missing reveals canonically and substitute per missing DID
-                let (p1_move_c, p2_move_c, missing_dids) = {
+                // Collect reveals by canonical role; None marks a missing DID
+                let (p1d, p2d, r1, r2) = {
                     let reveals = REVEALS.lock().unwrap();
                     let per_turn = reveals.get(&mid_now).and_then(|pt| pt.get(&current_turn));
                     let parts = PARTICIPANTS.lock().unwrap();
                     let mut ids: Vec<String> = parts.get(&mid_now).cloned().unwrap_or_default().into_iter().collect();
                     ids.sort();
-                    let p1d = p1_did_from_mid.clone().unwrap_or_else(|| ids.get(0).cloned().unwrap_or_default());
+                    let p1d = p1_did_from_mid.clone().unwrap_or_else(|| ids.first().cloned().unwrap_or_default());
                     let p2d = p2_did_from_mid.clone().unwrap_or_else(|| ids.get(1).cloned().unwrap_or_default());
-                    let rand = |seed: u64| -> char { match seed % 3 { 0 => 'R', 1 => 'P', _ => 'S' } };
-                    let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_millis(0)).as_millis() as u64;
-                    let mut miss: Vec<String> = Vec::new();
-                    let m1 = per_turn.and_then(|pp| pp.get(&p1d)).copied().unwrap_or_else(|| { miss.push(p1d.clone()); rand(now_ms ^ 0x1111) });
-                    let m2 = per_turn.and_then(|pp| pp.get(&p2d)).copied().unwrap_or_else(|| { miss.push(p2d.clone()); rand(now_ms ^ 0x2222) });
-                    (m1, m2, miss)
+                    let r1 = per_turn.and_then(|pp| pp.get(&p1d)).copied();
+                    let r2 = per_turn.and_then(|pp| pp.get(&p2d)).copied();
+                    (p1d, p2d, r1, r2)
+                };
+                // a peer reveal only resolves once both sides are in; otherwise wait for the deadline
+                if !timed_out && (r1.is_none() || r2.is_none()) { continue; }
+                // claim the turn before awaiting substitutions so no other path resolves it too
+                let need_resolve = {
+                    let mut resolved = TURN_RESOLVED.lock().unwrap();
+                    let key = format!("{}#{}", mid_now, current_turn);
+                    if resolved.contains(&key) { false } else { resolved.insert(key); true }
                 };
+                if !need_resolve { continue; }
+                // Substitute a VRF-proven uniform move per missing DID
+                let missing_dids: Vec<String> = [(&p1d, r1), (&p2d, r2)].iter().filter(|(_, r)| r.is_none()).map(|(d, _)| (*d).clone()).collect();
+                let substitutions = futures::future::join_all(
+                    missing_dids.iter().map(|d| substitute::substitute(&http, &fairness_http, &mid_now, current_turn, d))
+                ).await;
+                for sub in &substitutions { transcript::record(&mid_now, LogEvent::Substitution { turn: current_turn, substitution: sub.clone() }); }
+                let substituted = |d: &str| substitutions.iter().find(|s| s.did == d).and_then(|s| s.move_.chars().next()).unwrap_or('R');
+                let p1_move_c = r1.unwrap_or_else(|| substituted(&p1d));
+                let p2_move_c = r2.unwrap_or_else(|| substituted(&p2d));
                 // Score canonically
-                let beats = |a: char, b: char| match (a, b) { ('R','S')|('S','P')|('P','R') => true, _ => false };
+                let beats = |a: char, b: char| matches!((a, b), ('R','S')|('S','P')|('P','R'));
                 let winner = if p1_move_c == p2_move_c { "DRAW" } else if beats(p1_move_c, p2_move_c) { "P1" } else { "P2" };
                 if winner == "P1" { p1_score += 1; } else if winner == "P2" { p2_score += 1; }
-                let tr_all = TurnResult { match_id: mid_now.clone(), turn: current_turn, result: winner.into(), ai: Some(!missing_dids.is_empty()), ai_for_dids: Some(missing_dids.clone()), p1_move: Some(p1_move_c.to_string()), p2_move: Some(p2_move_c.to_string()) };
+                transcript::record(&mid_now, LogEvent::TurnResult { turn: current_turn, result: winner.into(), p1_move: p1_move_c.to_string(), p2_move: p2_move_c.to_string() });
+                let tr_all = TurnResult { match_id: mid_now.clone(), turn: current_turn, result: winner.into(), ai: Some(!missing_dids.is_empty()), ai_for_dids: Some(missing_dids.clone()), p1_move: Some(p1_move_c.to_string()), p2_move: Some(p2_move_c.to_string()), substitutions };
                 if let Ok(txt_all) = serde_json::to_string(&ServerToClient::TurnResult(tr_all)) {
-                    let _ = socket.send(Message::Text(txt_all.clone())).await;
+                    let _ = socket.send(handshake::frame(encoding, txt_all.clone())).await;
                     let peers = { MAILBOXES.lock().unwrap().get(&mid_now).cloned().unwrap_or_default() };
                     for p in peers { let _ = p.send(txt_all.clone()); }
                 }
-                {
-                    let mut resolved = TURN_RESOLVED.lock().unwrap();
-                    let key = format!("{}#{}", mid_now, current_turn);
-                    resolved.insert(key);
-                }
                 if p1_score >= 5 || p2_score >= 5 {
                     let winner_id = if p1_score >= 5 { "P1" } else { "P2" };
-                    let mr = MatchResult { match_id: mid_now.clone(), winner: winner_id.into() };
-                    if let Ok(txt) = serde_json::to_string(&ServerToClient::MatchResult(mr)) { let _ = socket.send(Message::Text(txt.clone())).await; let peers = MAILBOXES.lock().unwrap().get(&mid_now).cloned().unwrap_or_default(); for p in peers { let _ = p.send(txt.clone()); } }
+                    let mr = MatchResult { match_id: mid_now.clone(), winner: winner_id.into(), commits_root: commits_root(&mid_now, p1_did_from_mid.as_deref(), p2_did_from_mid.as_deref()) };
+                    transcript::finish(&mid_now, LogEvent::MatchResult { winner: winner_id.into(), commits_root: mr.commits_root.clone() });
+                    if let (Some(p1), Some(p2), true) = (&p1_did_from_mid, &p2_did_from_mid, rated) {
+                        report_match_result(p1, p2, winner_id);
+                        if let Some((tid, round)) = &bracket { publish_match_digest(&mid_now, (tid, *round), p1, p2, winner_id, mr.commits_root.clone()); }
+                    }
+                    if let Ok(txt) = serde_json::to_string(&ServerToClient::MatchResult(mr)) { let _ = socket.send(handshake::frame(encoding, txt.clone())).await; let peers = MAILBOXES.lock().unwrap().get(&mid_now).cloned().unwrap_or_default(); for p in peers { let _ = p.send(txt.clone()); } }
                     break;
                 }
                 current_turn += 1;
@@ -475,6 +719,7 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
                 }
                 let now_ms = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::from_millis(0)).as_millis() as i64;
                 let ts = TurnStart { match_id: mid_now.clone(), turn: current_turn, deadline_ms_epoch: next_deadline, now_ms_epoch: now_ms };
+                transcript::record(&mid_now, LogEvent::TurnStart { turn: current_turn, deadline_ms_epoch: next_deadline });
                 if let Ok(txt_ts) = serde_json::to_string(&ServerToClient::TurnStart(ts)) { let peers = { MAILBOXES.lock().unwrap().get(&mid_now).cloned().unwrap_or_default() }; for p in peers { let _ = p.send(txt_ts.clone()); } }
                 // schedule next timeout
                 let tx2 = tx.clone();
@@ -494,6 +739,10 @@ async fn handle_socket(mut socket: WebSocket, did: String, mid_from_ticket: Stri
 async fn main() {
     let app = Router::new()
         .route("/healthz", get(health))
+        .route("/admin/abandon", post(admin_abandon))
+        .route("/vrf_pubkey", get(substitute::vrf_pubkey))
+        .route("/matches/:mid/transcript", get(transcript::get_transcript))
+        .route("/transcript_pubkey", get(transcript::transcript_pubkey))
         .route("/ws", get(|ws: WebSocketUpgrade, q: Query<WsAuth>| async move { ws_handler(q, ws).await }))
         .layer(CorsLayer::new().allow_origin(Any).allow_methods(Any).allow_headers(Any));
 
@@ -502,6 +751,15 @@ async fn main() {
     tracing_subscriber::fmt().with_env_filter("info").init();
     tracing::info!(%addr, "signaling listening");
 
+    transcript::init();
+    // load the fairness VRF key up front; substitutions retry if fairness is not up yet
+    tokio::spawn(async {
+        let fairness = std::env::var("FAIRNESS_HTTP").unwrap_or_else(|_| "http://localhost:8084".to_string());
+        if let Err(err) = substitute::fairness_key(&HttpClient::new(), &fairness).await {
+            tracing::warn!(%err, "fairness VRF key not loaded yet");
+        }
+    });
+
     // TTL sweeper for matches to remove ghosts
     let ttl_ms: u64 = std::env::var("MATCH_TTL_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(120_000);
     let sweep_ms: u64 = std::env::var("SWEEP_INTERVAL_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(10_000);
@@ -510,7 +768,7 @@ async fn main() {
         loop {
             ticker.tick().await;
             let now = Instant::now();
-            let mut last = MATCH_LAST_SEEN.lock().unwrap();
+            let last = MATCH_LAST_SEEN.lock().unwrap();
             let mids: Vec<String> = last.iter()
                 .filter(|(_, t)| now.duration_since(**t).as_millis() >= ttl_ms as u128)
                 .map(|(k, _)| k.clone())
@@ -534,36 +792,55 @@ struct AdminResetReq { match_id: Option<String> }
 struct AdminResetResp { ok: bool, cleared_matches: usize }
 
 /// Admin endpoint to reset state. If `match_id` provided, clears only that match;
-/// otherwise wipes all in-memory maps.
+/// otherwise wipes all in-memory maps. Not routed.
+#[allow(dead_code)]
 async fn admin_reset(axum::Json(req): axum::Json<AdminResetReq>) -> axum::Json<AdminResetResp> {
     if let Some(mid) = req.match_id {
-        MAILBOXES.lock().unwrap().remove(&mid);
-        REVEALS.lock().unwrap().remove(&mid);
-        PARTICIPANTS.lock().unwrap().remove(&mid);
-        MATCH_STARTED.lock().unwrap().remove(&mid);
-        TURN_STATE.lock().unwrap().remove(&mid);
-        // remove any TURN_RESOLVED keys for this mid
-        {
-            let mut tr = TURN_RESOLVED.lock().unwrap();
-            let keys: Vec<String> = tr.iter().filter(|k| k.starts_with(&format!("{}#", mid))).cloned().collect();
-            for k in keys { tr.remove(&k); }
-        }
+        clear_match_state(&mid);
         axum::Json(AdminResetResp { ok: true, cleared_matches: 1 })
     } else {
         MAILBOXES.lock().unwrap().clear();
         REVEALS.lock().unwrap().clear();
+        COMMITS.lock().unwrap().clear();
+        SESSION_EVENTS.lock().unwrap().clear();
+        AI_SEATS.lock().unwrap().clear();
         PARTICIPANTS.lock().unwrap().clear();
         MATCH_STARTED.lock().unwrap().clear();
         TURN_RESOLVED.lock().unwrap().clear();
         TURN_STATE.lock().unwrap().clear();
+        precommit::clear();
+        transcript::clear();
         axum::Json(AdminResetResp { ok: true, cleared_matches: 0 })
     }
 }
 
+#[derive(Debug, serde::Deserialize)]
+struct AdminAbandonReq { match_id: String }
+
+#[derive(Debug, serde::Serialize)]
+struct AdminAbandonResp { ok: bool, notified: usize }
+
+/// Called by the coordinator when one side never claimed its assignment.
+/// Sends OPPONENT_LEFT to whoever already joined and clears the match.
+/// Internal: requires the `INTERNAL_TOKEN` bearer token.
+async fn admin_abandon(headers: axum::http::HeaderMap, axum::Json(req): axum::Json<AdminAbandonReq>) -> Result<axum::Json<AdminAbandonResp>, axum::http::StatusCode> {
+    if !internal::authorized(headers.get(axum::http::header::AUTHORIZATION).and_then(|v| v.to_str().ok())) {
+        return Err(axum::http::StatusCode::UNAUTHORIZED);
+    }
+    let peers = MAILBOXES.lock().unwrap().get(&req.match_id).cloned().unwrap_or_default();
+    if let Ok(txt) = serde_json::to_string(&ServerToClient::OpponentLeft(OpponentLeft { match_id: req.match_id.clone() })) {
+        for p in &peers { let _ = p.send(txt.clone()); }
+    }
+    clear_match_state(&req.match_id);
+    tracing::info!(match_id = %req.match_id, notified = peers.len(), "match abandoned");
+    Ok(axum::Json(AdminAbandonResp { ok: true, notified: peers.len() }))
+}
+
 #[derive(Debug, serde::Serialize)]
 struct AdminStateResp { matches: usize, participants: usize, pending_turn_states: usize }
 
-/// Admin endpoint to inspect current in-memory counts for matches/participants.
+/// Admin endpoint to inspect current in-memory counts for matches/participants. Not routed.
+#[allow(dead_code)]
 async fn admin_state() -> axum::Json<AdminStateResp> {
     let matches = MAILBOXES.lock().unwrap().len();
     let participants: usize = PARTICIPANTS.lock().unwrap().values().map(|s| s.len()).sum();
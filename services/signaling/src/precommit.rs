@@ -0,0 +1,71 @@
+//! Client pre-commitments (`COMMIT_HASHES`): up to 32 per-turn commits sent
+//! before the player's first reveal. Once accepted, a reveal for turn `t` must
+//! open `hashes[t - 1]`; turns past the list, and players who never send one,
+//! are only committed by the server-computed hash in the transcript.
+
+use std::collections::HashMap;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+
+// match_id -> did -> pre-committed hashes (lowercase hex, turn 1 first)
+type PlayerCommits = HashMap<String, Vec<String>>;
+static PRECOMMITS: Lazy<Mutex<HashMap<String, PlayerCommits>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn normalize(h: &str) -> String { h.trim().trim_start_matches("0x").to_ascii_lowercase() }
+
+/// Stores `did`'s hashes for `mid`. Refused once the player has revealed or
+/// already committed, so the list cannot be chosen after seeing a result.
+pub fn accept(mid: &str, did: &str, hashes: &[String], revealed: bool) -> Result<Vec<String>, (&'static str, &'static str)> {
+    if revealed { return Err(("TOO_LATE", "COMMIT_HASHES must arrive before the first reveal")); }
+    let hashes: Vec<String> = hashes.iter().map(|h| normalize(h)).collect();
+    if hashes.iter().any(|h| h.len() != 64 || hex::decode(h).is_err()) {
+        return Err(("BAD_COMMIT", "each hash must be 32 bytes of hex"));
+    }
+    let mut all = PRECOMMITS.lock().unwrap();
+    let per_match = all.entry(mid.to_string()).or_default();
+    if per_match.contains_key(did) { return Err(("ALREADY_COMMITTED", "COMMIT_HASHES was already accepted for this match")); }
+    per_match.insert(did.to_string(), hashes.clone());
+    Ok(hashes)
+}
+
+/// Checks a reveal's commit against the pre-committed hash for `turn`.
+/// `Ok(true)` when it was pre-committed, `Ok(false)` when nothing covers the turn.
+pub fn check(mid: &str, did: &str, turn: u32, commit: &str) -> Result<bool, (&'static str, &'static str)> {
+    let all = PRECOMMITS.lock().unwrap();
+    let Some(expected) = all.get(mid).and_then(|m| m.get(did)).and_then(|h| h.get((turn as usize).wrapping_sub(1))) else { return Ok(false) };
+    if *expected == normalize(commit) { Ok(true) } else { Err(("COMMIT_MISMATCH", "reveal does not open the pre-committed hash")) }
+}
+
+pub fn remove(mid: &str) { PRECOMMITS.lock().unwrap().remove(mid); }
+
+pub fn clear() { PRECOMMITS.lock().unwrap().clear(); }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hashes(n: u8) -> Vec<String> { (1..=n).map(|i| hex::encode([i; 32])).collect() }
+
+    #[test]
+    fn reveals_must_open_the_committed_hash() {
+        let mid = "pre-1";
+        accept(mid, "did:plc:a", &hashes(2), false).unwrap();
+        assert_eq!(check(mid, "did:plc:a", 1, &format!("0x{}", hex::encode([1u8; 32]).to_uppercase())), Ok(true));
+        assert_eq!(check(mid, "did:plc:a", 2, &hex::encode([1u8; 32])).unwrap_err().0, "COMMIT_MISMATCH");
+        // past the list, and for players without a list, nothing is checked
+        assert_eq!(check(mid, "did:plc:a", 3, "00"), Ok(false));
+        assert_eq!(check(mid, "did:plc:b", 1, "00"), Ok(false));
+        remove(mid);
+        assert_eq!(check(mid, "did:plc:a", 2, "00"), Ok(false));
+    }
+
+    #[test]
+    fn lists_are_accepted_once_and_before_any_reveal() {
+        let mid = "pre-2";
+        assert_eq!(accept(mid, "did:plc:a", &hashes(1), true).unwrap_err().0, "TOO_LATE");
+        assert_eq!(accept(mid, "did:plc:a", &["zz".to_string()], false).unwrap_err().0, "BAD_COMMIT");
+        accept(mid, "did:plc:a", &hashes(1), false).unwrap();
+        assert_eq!(accept(mid, "did:plc:a", &hashes(1), false).unwrap_err().0, "ALREADY_COMMITTED");
+        remove(mid);
+    }
+}
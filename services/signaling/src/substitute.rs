@@ -0,0 +1,242 @@
+//! Verifiable move substitution for players who miss a turn deadline.
+//!
+//! Each missing DID gets a uniform VRF move from the fairness service
+//! (`POST /substitute`). Signaling checks the answer against the fairness key
+//! it loaded at startup and the verified drand beacon for the claimed round. If
+//! fairness is unreachable or the answer does not verify, the move is proven
+//! locally with signaling's own key over the same input bound to drand round 0,
+//! so every substituted move still carries a checkable proof.
+
+use axum::Json;
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use reqwest::Client as HttpClient;
+use rps_crypto::drand::{self as beacon, ChainKey, Network};
+use rps_crypto::moves::{move_from_output, substitution_alpha};
+use rps_crypto::vrf::{self, PublicKey, SecretKey};
+use rps_shared_types::{internal, Substitution};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::OnceCell;
+
+// fallback key from SIGNALING_VRF_SECRET_KEY (hex, 32-byte seed); ephemeral if unset
+static LOCAL_KEY: Lazy<SecretKey> = Lazy::new(|| {
+    let seed = std::env::var("SIGNALING_VRF_SECRET_KEY").ok().and_then(|s| {
+        let bytes = hex::decode(s.trim().trim_start_matches("0x")).ok()?;
+        <[u8; 32]>::try_from(bytes.as_slice()).ok()
+    });
+    SecretKey::from_seed(seed.unwrap_or_else(|| {
+        tracing::warn!("SIGNALING_VRF_SECRET_KEY unset or invalid; fallback substitutions use an ephemeral key");
+        let mut s = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut s);
+        s
+    }))
+});
+
+// fairness VRF key from FAIRNESS_VRF_PUBLIC_KEY (hex), else fetched once from fairness /vrf_pubkey
+static FAIRNESS_KEY: OnceCell<PublicKey> = OnceCell::const_new();
+
+// drand network fairness binds substitutions to (DRAND_CHAIN, as in fairness)
+static DRAND: Lazy<(Network, ChainKey)> = Lazy::new(|| {
+    let network = std::env::var("DRAND_CHAIN").ok().and_then(|n| beacon::network(&n)).unwrap_or(beacon::QUICKNET);
+    let key = hex::decode(network.public_key).ok().and_then(|b| ChainKey::from_bytes(network.scheme, &b).ok());
+    (network, key.expect("pinned drand chain key decodes"))
+});
+
+fn parse_key(h: &str) -> Result<PublicKey, String> {
+    let bytes = hex::decode(h.trim().trim_start_matches("0x")).map_err(|e| e.to_string())?;
+    PublicKey::from_bytes(&bytes).map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VrfPubkeyBody { public_key: String }
+
+/// The fairness key substitutions are checked against. Loaded on first use (and
+/// at startup by `main`), then kept: a key in a `/substitute` answer is never trusted.
+pub async fn fairness_key(http: &HttpClient, fairness: &str) -> Result<PublicKey, String> {
+    FAIRNESS_KEY.get_or_try_init(|| async {
+        if let Ok(h) = std::env::var("FAIRNESS_VRF_PUBLIC_KEY") {
+            return parse_key(&h).map_err(|e| format!("FAIRNESS_VRF_PUBLIC_KEY: {}", e));
+        }
+        let res = http.get(format!("{}/vrf_pubkey", fairness)).timeout(Duration::from_secs(2)).send().await;
+        let body: VrfPubkeyBody = res.and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?.json().await.map_err(|e| e.to_string())?;
+        let pk = parse_key(&body.public_key)?;
+        tracing::info!(public_key = %body.public_key, "fairness VRF key loaded");
+        Ok(pk)
+    }).await.copied()
+}
+
+/// `GET /beacon` payload from fairness (fields we use).
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BeaconBody {
+    chain_hash: String,
+    round: u64,
+    randomness: String,
+    signature: String,
+    #[serde(default, rename = "previous_signature")]
+    previous_signature: Option<String>,
+}
+
+/// Randomness of drand `round` (zeros for round 0). Fetched through fairness
+/// but verified against the pinned chain key, so fairness cannot choose it.
+async fn beacon_randomness(http: &HttpClient, fairness: &str, round: u64) -> Result<[u8; 32], String> {
+    if round == 0 { return Ok([0u8; 32]); }
+    let res = http.get(format!("{}/beacon?round={}", fairness, round)).timeout(Duration::from_secs(2)).send().await;
+    let b: BeaconBody = res.and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?.json().await.map_err(|e| e.to_string())?;
+    let (network, key) = &*DRAND;
+    if b.chain_hash != network.hash || b.round != round {
+        return Err(format!("beacon for chain {} round {}, wanted round {}", b.chain_hash, b.round, round));
+    }
+    let sig = hex::decode(&b.signature).map_err(|e| e.to_string())?;
+    let prev = b.previous_signature.map(hex::decode).transpose().map_err(|e| e.to_string())?;
+    let randomness = hex::decode(&b.randomness).map_err(|e| e.to_string())?;
+    beacon::verify_beacon(network.scheme, key, round, &sig, prev.as_deref(), &randomness).map_err(|e| e.to_string())?;
+    randomness.try_into().map_err(|_| "randomness is not 32 bytes".to_string())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SubstituteResp { rps: char, vrf_output: String, vrf_proof: String, drand_epoch: u64, public_key: String }
+
+/// Substitutes a move for `did`, preferring the fairness service.
+pub async fn substitute(http: &HttpClient, fairness: &str, mid: &str, turn: u32, did: &str) -> Substitution {
+    let timeout_ms: u64 = std::env::var("SUBSTITUTE_TIMEOUT_MS").ok().and_then(|s| s.parse().ok()).unwrap_or(1_500);
+    let mut req = http.post(format!("{}/substitute", fairness))
+        .json(&serde_json::json!({ "match_id": mid, "turn": turn, "did": did }))
+        .timeout(Duration::from_millis(timeout_ms));
+    if let Some(b) = internal::bearer() { req = req.header(reqwest::header::AUTHORIZATION, b); }
+    let res = req.send().await;
+    let remote = async {
+        let r = res.and_then(|r| r.error_for_status()).map_err(|e| e.to_string())?.json::<SubstituteResp>().await.map_err(|e| e.to_string())?;
+        let pk = fairness_key(http, fairness).await?;
+        let randomness = beacon_randomness(http, fairness, r.drand_epoch).await?;
+        check_remote(&pk, mid, turn, did, &randomness, r)
+    };
+    match remote.await {
+        Ok(s) => s,
+        Err(err) => {
+            tracing::warn!(%err, match_id = %mid, turn, %did, "fairness substitution failed, proving locally");
+            local(mid, turn, did)
+        }
+    }
+}
+
+/// Accepts a fairness answer only if its proof verifies under the stored
+/// fairness key for the substitution input rebuilt from (match, turn, DID, the
+/// claimed drand round and that round's `randomness`), and yields the output
+/// and move it claims.
+fn check_remote(pk: &PublicKey, mid: &str, turn: u32, did: &str, randomness: &[u8; 32], r: SubstituteResp) -> Result<Substitution, String> {
+    let pk_hex = hex::encode(pk.to_bytes());
+    if r.public_key != pk_hex { return Err(format!("fairness answered with key {}, expected {}", r.public_key, pk_hex)); }
+    let proof = hex::decode(&r.vrf_proof).map_err(|e| e.to_string())?;
+    let beta = pk.verify(&substitution_alpha(mid, turn, did, r.drand_epoch, randomness), &proof).map_err(|e| e.to_string())?;
+    if hex::encode(beta) != r.vrf_output || move_from_output(&beta) != r.rps {
+        return Err("fairness response does not match its proof".into());
+    }
+    Ok(Substitution {
+        did: did.to_string(),
+        move_: r.rps.to_string(),
+        vrf_output: r.vrf_output,
+        vrf_proof: r.vrf_proof,
+        public_key: pk_hex,
+        drand_round: r.drand_epoch,
+        drand_randomness: hex::encode(randomness),
+        source: "fairness".into(),
+    })
+}
+
+/// Defined fallback: the same substitution input at drand round 0, proven with
+/// signaling's key (published at `/vrf_pubkey`).
+pub fn local(mid: &str, turn: u32, did: &str) -> Substitution {
+    let proof = LOCAL_KEY.prove(&substitution_alpha(mid, turn, did, 0, &[0u8; 32]));
+    let beta = vrf::proof_to_hash(&proof).expect("freshly generated proof decodes");
+    Substitution {
+        did: did.to_string(),
+        move_: move_from_output(&beta).to_string(),
+        vrf_output: hex::encode(beta),
+        vrf_proof: hex::encode(proof),
+        public_key: hex::encode(LOCAL_KEY.public_key().to_bytes()),
+        drand_round: 0,
+        drand_randomness: hex::encode([0u8; 32]),
+        source: "local".into(),
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VrfPubkeyResp { suite: &'static str, public_key: String }
+
+/// Publishes the fallback substitution key.
+pub async fn vrf_pubkey() -> Json<VrfPubkeyResp> {
+    Json(VrfPubkeyResp { suite: vrf::SUITE_NAME, public_key: hex::encode(LOCAL_KEY.public_key().to_bytes()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MID: &str = "cup-r1-did_plc_alice-did_plc_bob";
+    const DID: &str = "did:plc:bob";
+
+    /// What an honest fairness service answers for `round` with `randomness`.
+    fn answer(key: &SecretKey, round: u64, randomness: &[u8; 32]) -> SubstituteResp {
+        let proof = key.prove(&substitution_alpha(MID, 3, DID, round, randomness));
+        let beta = vrf::proof_to_hash(&proof).unwrap();
+        SubstituteResp {
+            rps: move_from_output(&beta),
+            vrf_output: hex::encode(beta),
+            vrf_proof: hex::encode(proof),
+            drand_epoch: round,
+            public_key: hex::encode(key.public_key().to_bytes()),
+        }
+    }
+
+    fn fairness() -> SecretKey { SecretKey::from_seed([1u8; 32]) }
+
+    #[test]
+    fn accepts_answers_from_the_stored_key() {
+        let pk = fairness().public_key();
+        for (round, randomness) in [(0, [0u8; 32]), (1234, [9u8; 32])] {
+            let s = check_remote(&pk, MID, 3, DID, &randomness, answer(&fairness(), round, &randomness)).unwrap();
+            assert_eq!((s.drand_round, s.source.as_str()), (round, "fairness"));
+        }
+    }
+
+    #[test]
+    fn rejects_another_key() {
+        let pk = fairness().public_key();
+        let impostor = SecretKey::from_seed([2u8; 32]);
+        assert!(check_remote(&pk, MID, 3, DID, &[0u8; 32], answer(&impostor, 0, &[0u8; 32])).is_err());
+        // the impostor's proof does not verify even when it names the stored key
+        let mut r = answer(&impostor, 0, &[0u8; 32]);
+        r.public_key = hex::encode(pk.to_bytes());
+        assert!(check_remote(&pk, MID, 3, DID, &[0u8; 32], r).is_err());
+    }
+
+    #[test]
+    fn rejects_a_proof_for_another_round_or_beacon() {
+        let pk = fairness().public_key();
+        let mut r = answer(&fairness(), 1234, &[9u8; 32]);
+        r.drand_epoch = 1235;
+        assert!(check_remote(&pk, MID, 3, DID, &[8u8; 32], r).is_err());
+        // right round number, but proven over randomness that is not the beacon's
+        assert!(check_remote(&pk, MID, 3, DID, &[8u8; 32], answer(&fairness(), 1234, &[9u8; 32])).is_err());
+        assert!(check_remote(&pk, MID, 4, DID, &[9u8; 32], answer(&fairness(), 1234, &[9u8; 32])).is_err());
+    }
+
+    #[test]
+    fn rejects_a_tampered_output_or_move() {
+        let pk = fairness().public_key();
+        let mut r = answer(&fairness(), 0, &[0u8; 32]);
+        r.vrf_output = hex::encode([0u8; vrf::OUTPUT_LEN]);
+        assert!(check_remote(&pk, MID, 3, DID, &[0u8; 32], r).is_err());
+        let mut r = answer(&fairness(), 0, &[0u8; 32]);
+        r.rps = match r.rps { 'R' => 'P', 'P' => 'S', _ => 'R' };
+        assert!(check_remote(&pk, MID, 3, DID, &[0u8; 32], r).is_err());
+        let mut r = answer(&fairness(), 0, &[0u8; 32]);
+        r.vrf_proof.replace_range(70..72, "00");
+        assert!(check_remote(&pk, MID, 3, DID, &[0u8; 32], r).is_err());
+    }
+}
@@ -0,0 +1,180 @@
+//! Per-match audit logs (`rps_match_engine::transcript`): appended in memory as
+//! the match runs, signed with signaling's Ed25519 key when it ends, then kept
+//! in SQLite (`TRANSCRIPT_DB_PATH`) for good, since match digests publish their
+//! URL. Served at `GET /matches/{mid}/transcript`.
+
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::Json;
+use ed25519_dalek::{Signer, SigningKey};
+use once_cell::sync::Lazy;
+use rand::RngCore;
+use rps_match_engine::transcript::{Event, Transcript};
+use rps_match_engine::Version as CommitVersion;
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// signing key from SIGNALING_SIGNING_KEY (hex, 32-byte Ed25519 seed); ephemeral if unset
+static SIGNING_KEY: Lazy<SigningKey> = Lazy::new(|| {
+    let seed = std::env::var("SIGNALING_SIGNING_KEY").ok().and_then(|s| {
+        let bytes = hex::decode(s.trim().trim_start_matches("0x")).ok()?;
+        <[u8; 32]>::try_from(bytes.as_slice()).ok()
+    });
+    SigningKey::from_bytes(&seed.unwrap_or_else(|| {
+        tracing::warn!("SIGNALING_SIGNING_KEY unset or invalid; transcripts are signed with an ephemeral key");
+        let mut s = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut s);
+        s
+    }))
+});
+// match_id -> transcript of a running match (or a finished one the store could not take)
+static TRANSCRIPTS: Lazy<Mutex<HashMap<String, Transcript>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+// finished transcripts at TRANSCRIPT_DB_PATH
+static STORE: Lazy<Store> = Lazy::new(|| {
+    let path = std::env::var("TRANSCRIPT_DB_PATH").unwrap_or_else(|_| "signaling-transcripts.sqlite3".to_string());
+    Store::open(&path).unwrap_or_else(|e| panic!("cannot open transcript store {path}: {e}"))
+});
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS transcripts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    match_id TEXT NOT NULL,
+    transcript TEXT NOT NULL,
+    finished_ms INTEGER NOT NULL
+);
+CREATE INDEX IF NOT EXISTS transcripts_match ON transcripts (match_id, id);
+";
+
+/// Finished, signed transcripts. Queue match ids repeat when the same two
+/// players meet again, so every log is kept and the latest one is served.
+pub struct Store { db: Mutex<Connection> }
+
+impl Store {
+    pub fn open(path: &str) -> rusqlite::Result<Self> {
+        let db = Connection::open(path)?;
+        db.execute_batch("PRAGMA journal_mode = WAL;")?;
+        db.execute_batch(SCHEMA)?;
+        Ok(Store { db: Mutex::new(db) })
+    }
+
+    pub fn save(&self, t: &Transcript, now: i64) -> rusqlite::Result<()> {
+        let json = serde_json::to_string(t).expect("transcripts serialize");
+        self.db.lock().unwrap().execute(
+            "INSERT INTO transcripts (match_id, transcript, finished_ms) VALUES (?1, ?2, ?3)",
+            params![t.match_id, json, now],
+        ).map(|_| ())
+    }
+
+    /// The latest log stored for `mid`.
+    pub fn load(&self, mid: &str) -> rusqlite::Result<Option<Transcript>> {
+        let json: Option<String> = self.db.lock().unwrap()
+            .query_row("SELECT transcript FROM transcripts WHERE match_id = ?1 ORDER BY id DESC LIMIT 1", [mid], |r| r.get(0))
+            .optional()?;
+        Ok(json.and_then(|j| serde_json::from_str(&j).ok()))
+    }
+}
+
+/// Opens the store at startup so a bad `TRANSCRIPT_DB_PATH` fails fast.
+pub fn init() { Lazy::force(&STORE); }
+
+fn now_ms() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+/// Starts the match's log unless one is already running.
+pub fn open(mid: &str, p1: Option<String>, p2: Option<String>, commit_version: CommitVersion) {
+    let mut all = TRANSCRIPTS.lock().unwrap();
+    if all.get(mid).is_some_and(|t| !t.is_finished()) { return; }
+    let mut t = Transcript::new(mid);
+    t.append(now_ms(), Event::Open { p1, p2, commit_version });
+    all.insert(mid.to_string(), t);
+}
+
+/// Appends to a running log; ignored once the log is finished.
+pub fn record(mid: &str, event: Event) {
+    if let Some(t) = TRANSCRIPTS.lock().unwrap().get_mut(mid).filter(|t| !t.is_finished()) {
+        t.append(now_ms(), event);
+    }
+}
+
+/// Appends the final event, signs the log and moves it to the store. If the
+/// store fails the log stays in memory, still served.
+pub fn finish(mid: &str, event: Event) {
+    let mut all = TRANSCRIPTS.lock().unwrap();
+    let Some(t) = all.get_mut(mid).filter(|t| !t.is_finished()) else { return };
+    t.append(now_ms(), event);
+    t.signature = Some(hex::encode(SIGNING_KEY.sign(&t.signing_message()).to_bytes()));
+    t.public_key = Some(hex::encode(SIGNING_KEY.verifying_key().to_bytes()));
+    match STORE.save(t, now_ms()) {
+        Ok(()) => { all.remove(mid); }
+        Err(err) => tracing::error!(%err, match_id = %mid, "transcript not stored; keeping it in memory"),
+    }
+}
+
+/// A running log from memory, else a finished one from the store.
+fn lookup(mid: &str) -> Option<Transcript> {
+    if let Some(t) = TRANSCRIPTS.lock().unwrap().get(mid) { return Some(t.clone()); }
+    STORE.load(mid).unwrap_or_else(|err| {
+        tracing::error!(%err, match_id = %mid, "transcript store read failed");
+        None
+    })
+}
+
+/// Closes a log that ended without a result.
+pub fn abandon(mid: &str) { finish(mid, Event::Abandoned); }
+
+/// Number of resolved turns and the turns where a move was substituted.
+pub fn turn_summary(mid: &str) -> Option<(u32, Vec<u32>)> {
+    let t = lookup(mid)?;
+    let turns = t.entries.iter().filter(|e| matches!(e.event, Event::TurnResult { .. })).count() as u32;
+    let mut ai_turns: Vec<u32> = t.entries.iter().filter_map(|e| match e.event {
+        Event::Substitution { turn, .. } => Some(turn),
+        _ => None,
+    }).collect();
+    ai_turns.dedup();
+    Some((turns, ai_turns))
+}
+
+/// Drops the in-memory logs; stored ones are kept.
+pub fn clear() { TRANSCRIPTS.lock().unwrap().clear(); }
+
+/// Returns the match's log; unsigned while the match is still running.
+pub async fn get_transcript(Path(mid): Path<String>) -> Result<Json<Transcript>, (StatusCode, String)> {
+    lookup(&mid).map(Json).ok_or((StatusCode::NOT_FOUND, "unknown match".into()))
+}
+
+#[derive(Debug, Serialize)]
+pub struct TranscriptPubkeyResp { alg: &'static str, public_key: String }
+
+/// Publishes the transcript signing key.
+pub async fn transcript_pubkey() -> Json<TranscriptPubkeyResp> {
+    Json(TranscriptPubkeyResp { alg: "ed25519", public_key: hex::encode(SIGNING_KEY.verifying_key().to_bytes()) })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_logs_survive_reopening() {
+        let path = std::env::temp_dir().join(format!("rps-transcripts-{}.sqlite3", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut t = Transcript::new("t-r1-a-b");
+        t.append(1, Event::Abandoned);
+        t.signature = Some("00".into());
+        Store::open(path).unwrap().save(&t, 2).unwrap();
+
+        let store = Store::open(path).unwrap();
+        let back = store.load("t-r1-a-b").unwrap().unwrap();
+        assert_eq!((back.entries.len(), back.signature.as_deref()), (1, Some("00")));
+        // a repeated match id serves its latest log
+        store.save(&Transcript::new("t-r1-a-b"), 3).unwrap();
+        assert!(store.load("t-r1-a-b").unwrap().unwrap().entries.is_empty());
+        assert!(store.load("other").unwrap().is_none());
+        drop(store);
+        for suffix in ["", "-wal", "-shm"] { let _ = std::fs::remove_file(format!("{path}{suffix}")); }
+    }
+}
@@ -0,0 +1,178 @@
+//! drand beacon verification (BLS12-381).
+//!
+//! Supports the two public League of Entropy schemes:
+//! - `pedersen-bls-chained` (the "default" network): public key on G1,
+//!   signatures on G2 over `sha256(previous_signature || round)`.
+//! - `bls-unchained-g1-rfc9380` (quicknet): public key on G2, signatures on G1
+//!   over `sha256(round)`.
+//!
+//! In both, the beacon randomness is `sha256(signature)`.
+
+use bls12_381::hash_to_curve::{ExpandMsgXmd, HashToCurve};
+use bls12_381::{multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, G2Projective, Gt};
+use sha2::{Digest, Sha256};
+
+const DST_G1: &[u8] = b"BLS_SIG_BLS12381G1_XMD:SHA-256_SSWU_RO_NUL_";
+const DST_G2: &[u8] = b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_NUL_";
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum BeaconError {
+    #[error("unknown scheme {0}")]
+    UnknownScheme(String),
+    #[error("invalid public key")]
+    InvalidPublicKey,
+    #[error("malformed signature")]
+    MalformedSignature,
+    #[error("chained beacon is missing previous_signature")]
+    MissingPrevious,
+    #[error("signature does not verify")]
+    InvalidSignature,
+    #[error("randomness is not sha256(signature)")]
+    RandomnessMismatch,
+}
+
+/// Signature scheme of a drand chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    PedersenBlsChained,
+    BlsUnchainedG1Rfc9380,
+}
+
+impl Scheme {
+    /// Parses the `schemeID` reported by a drand `/info` endpoint.
+    pub fn from_id(id: &str) -> Result<Self, BeaconError> {
+        match id {
+            "pedersen-bls-chained" => Ok(Scheme::PedersenBlsChained),
+            "bls-unchained-g1-rfc9380" => Ok(Scheme::BlsUnchainedG1Rfc9380),
+            other => Err(BeaconError::UnknownScheme(other.to_string())),
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            Scheme::PedersenBlsChained => "pedersen-bls-chained",
+            Scheme::BlsUnchainedG1Rfc9380 => "bls-unchained-g1-rfc9380",
+        }
+    }
+
+    /// Whether each round's message commits to the previous signature.
+    pub fn is_chained(&self) -> bool { matches!(self, Scheme::PedersenBlsChained) }
+}
+
+/// A League of Entropy network pinned by the services.
+#[derive(Debug, Clone, Copy)]
+pub struct Network {
+    pub hash: &'static str,
+    pub scheme: Scheme,
+    /// Hex group public key.
+    pub public_key: &'static str,
+    pub period: u64,
+    pub genesis_time: u64,
+}
+
+/// quicknet: unchained, 3s rounds.
+pub const QUICKNET: Network = Network {
+    hash: "52db9ba70e0cc0f6eaf7803dd07447a1f5477735fd3f661792ba94600c84e971",
+    scheme: Scheme::BlsUnchainedG1Rfc9380,
+    public_key: "83cf0f2896adee7eb8b5f01fcad3912212c437e0073e911fb90022d3e760183c8c4b450b6a0a6c3ac6a5776a2d1064510d1fec758c921cc22b0e17e63aaf4bcb5ed66304de9cf809bd274ca73bab4af5a6e9c76a4bc09e76eae8991ef5ece45a",
+    period: 3,
+    genesis_time: 1692803367,
+};
+
+/// The default network: chained, 30s rounds.
+pub const MAINNET: Network = Network {
+    hash: "8990e7a9aaed2ffed73dbd7092123d6f289930540d7651336225dc172e51b2ce",
+    scheme: Scheme::PedersenBlsChained,
+    public_key: "868f005eb8e6e4ca0a47c8a77ceaa5309a47978a7c71bc5cce96366b5d7a569937c529eeda66c7293784a9402801af31",
+    period: 30,
+    genesis_time: 1595431050,
+};
+
+/// Network for a `DRAND_CHAIN` value: `quicknet`, or `default` / `mainnet`.
+pub fn network(name: &str) -> Option<Network> {
+    match name {
+        "quicknet" => Some(QUICKNET),
+        "default" | "mainnet" => Some(MAINNET),
+        _ => None,
+    }
+}
+
+/// A chain's group public key, decoded for its scheme.
+#[derive(Debug, Clone, Copy)]
+pub enum ChainKey {
+    G1(G1Affine),
+    G2(G2Affine),
+}
+
+impl ChainKey {
+    /// Decodes a compressed public key: 48 bytes (G1) for chained, 96 bytes (G2) for quicknet.
+    pub fn from_bytes(scheme: Scheme, bytes: &[u8]) -> Result<Self, BeaconError> {
+        let key = match scheme {
+            Scheme::PedersenBlsChained => ChainKey::G1(g1_from_bytes(bytes).ok_or(BeaconError::InvalidPublicKey)?),
+            Scheme::BlsUnchainedG1Rfc9380 => ChainKey::G2(g2_from_bytes(bytes).ok_or(BeaconError::InvalidPublicKey)?),
+        };
+        let identity = match key { ChainKey::G1(p) => bool::from(p.is_identity()), ChainKey::G2(p) => bool::from(p.is_identity()) };
+        if identity { return Err(BeaconError::InvalidPublicKey); }
+        Ok(key)
+    }
+}
+
+/// The message signed for `round`: `sha256(previous_signature || u64be(round))`
+/// for chained schemes, `sha256(u64be(round))` otherwise.
+pub fn round_message(scheme: Scheme, round: u64, previous_signature: Option<&[u8]>) -> Result<[u8; 32], BeaconError> {
+    let mut h = Sha256::new();
+    if scheme.is_chained() {
+        h.update(previous_signature.ok_or(BeaconError::MissingPrevious)?);
+    }
+    h.update(round.to_be_bytes());
+    Ok(h.finalize().into())
+}
+
+/// Beacon randomness derived from a signature.
+pub fn randomness(signature: &[u8]) -> [u8; 32] { Sha256::digest(signature).into() }
+
+/// Verifies one beacon's BLS signature and its randomness.
+pub fn verify_beacon(
+    scheme: Scheme,
+    key: &ChainKey,
+    round: u64,
+    signature: &[u8],
+    previous_signature: Option<&[u8]>,
+    claimed_randomness: &[u8],
+) -> Result<(), BeaconError> {
+    let msg = round_message(scheme, round, previous_signature)?;
+    let ok = match (scheme, key) {
+        (Scheme::PedersenBlsChained, ChainKey::G1(pk)) => {
+            let sig = g2_from_bytes(signature).ok_or(BeaconError::MalformedSignature)?;
+            let hm = G2Affine::from(<G2Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(msg, DST_G2));
+            // e(pk, H(m)) == e(g1, sig)  <=>  e(pk, H(m)) * e(-g1, sig) == 1
+            pairing_product_is_one(&[(pk, &hm), (&-G1Affine::generator(), &sig)])
+        }
+        (Scheme::BlsUnchainedG1Rfc9380, ChainKey::G2(pk)) => {
+            let sig = g1_from_bytes(signature).ok_or(BeaconError::MalformedSignature)?;
+            let hm = G1Affine::from(<G1Projective as HashToCurve<ExpandMsgXmd<sha2_09::Sha256>>>::hash_to_curve(msg, DST_G1));
+            // e(H(m), pk) == e(sig, g2)  <=>  e(H(m), pk) * e(-sig, g2) == 1
+            pairing_product_is_one(&[(&hm, pk), (&-sig, &G2Affine::generator())])
+        }
+        _ => return Err(BeaconError::InvalidPublicKey),
+    };
+    if !ok { return Err(BeaconError::InvalidSignature); }
+    if claimed_randomness != randomness(signature) { return Err(BeaconError::RandomnessMismatch); }
+    Ok(())
+}
+
+fn pairing_product_is_one(terms: &[(&G1Affine, &G2Affine)]) -> bool {
+    let prepared: Vec<(&G1Affine, G2Prepared)> = terms.iter().map(|(p, q)| (*p, G2Prepared::from(**q))).collect();
+    let refs: Vec<(&G1Affine, &G2Prepared)> = prepared.iter().map(|(p, q)| (*p, q)).collect();
+    multi_miller_loop(&refs).final_exponentiation() == Gt::identity()
+}
+
+fn g1_from_bytes(bytes: &[u8]) -> Option<G1Affine> {
+    let arr: &[u8; 48] = bytes.try_into().ok()?;
+    Option::from(G1Affine::from_compressed(arr))
+}
+
+fn g2_from_bytes(bytes: &[u8]) -> Option<G2Affine> {
+    let arr: &[u8; 96] = bytes.try_into().ok()?;
+    Option::from(G2Affine::from_compressed(arr))
+}
@@ -0,0 +1,10 @@
+//! Verification primitives shared by the services and the browser (wasm) build.
+//!
+//! Kept free of async/server dependencies so it compiles to `wasm32-unknown-unknown`.
+
+pub mod drand;
+pub mod keccak;
+pub mod merkle;
+pub mod moves;
+pub mod poseidon2;
+pub mod vrf;
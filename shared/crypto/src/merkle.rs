@@ -0,0 +1,186 @@
+//! SHA-256 Merkle trees with inclusion proofs.
+//!
+//! Leaves hash as `sha256(0x00 || data)` and nodes as `sha256(0x01 || left || right)`.
+//! Levels are built bottom-up, pairing neighbours and carrying an odd last node
+//! up unchanged, which gives the same root as RFC 6962's split at the largest
+//! power of two. The empty tree's root is `sha256("")`.
+//!
+//! Two modes:
+//! - [`Mode::Positional`]: children hashed in order; a proof binds the leaf's index.
+//! - [`Mode::Sorted`]: each pair is sorted before hashing, so proofs verify
+//!   without knowing the index (set membership).
+
+use sha2::{Digest, Sha256};
+
+pub type Hash = [u8; 32];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Positional,
+    Sorted,
+}
+
+/// Hash of a leaf's data.
+pub fn leaf_hash(data: &[u8]) -> Hash {
+    Sha256::new().chain_update([0u8]).chain_update(data).finalize().into()
+}
+
+/// Hash of an inner node.
+pub fn node_hash(mode: Mode, left: &Hash, right: &Hash) -> Hash {
+    let (l, r) = match mode {
+        Mode::Sorted if right < left => (right, left),
+        _ => (left, right),
+    };
+    Sha256::new().chain_update([1u8]).chain_update(l).chain_update(r).finalize().into()
+}
+
+/// A tree kept level by level (leaf hashes first) so proofs can be cut from it.
+#[derive(Debug, Clone)]
+pub struct MerkleTree {
+    mode: Mode,
+    levels: Vec<Vec<Hash>>,
+}
+
+/// Inclusion proof: sibling hashes from the leaf up. Levels where the node was
+/// carried up without a sibling contribute nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    pub index: usize,
+    pub leaf_count: usize,
+    pub siblings: Vec<Hash>,
+}
+
+impl MerkleTree {
+    pub fn new<T: AsRef<[u8]>>(mode: Mode, leaves: &[T]) -> Self {
+        let mut levels = vec![leaves.iter().map(|l| leaf_hash(l.as_ref())).collect::<Vec<_>>()];
+        while levels.last().is_some_and(|l| l.len() > 1) {
+            let next = levels.last().unwrap().chunks(2).map(|p| match p {
+                [l, r] => node_hash(mode, l, r),
+                [single] => *single,
+                _ => unreachable!(),
+            }).collect();
+            levels.push(next);
+        }
+        MerkleTree { mode, levels }
+    }
+
+    pub fn mode(&self) -> Mode { self.mode }
+
+    pub fn len(&self) -> usize { self.levels[0].len() }
+
+    pub fn is_empty(&self) -> bool { self.levels[0].is_empty() }
+
+    pub fn root(&self) -> Hash {
+        match self.levels.last().and_then(|l| l.first()) {
+            Some(h) => *h,
+            None => Sha256::digest([]).into(),
+        }
+    }
+
+    /// Proof for the leaf at `index`, or `None` if out of range.
+    pub fn proof(&self, index: usize) -> Option<Proof> {
+        if index >= self.len() { return None; }
+        let mut siblings = Vec::new();
+        let mut i = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(s) = level.get(i ^ 1) { siblings.push(*s); }
+            i /= 2;
+        }
+        Some(Proof { index, leaf_count: self.len(), siblings })
+    }
+}
+
+/// Root of `leaves` without keeping the tree.
+pub fn root<T: AsRef<[u8]>>(mode: Mode, leaves: &[T]) -> Hash {
+    MerkleTree::new(mode, leaves).root()
+}
+
+/// Checks that `leaf` is in the tree with `root`. In sorted mode the index only
+/// shapes the walk (which levels carry), not the hashing order.
+pub fn verify(mode: Mode, root: &Hash, leaf: &[u8], proof: &Proof) -> bool {
+    if proof.index >= proof.leaf_count { return false; }
+    let mut h = leaf_hash(leaf);
+    let (mut i, mut width) = (proof.index, proof.leaf_count);
+    let mut siblings = proof.siblings.iter();
+    while width > 1 {
+        // the last node of an odd level is carried up without a sibling
+        if !(i == width - 1 && width % 2 == 1) {
+            let Some(s) = siblings.next() else { return false };
+            h = if i % 2 == 0 { node_hash(mode, &h, s) } else { node_hash(mode, s, &h) };
+        }
+        i /= 2;
+        width = width.div_ceil(2);
+    }
+    siblings.next().is_none() && &h == root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(n: usize) -> Vec<Vec<u8>> { (0..n).map(|i| format!("leaf-{i}").into_bytes()).collect() }
+
+    /// RFC 6962 MTH, recursive definition.
+    fn rfc6962(leaves: &[Vec<u8>]) -> Hash {
+        match leaves {
+            [] => Sha256::digest([]).into(),
+            [l] => leaf_hash(l),
+            _ => {
+                let k = leaves.len().next_power_of_two() / 2;
+                node_hash(Mode::Positional, &rfc6962(&leaves[..k]), &rfc6962(&leaves[k..]))
+            }
+        }
+    }
+
+    #[test]
+    fn positional_root_matches_rfc6962() {
+        for n in 0..40 {
+            assert_eq!(root(Mode::Positional, &leaves(n)), rfc6962(&leaves(n)), "n={n}");
+        }
+    }
+
+    #[test]
+    fn proofs_verify_for_every_leaf() {
+        for mode in [Mode::Positional, Mode::Sorted] {
+            for n in 1..34 {
+                let ls = leaves(n);
+                let t = MerkleTree::new(mode, &ls);
+                for (i, l) in ls.iter().enumerate() {
+                    let p = t.proof(i).unwrap();
+                    assert!(verify(mode, &t.root(), l, &p), "{mode:?} n={n} i={i}");
+                    assert!(!verify(mode, &t.root(), b"other", &p));
+                }
+                assert!(t.proof(n).is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn positional_proofs_bind_the_index() {
+        let ls = leaves(8);
+        let t = MerkleTree::new(Mode::Positional, &ls);
+        let mut p = t.proof(2).unwrap();
+        p.index = 3;
+        assert!(!verify(Mode::Positional, &t.root(), &ls[2], &p));
+    }
+
+    #[test]
+    fn sorted_mode_is_order_independent_per_pair() {
+        let (a, b) = (leaf_hash(b"a"), leaf_hash(b"b"));
+        assert_eq!(node_hash(Mode::Sorted, &a, &b), node_hash(Mode::Sorted, &b, &a));
+        assert_ne!(node_hash(Mode::Positional, &a, &b), node_hash(Mode::Positional, &b, &a));
+    }
+
+    #[test]
+    fn rejects_truncated_or_padded_proofs() {
+        let ls = leaves(5);
+        let t = MerkleTree::new(Mode::Positional, &ls);
+        let p = t.proof(1).unwrap();
+        let mut short = p.clone();
+        short.siblings.pop();
+        assert!(!verify(Mode::Positional, &t.root(), &ls[1], &short));
+        let mut long = p;
+        long.siblings.push([0; 32]);
+        assert!(!verify(Mode::Positional, &t.root(), &ls[1], &long));
+    }
+}
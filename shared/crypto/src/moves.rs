@@ -0,0 +1,49 @@
+//! VRF input encodings for server-chosen moves and the output → move mapping.
+//!
+//! Shared by the fairness service (which proves) and anyone re-checking a
+//! proof, so both sides build byte-identical inputs.
+
+use crate::vrf::OUTPUT_LEN;
+
+/// Domain separator for AI seat moves; bump on any encoding change.
+pub const AI_MOVE_DOMAIN: &[u8] = b"rps-ai-move-v1";
+/// Domain separator for moves substituted for a player who missed a deadline.
+pub const SUBSTITUTION_DOMAIN: &[u8] = b"rps-substitute-v1";
+
+/// AI move input: `domain || u32be(len(match_id)) || match_id || u32be(turn) || u64be(round) || randomness`,
+/// where `randomness` is the drand round's 32-byte output (zeros for round 0).
+pub fn ai_move_alpha(match_id: &str, turn: u32, round: u64, randomness: &[u8; 32]) -> Vec<u8> {
+    let mut alpha = Vec::with_capacity(AI_MOVE_DOMAIN.len() + 48 + match_id.len());
+    alpha.extend_from_slice(AI_MOVE_DOMAIN);
+    push_str(&mut alpha, match_id);
+    alpha.extend_from_slice(&turn.to_be_bytes());
+    alpha.extend_from_slice(&round.to_be_bytes());
+    alpha.extend_from_slice(randomness);
+    alpha
+}
+
+/// Substitution input: like [`ai_move_alpha`] under its own domain, with the
+/// missing player's DID (length-prefixed) after `turn`, so two players timing
+/// out on the same turn get independent moves.
+pub fn substitution_alpha(match_id: &str, turn: u32, did: &str, round: u64, randomness: &[u8; 32]) -> Vec<u8> {
+    let mut alpha = Vec::with_capacity(SUBSTITUTION_DOMAIN.len() + 52 + match_id.len() + did.len());
+    alpha.extend_from_slice(SUBSTITUTION_DOMAIN);
+    push_str(&mut alpha, match_id);
+    alpha.extend_from_slice(&turn.to_be_bytes());
+    push_str(&mut alpha, did);
+    alpha.extend_from_slice(&round.to_be_bytes());
+    alpha.extend_from_slice(randomness);
+    alpha
+}
+
+/// Maps a VRF output to a move: first 16 bytes as big-endian u128, mod 3.
+pub fn move_from_output(beta: &[u8; OUTPUT_LEN]) -> char {
+    let mut head = [0u8; 16];
+    head.copy_from_slice(&beta[..16]);
+    ['R', 'P', 'S'][(u128::from_be_bytes(head) % 3) as usize]
+}
+
+fn push_str(out: &mut Vec<u8>, s: &str) {
+    out.extend_from_slice(&(s.len() as u32).to_be_bytes());
+    out.extend_from_slice(s.as_bytes());
+}
@@ -0,0 +1,222 @@
+//! Poseidon2 permutation and a byte sponge over the BN254 scalar field.
+//!
+//! Field: p = 21888242871839275222246405745257275088548364400416034343698204186575808495617
+//! (the order of BN254's G1). Parameters follow the Poseidon2 paper's reference
+//! instance: width t = 3, S-box x^5, 8 full rounds (4 before, 4 after) and 56
+//! partial rounds. The external matrix is circ(2, 1, 1), the internal one
+//! diag(1, 1, 2) + 1. Round constants come from the Grain LFSR of the
+//! reference scripts; partial rounds add a constant to the first element only.
+//!
+//! [`hash_bytes`] is a sponge with rate 2 and capacity 1: the input is split
+//! into 31-byte big-endian chunks, the capacity starts at `len(input) * 2^64`,
+//! chunks are absorbed two at a time (a missing second chunk is zero, and the
+//! empty input absorbs one zero block), and the digest is the first state
+//! element as 32 big-endian bytes.
+
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, MontFp, PrimeField};
+
+pub const T: usize = 3;
+const ROUNDS_F: usize = 8;
+const ROUNDS_P: usize = 56;
+/// Bytes per absorbed field element (248 bits, always below p).
+const CHUNK: usize = 31;
+
+/// The Poseidon2 permutation.
+pub fn permute(mut s: [Fr; T]) -> [Fr; T] {
+    external(&mut s);
+    for rc in &RC_FULL[..ROUNDS_F / 2] { full_round(&mut s, rc); }
+    for rc in &RC_PARTIAL {
+        s[0] = sbox(s[0] + rc);
+        internal(&mut s);
+    }
+    for rc in &RC_FULL[ROUNDS_F / 2..] { full_round(&mut s, rc); }
+    s
+}
+
+/// Hashes arbitrary bytes to a field element, returned as 32 big-endian bytes.
+pub fn hash_bytes(data: &[u8]) -> [u8; 32] {
+    let mut s = [Fr::from(0u64), Fr::from(0u64), Fr::from(data.len() as u64) * Fr::from(1u128 << 64)];
+    let chunks: Vec<Fr> = data.chunks(CHUNK).map(Fr::from_be_bytes_mod_order).collect();
+    let blocks = chunks.len().div_ceil(2).max(1);
+    for b in 0..blocks {
+        s[0] += chunks.get(2 * b).copied().unwrap_or_default();
+        s[1] += chunks.get(2 * b + 1).copied().unwrap_or_default();
+        s = permute(s);
+    }
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&s[0].into_bigint().to_bytes_be());
+    out
+}
+
+fn sbox(x: Fr) -> Fr {
+    let x2 = x * x;
+    x2 * x2 * x
+}
+
+fn full_round(s: &mut [Fr; T], rc: &[Fr; T]) {
+    for (x, c) in s.iter_mut().zip(rc) { *x = sbox(*x + c); }
+    external(s);
+}
+
+/// circ(2, 1, 1): add the sum of the state to every element.
+fn external(s: &mut [Fr; T]) {
+    let sum = s[0] + s[1] + s[2];
+    for x in s.iter_mut() { *x += sum; }
+}
+
+/// diag(1, 1, 2) + the all-ones matrix.
+fn internal(s: &mut [Fr; T]) {
+    let sum = s[0] + s[1] + s[2];
+    s[0] += sum;
+    s[1] += sum;
+    s[2] = s[2] + s[2] + sum;
+}
+
+const RC_FULL: [[Fr; T]; ROUNDS_F] = [
+    [
+        MontFp!("13128406282895484157369354038809433636203389051939936481821261911791933663254"),
+        MontFp!("18931653859213243425446645781588512487838213266321401679594943842133071369744"),
+        MontFp!("14100663835952519432830313936592734340076294692040144715814219945570907513297"),
+    ],
+    [
+        MontFp!("4829113795940962171577509772302063766582957624337039572002553144762883322341"),
+        MontFp!("15524196826242151316602020382811195434692947787822797536837043495207890599720"),
+        MontFp!("11824742889827005569732308046012743315382715056680481843559537371456931944245"),
+    ],
+    [
+        MontFp!("15824369292130948538570881538463827283727388637222356799784648390667783881850"),
+        MontFp!("7395652367440825515524159918310823124942438011035473842936180620057265532493"),
+        MontFp!("1241351203963627868835881804826107927839874261162687401459390240620885410254"),
+    ],
+    [
+        MontFp!("6688265362431458560657026053775250595854204120757399493099812773970419156132"),
+        MontFp!("18628865421786169197184064906533816626840829027307965436801990532221681661310"),
+        MontFp!("17770079997659052348824924629777474963416629061770380464722096481670103655806"),
+    ],
+    [
+        MontFp!("12123026335854515584932892161148559902027319284544852339906677442670161590992"),
+        MontFp!("11747143856113197599032240626240804787576886917202313931914972592787570603429"),
+        MontFp!("12689083329367969619896630238881490862330991685178863399139986099061967775891"),
+    ],
+    [
+        MontFp!("9363616378570856727297258914956380343356030981401312041884116403700849212733"),
+        MontFp!("13238291046435061349401827110993774315432323243867917623501520885175217584478"),
+        MontFp!("13857006478672530359037215101120381968370236111775805219419707798416454682620"),
+    ],
+    [
+        MontFp!("2022752961549084842139747691238383165524359342011064407942599644003308437489"),
+        MontFp!("11377043765620686524844863869245961003946340433252666374730228559486855986878"),
+        MontFp!("9107028336454933966239128359918274121166034584181733998485105905495346200934"),
+    ],
+    [
+        MontFp!("900063247840342897532382686223939136593244983486268682637380837456165317070"),
+        MontFp!("11261302954518146885624063833699323298803404236535464228351677636819579513431"),
+        MontFp!("7126990412157463341897179572979760225771626877677162088926546182321369054630"),
+    ],
+];
+
+const RC_PARTIAL: [Fr; ROUNDS_P] = [
+    MontFp!("11811415718957691261673974625780511541635150909919309658375768251762566747317"),
+    MontFp!("17491388639298611159333770975992024026420968324544834879936543171716736973879"),
+    MontFp!("5647537972700463414111873015737673282707440513292923385601908870282442800104"),
+    MontFp!("13098696909140066209556423100763036393001603197583133354863092304798723388565"),
+    MontFp!("6951180250619279643770888203380891623788978362131976553140006882493632020745"),
+    MontFp!("11250251081997661635793843737498879309304455145146915350538637298238893102958"),
+    MontFp!("2246982048814095620312232487641427155108104073024754628893054837638848127964"),
+    MontFp!("18897180842973857564376958241871700087418903006311506731527228148081597475814"),
+    MontFp!("11557404599711559103972421944754928847181400366333080241838467983028485750549"),
+    MontFp!("17156358787639157774388183034849932704703797218604790661321342987075785318260"),
+    MontFp!("8846001957151556825394442611430138293780354129800063716225175548340091032449"),
+    MontFp!("21883449834630454155761926448978525628607016008113566399646971468161186616967"),
+    MontFp!("11782201180140779170005707786217005381305915516114251118577530420880166417952"),
+    MontFp!("19574374768428302416384468550351257389078501920039012797497943057156188490399"),
+    MontFp!("8515987927591912252146893631936027853249294776314628553087138119917968203620"),
+    MontFp!("17278996890957540943430295799612663512184925495827057764219426280563743078943"),
+    MontFp!("4560144125266860756441160513270281593457202308593722614013851111005532208589"),
+    MontFp!("18507459160700813704135500972073304101922968342745790738233104310822653821881"),
+    MontFp!("12853272419783978245995917302225694649366687506910892647236063701566570840428"),
+    MontFp!("14374895923592519298500369713759001634990764548024903321294831249025876110484"),
+    MontFp!("1754533789272381217541450481312878927560073411620344950409407505576538004136"),
+    MontFp!("20448232810715691360468548645921483318770769828465347895613479253435247065293"),
+    MontFp!("4203277692183102377396835282861288449527228200284576966986741905195109677387"),
+    MontFp!("11506339386261725202512749094297334054772084639665212079028551409689271965431"),
+    MontFp!("4408799661846477128378547528471700197737434561274043409442231147309460168718"),
+    MontFp!("10862521404448958117187164110262290189825635328197001646848012017699995213390"),
+    MontFp!("7012061838863338817532836723152059636816924388921632356281537445328382279260"),
+    MontFp!("8337544039076735620694225144163354013921209405711398618659178986151546625400"),
+    MontFp!("16173744372216956516796750206695252671549928142051779144629150462255079400849"),
+    MontFp!("19072902632067672883974143637757649536845413107085656789672471396027868707732"),
+    MontFp!("3487852254355424154670010750480228751987308757772575371606146474985412561707"),
+    MontFp!("17727517395793273304860106667199855253218123164763798377815886217088561516989"),
+    MontFp!("13280131383170382695839570176732265848909891244754629477752800360224963964534"),
+    MontFp!("21504421972374418324171209120165696620934505501591484695447432472073975792776"),
+    MontFp!("13753604424945682926871108642602624411461374991709441590662260371815673344981"),
+    MontFp!("8053178768600673579416591772204841415225213226540397062676127402210384682315"),
+    MontFp!("15101558583452488762759591936595783545455044970328380152280373697190919758012"),
+    MontFp!("6286700389345423344101403023711121482167900236544298155098199100234816571786"),
+    MontFp!("19368755554193272721035317233504719593365546521121074341670771231332472422552"),
+    MontFp!("13306281365497267243785678269212920842854030794417306689235276460198094483575"),
+    MontFp!("10121764749051640353641114693266514664967620368543293902008953934189850195966"),
+    MontFp!("179619165022370308972665071682395477322215797039585945216341070107573537790"),
+    MontFp!("14053393851645634065914179337120715807963438235922115988819572738574714471437"),
+    MontFp!("17345906218970918797922168310670548252023720338285437740234091480846393436478"),
+    MontFp!("10383068492552043678323859571562933490503408853170063884414176092784243607055"),
+    MontFp!("12096041499044892166554391619429604246288825927654072010011878199637889490527"),
+    MontFp!("6449742640166027959651492823149770763572943879017164812917305794918053034585"),
+    MontFp!("6551805454148805882554763665748573416514894105513920161214733482541847062214"),
+    MontFp!("3651410956659878392469489270906333016569562868954890104332567650040497030813"),
+    MontFp!("15219053914464753937310253926447830297339787956721755285255510737973021838676"),
+    MontFp!("881679665678132972106931291023348167890022611850562267871389203532691753422"),
+    MontFp!("5006067481688857073852527145736822635357747460125905556158034280392250104971"),
+    MontFp!("12765332320844032254009314500332101047115754896003948733635815046365410860591"),
+    MontFp!("12908190215073542091623737558383307555705501651914623082354191483197810853182"),
+    MontFp!("1446042792715825508366007519346636771782990303010685652946852324744810237839"),
+    MontFp!("17414863822034645298427260856470503848317996477890518738401812766215195632841"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // computed with an independent Python implementation of the sponge above
+    const SPONGE_VECTORS: &[(&str, &str)] = &[
+        ("", "2ed1da00b14d635bd35b88ab49390d5c13c90da7e9e3a5f1ea69cd87a0aa3e82"),
+        ("abc", "20b6481731be69e3a5e9faa425dc4d4fab81433cb6c500cf5e2dd735dcb945f1"),
+        ("rps", "1b14f7e21e7e4f232e22a62582a713465d027e5e69397ccffb723bf4d4ab7e62"),
+        ("xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx", "196a25f066d1d45eb0363d9564c3ce455f3f078365177ce41caaa5ee5e6f955a"),
+        ("xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx", "148fdab600214dfa655157be40b45d0bf036edf7a46d5a88a6d5f7346cd5a43c"),
+        ("rock paper scissors, best of nine, commit then reveal", "05324b22cc8e9be9ebad8d1d2620978dff0e361e4347fe5cedf0c3d255b0916d"),
+    ];
+
+    fn fr(hex: &str) -> Fr {
+        let b: Vec<u8> = (0..hex.len()).step_by(2).map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap()).collect();
+        Fr::from_be_bytes_mod_order(&b)
+    }
+
+    #[test]
+    fn permutation_matches_reference_vector() {
+        // HorizenLabs poseidon2, bn256 t=3, input [0, 1, 2]
+        let out = permute([Fr::from(0u64), Fr::from(1u64), Fr::from(2u64)]);
+        assert_eq!(out, [
+            fr("0bb61d24daca55eebcb1929a82650f328134334da98ea4f847f760054f4a3033"),
+            fr("303b6f7c86d043bfcbcc80214f26a30277a15d3f74ca654992defe7ff8d03570"),
+            fr("1ed25194542b12eef8617361c3ba7c52e660b145994427cc86296242cf766ec8"),
+        ]);
+    }
+
+    #[test]
+    fn sponge_vectors() {
+        for (input, digest) in SPONGE_VECTORS {
+            let h = hash_bytes(input.as_bytes());
+            let hex: String = h.iter().map(|b| format!("{b:02x}")).collect();
+            assert_eq!(hex, *digest, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn length_is_bound() {
+        assert_ne!(hash_bytes(b""), hash_bytes(&[0]));
+        assert_ne!(hash_bytes(&[0]), hash_bytes(&[0, 0]));
+    }
+}
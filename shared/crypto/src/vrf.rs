@@ -0,0 +1,227 @@
+//! ECVRF-EDWARDS25519-SHA512-TAI (RFC 9381, suite 0x03).
+//!
+//! Keys use the Ed25519 format: a 32-byte secret seed and a 32-byte
+//! compressed Edwards point. Proofs are 80 bytes (`Gamma || c || s`) and the
+//! VRF output (`beta`) is 64 bytes.
+
+use curve25519_dalek::constants::ED25519_BASEPOINT_POINT;
+use curve25519_dalek::edwards::{CompressedEdwardsY, EdwardsPoint};
+use curve25519_dalek::scalar::{clamp_integer, Scalar};
+use sha2::{Digest, Sha512};
+
+/// Human-readable suite name, published next to the public key.
+pub const SUITE_NAME: &str = "ECVRF-EDWARDS25519-SHA512-TAI";
+const SUITE: u8 = 0x03;
+const C_LEN: usize = 16;
+pub const PROOF_LEN: usize = 80;
+pub const OUTPUT_LEN: usize = 64;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum VrfError {
+    #[error("invalid public key")]
+    InvalidPublicKey,
+    #[error("malformed proof")]
+    MalformedProof,
+    #[error("proof does not verify")]
+    InvalidProof,
+}
+
+/// A VRF secret key (Ed25519 seed) with its derived scalar and public key.
+#[derive(Clone)]
+pub struct SecretKey {
+    seed: [u8; 32],
+    x: Scalar,
+    pk: PublicKey,
+}
+
+/// A VRF public key: a validated compressed Edwards point.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PublicKey {
+    bytes: [u8; 32],
+    point: EdwardsPoint,
+}
+
+impl SecretKey {
+    /// Derives the key pair from a 32-byte seed as in RFC 8032.
+    pub fn from_seed(seed: [u8; 32]) -> Self {
+        let h = Sha512::digest(seed);
+        let mut lower = [0u8; 32];
+        lower.copy_from_slice(&h[..32]);
+        let x = Scalar::from_bytes_mod_order(clamp_integer(lower));
+        let point = ED25519_BASEPOINT_POINT * x;
+        let pk = PublicKey { bytes: point.compress().to_bytes(), point };
+        SecretKey { seed, x, pk }
+    }
+
+    pub fn public_key(&self) -> PublicKey { self.pk }
+
+    /// ECVRF_prove: returns the 80-byte proof for `alpha`.
+    pub fn prove(&self, alpha: &[u8]) -> [u8; PROOF_LEN] {
+        let h = encode_to_curve(&self.pk.bytes, alpha);
+        let h_bytes = h.compress().to_bytes();
+        let gamma = h * self.x;
+        let k = self.nonce(&h_bytes);
+        let c = challenge(&[&self.pk.point, &h, &gamma, &(ED25519_BASEPOINT_POINT * k), &(h * k)]);
+        let s = k + c * self.x;
+        let mut pi = [0u8; PROOF_LEN];
+        pi[..32].copy_from_slice(gamma.compress().as_bytes());
+        pi[32..32 + C_LEN].copy_from_slice(&c.as_bytes()[..C_LEN]);
+        pi[32 + C_LEN..].copy_from_slice(s.as_bytes());
+        pi
+    }
+
+    // RFC 9381 5.4.2.2: nonce from the upper half of SHA512(seed) and H
+    fn nonce(&self, h_bytes: &[u8; 32]) -> Scalar {
+        let hashed = Sha512::digest(self.seed);
+        let mut hasher = Sha512::new();
+        hasher.update(&hashed[32..]);
+        hasher.update(h_bytes);
+        Scalar::from_bytes_mod_order_wide(&hasher.finalize().into())
+    }
+}
+
+impl PublicKey {
+    /// Decodes and validates a public key, rejecting non-canonical encodings
+    /// and small-order points.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VrfError> {
+        let bytes: [u8; 32] = bytes.try_into().map_err(|_| VrfError::InvalidPublicKey)?;
+        let point = decode_point(&bytes).ok_or(VrfError::InvalidPublicKey)?;
+        if point.is_small_order() { return Err(VrfError::InvalidPublicKey); }
+        Ok(PublicKey { bytes, point })
+    }
+
+    pub fn to_bytes(&self) -> [u8; 32] { self.bytes }
+
+    /// ECVRF_verify: checks `pi` for `alpha` and returns the VRF output.
+    pub fn verify(&self, alpha: &[u8], pi: &[u8]) -> Result<[u8; OUTPUT_LEN], VrfError> {
+        let (gamma, c, s) = decode_proof(pi)?;
+        let h = encode_to_curve(&self.bytes, alpha);
+        let u = ED25519_BASEPOINT_POINT * s - self.point * c;
+        let v = h * s - gamma * c;
+        let c2 = challenge(&[&self.point, &h, &gamma, &u, &v]);
+        if c2 != c { return Err(VrfError::InvalidProof); }
+        Ok(gamma_to_hash(&gamma))
+    }
+}
+
+/// ECVRF_proof_to_hash: the VRF output for a (not yet verified) proof.
+pub fn proof_to_hash(pi: &[u8]) -> Result<[u8; OUTPUT_LEN], VrfError> {
+    let (gamma, _, _) = decode_proof(pi)?;
+    Ok(gamma_to_hash(&gamma))
+}
+
+fn gamma_to_hash(gamma: &EdwardsPoint) -> [u8; OUTPUT_LEN] {
+    let mut hasher = Sha512::new();
+    hasher.update([SUITE, 0x03]);
+    hasher.update(gamma.mul_by_cofactor().compress().as_bytes());
+    hasher.update([0x00]);
+    hasher.finalize().into()
+}
+
+// RFC 9381 5.4.1.1: try-and-increment hash to curve
+fn encode_to_curve(pk_bytes: &[u8; 32], alpha: &[u8]) -> EdwardsPoint {
+    for ctr in 0u8..=255 {
+        let mut hasher = Sha512::new();
+        hasher.update([SUITE, 0x01]);
+        hasher.update(pk_bytes);
+        hasher.update(alpha);
+        hasher.update([ctr, 0x00]);
+        let digest = hasher.finalize();
+        let mut candidate = [0u8; 32];
+        candidate.copy_from_slice(&digest[..32]);
+        if let Some(p) = decode_point(&candidate) {
+            return p.mul_by_cofactor();
+        }
+    }
+    // each attempt succeeds with probability ~1/2; 256 failures does not happen
+    unreachable!("encode_to_curve exhausted its counter")
+}
+
+// RFC 9381 5.4.3: challenge over the five points, truncated to C_LEN bytes
+fn challenge(points: &[&EdwardsPoint; 5]) -> Scalar {
+    let mut hasher = Sha512::new();
+    hasher.update([SUITE, 0x02]);
+    for p in points { hasher.update(p.compress().as_bytes()); }
+    hasher.update([0x00]);
+    let digest = hasher.finalize();
+    let mut c = [0u8; 32];
+    c[..C_LEN].copy_from_slice(&digest[..C_LEN]);
+    Scalar::from_bytes_mod_order(c)
+}
+
+fn decode_proof(pi: &[u8]) -> Result<(EdwardsPoint, Scalar, Scalar), VrfError> {
+    if pi.len() != PROOF_LEN { return Err(VrfError::MalformedProof); }
+    let mut g = [0u8; 32];
+    g.copy_from_slice(&pi[..32]);
+    let gamma = decode_point(&g).ok_or(VrfError::MalformedProof)?;
+    let mut c = [0u8; 32];
+    c[..C_LEN].copy_from_slice(&pi[32..32 + C_LEN]);
+    let mut s = [0u8; 32];
+    s.copy_from_slice(&pi[32 + C_LEN..]);
+    let s = Option::<Scalar>::from(Scalar::from_canonical_bytes(s)).ok_or(VrfError::MalformedProof)?;
+    Ok((gamma, Scalar::from_bytes_mod_order(c), s))
+}
+
+// RFC 8032 decoding: reject encodings that do not round-trip (y >= p, or x = 0 with the sign bit set)
+fn decode_point(bytes: &[u8; 32]) -> Option<EdwardsPoint> {
+    let p = CompressedEdwardsY(*bytes).decompress()?;
+    (p.compress().as_bytes() == bytes).then_some(p)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unhex(s: &str) -> Vec<u8> { (0..s.len()).step_by(2).map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap()).collect() }
+
+    // RFC 9381 Appendix B.3, Examples 16-18: (SK, PK, alpha, pi, beta)
+    const VECTORS: [(&str, &str, &str, &str, &str); 3] = [
+        (
+            "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "",
+            "8657106690b5526245a92b003bb079ccd1a92130477671f6fc01ad16f26f723f26f8a57ccaed74ee1b190bed1f479d9727d2d0f9b005a6e456a35d4fb0daab1268a1b0db10836d9826a528ca76567805",
+            "90cf1df3b703cce59e2a35b925d411164068269d7b2d29f3301c03dd757876ff66b71dda49d2de59d03450451af026798e8f81cd2e333de5cdf4f3e140fdd8ae",
+        ),
+        (
+            "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+            "72",
+            "f3141cd382dc42909d19ec5110469e4feae18300e94f304590abdced48aed5933bf0864a62558b3ed7f2fea45c92a465301b3bbf5e3e54ddf2d935be3b67926da3ef39226bbc355bdc9850112c8f4b02",
+            "eb4440665d3891d668e7e0fcaf587f1b4bd7fbfe99d0eb2211ccec90496310eb5e33821bc613efb94db5e5b54c70a848a0bef4553a41befc57663b56373a5031",
+        ),
+        (
+            "c5aa8df43f9f837bedb7442f31dcb7b166d38535076f094b85ce3a2e0b4458f7",
+            "fc51cd8e6218a1a38da47ed00230f0580816ed13ba3303ac5deb911548908025",
+            "af82",
+            "9bc0f79119cc5604bf02d23b4caede71393cedfbb191434dd016d30177ccbf8096bb474e53895c362d8628ee9f9ea3c0e52c7a5c691b6c18c9979866568add7a2d41b00b05081ed0f58ee5e31b3a970e",
+            "645427e5d00c62a23fb703732fa5d892940935942101e456ecca7bb217c61c452118fec1219202a0edcf038bb6373241578be7217ba85a2687f7a0310b2df19f",
+        ),
+    ];
+
+    #[test]
+    fn matches_rfc9381_vectors() {
+        for (sk, pk, alpha, pi, beta) in VECTORS {
+            let key = SecretKey::from_seed(unhex(sk).try_into().unwrap());
+            let alpha = unhex(alpha);
+            assert_eq!(key.public_key().to_bytes().to_vec(), unhex(pk), "pk for {sk}");
+            assert_eq!(key.prove(&alpha).to_vec(), unhex(pi), "pi for {sk}");
+            let pk = PublicKey::from_bytes(&unhex(pk)).unwrap();
+            assert_eq!(pk.verify(&alpha, &unhex(pi)).unwrap().to_vec(), unhex(beta), "beta for {sk}");
+            assert_eq!(proof_to_hash(&unhex(pi)).unwrap().to_vec(), unhex(beta));
+        }
+    }
+
+    #[test]
+    fn rejects_a_proof_for_other_input_or_key() {
+        let (sk, _, _, pi, _) = VECTORS[1];
+        let pk = SecretKey::from_seed(unhex(sk).try_into().unwrap()).public_key();
+        assert_eq!(pk.verify(b"s", &unhex(pi)), Err(VrfError::InvalidProof));
+        let other = PublicKey::from_bytes(&unhex(VECTORS[2].1)).unwrap();
+        assert_eq!(other.verify(&[0x72], &unhex(pi)), Err(VrfError::InvalidProof));
+        let mut tampered = unhex(pi);
+        tampered[40] ^= 1;
+        assert_eq!(pk.verify(&[0x72], &tampered), Err(VrfError::InvalidProof));
+        assert_eq!(pk.verify(&[0x72], &tampered[..79]), Err(VrfError::MalformedProof));
+    }
+}
@@ -1,29 +1,44 @@
-export type ReadyForRound = { tid: string; round: number };
-export type Heartbeat = {};
-export type SdpOffer = { matchId: string; sdp: string };
-export type SdpAnswer = { matchId: string; sdp: string };
-export type Ice = { matchId: string; candidate: string };
-export type CommitHashes = { matchId: string; hashes: string[] };
-export type Reveal = { matchId: string; turn: number; move: 'R'|'P'|'S'; nonce: string };
-
-export type ClientToServer =
-  | { type: 'READY_FOR_ROUND'; data: ReadyForRound }
-  | { type: 'HEARTBEAT'; data: Heartbeat }
-  | { type: 'SDP_OFFER'; data: SdpOffer }
-  | { type: 'SDP_ANSWER'; data: SdpAnswer }
-  | { type: 'ICE'; data: Ice }
-  | { type: 'COMMIT_HASHES'; data: CommitHashes }
-  | { type: 'REVEAL'; data: Reveal };
-
-export type Assign = { matchId: string; role: 'P1'|'P2'; peer: { did: string; handle: string }; rtc: { turns: string[] } };
-export type TurnStart = { matchId: string; turn: number; deadlineMsEpoch: number };
-export type TurnResult = { matchId: string; turn: number; result: 'P1'|'P2'|'DRAW'; ai?: boolean };
-export type MatchResult = { matchId: string; winner: string };
-export type ErrorMsg = { code: string; msg: string };
-
-export type ServerToClient =
-  | { type: 'ASSIGN'; data: Assign }
-  | { type: 'TURN_START'; data: TurnStart }
-  | { type: 'TURN_RESULT'; data: TurnResult }
-  | { type: 'MATCH_RESULT'; data: MatchResult }
-  | { type: 'ERROR'; data: ErrorMsg };
+// Generated by `cargo xtask codegen` from rps-shared-types. Do not edit.
+
+export const PROTOCOL_VERSION = 1;
+export const MIN_PROTOCOL_VERSION = 1;
+
+export type Hello = { protocol_version: number, client_name?: string, capabilities?: Array<string>, };
+
+export type ReadyForRound = { tid: string, round: number, };
+
+export type Heartbeat = Record<string, never>;
+
+export type SdpOffer = { match_id: string, sdp: string, };
+
+export type SdpAnswer = { match_id: string, sdp: string, };
+
+export type Ice = { match_id: string, candidate: string, };
+
+export type CommitHashes = { match_id: string, hashes: Array<string>, };
+
+export type Reveal = { match_id: string, turn: number, move_: string, nonce: string, };
+
+export type ClientToServer = { "type": "HELLO" } & Hello | { "type": "READY_FOR_ROUND" } & ReadyForRound | { "type": "HEARTBEAT" } & Heartbeat | { "type": "SDP_OFFER" } & SdpOffer | { "type": "SDP_ANSWER" } & SdpAnswer | { "type": "ICE" } & Ice | { "type": "COMMIT_HASHES" } & CommitHashes | { "type": "REVEAL" } & Reveal;
+
+export type Assign = { match_id: string, role: string, peer: Peer, rtc: RtcConfig, commit_version?: string, };
+
+export type Peer = { did: string, handle: string, };
+
+export type RtcConfig = { turns: Array<string>, };
+
+export type TurnStart = { match_id: string, turn: number, deadline_ms_epoch: number, now_ms_epoch: number, };
+
+export type TurnResult = { match_id: string, turn: number, result: string, ai: boolean | null, ai_for_dids: Array<string> | null, p1_move: string | null, p2_move: string | null, substitutions?: Array<Substitution>, };
+
+export type Substitution = { did: string, move_: string, vrf_output: string, vrf_proof: string, public_key: string, drand_round: number, drand_randomness: string, source: string, };
+
+export type MatchResult = { match_id: string, winner: string, commits_root?: string, };
+
+export type OpponentLeft = { match_id: string, };
+
+export type ErrorMsg = { code: string, msg: string, };
+
+export type Welcome = { protocol_version: number, min_protocol_version: number, server_name: string, capabilities: Array<string>, };
+
+export type ServerToClient = { "type": "ASSIGN" } & Assign | { "type": "TURN_START" } & TurnStart | { "type": "TURN_RESULT" } & TurnResult | { "type": "MATCH_RESULT" } & MatchResult | { "type": "OPPONENT_LEFT" } & OpponentLeft | { "type": "ERROR" } & ErrorMsg | { "type": "WELCOME" } & Welcome;
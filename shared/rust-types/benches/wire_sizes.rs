@@ -0,0 +1,68 @@
+//! Frame sizes and encode/decode cost of each wire encoding for typical match
+//! traffic. Run with `cargo bench -p rps-shared-types --bench wire_sizes`.
+
+use rps_shared_types::wire::Encoding;
+use rps_shared_types::*;
+use std::hint::black_box;
+use std::time::Instant;
+
+const ITERS: u32 = 20_000;
+const MID: &str = "cup-2026-r3-did_plc_ewvi7nxzyoun6zhxrhs64oiz-did_plc_z72i7hdynmk6r22z27h6tvur";
+
+fn hex(n: usize) -> String { "9f".repeat(n) }
+
+fn server_messages() -> Vec<(&'static str, ServerToClient)> {
+  vec![
+    ("ASSIGN", ServerToClient::Assign(Assign {
+      match_id: MID.into(), role: "P1".into(),
+      peer: Peer { did: "did:plc:z72i7hdynmk6r22z27h6tvur".into(), handle: "bob.bsky.social".into() },
+      rtc: RtcConfig { turns: vec!["stun:stun.l.google.com:19302".into()] }, commit_version: Some("v2".into()),
+    })),
+    ("TURN_START", ServerToClient::TurnStart(TurnStart { match_id: MID.into(), turn: 4, deadline_ms_epoch: 1_792_345_678_901, now_ms_epoch: 1_792_345_668_901 })),
+    ("TURN_RESULT", ServerToClient::TurnResult(TurnResult {
+      match_id: MID.into(), turn: 4, result: "P2".into(), ai: Some(false), ai_for_dids: Some(vec![]),
+      p1_move: Some("R".into()), p2_move: Some("P".into()), substitutions: vec![],
+    })),
+    ("TURN_RESULT (substituted)", ServerToClient::TurnResult(TurnResult {
+      match_id: MID.into(), turn: 5, result: "P1".into(), ai: Some(true), ai_for_dids: Some(vec!["did:plc:z72i7hdynmk6r22z27h6tvur".into()]),
+      p1_move: Some("S".into()), p2_move: Some("P".into()),
+      substitutions: vec![Substitution {
+        did: "did:plc:z72i7hdynmk6r22z27h6tvur".into(), move_: "P".into(), vrf_output: hex(32), vrf_proof: hex(80),
+        public_key: hex(32), drand_round: 5_123_456, drand_randomness: hex(32), source: "fairness".into(),
+      }],
+    })),
+    ("MATCH_RESULT", ServerToClient::MatchResult(MatchResult { match_id: MID.into(), winner: "did:plc:ewvi7nxzyoun6zhxrhs64oiz".into(), commits_root: Some(hex(32)) })),
+  ]
+}
+
+fn client_messages() -> Vec<(&'static str, ClientToServer)> {
+  vec![
+    ("HEARTBEAT", ClientToServer::Heartbeat(Heartbeat {})),
+    ("COMMIT_HASHES", ClientToServer::CommitHashes(CommitHashes { match_id: MID.into(), hashes: std::array::from_fn(|_| hex(32)) })),
+    ("REVEAL", ClientToServer::Reveal(Reveal { match_id: MID.into(), turn: 4, move_: "R".into(), nonce: hex(32) })),
+  ]
+}
+
+fn row<T: serde::Serialize + serde::de::DeserializeOwned>(name: &str, msg: &T) {
+  let sizes: Vec<usize> = Encoding::ALL.iter().map(|e| e.encode(msg).unwrap().len()).collect();
+  let mut cells = String::new();
+  for (enc, size) in Encoding::ALL.iter().zip(&sizes) {
+    let bytes = enc.encode(msg).unwrap();
+    let start = Instant::now();
+    for _ in 0..ITERS {
+      black_box(enc.encode(black_box(msg)).unwrap());
+      black_box(enc.decode::<T>(black_box(&bytes)).unwrap());
+    }
+    let ns = start.elapsed().as_nanos() / ITERS as u128;
+    cells.push_str(&format!(" {:>5} B {:>3}% {:>6} ns |", size, size * 100 / sizes[0], ns));
+  }
+  println!("| {:<26} |{}", name, cells);
+}
+
+fn main() {
+  println!("size, size relative to JSON, and encode+decode time per message\n");
+  println!("| {:<26} |{}", "message", Encoding::ALL.iter().map(|e| format!(" {:<21} |", e.name())).collect::<String>());
+  println!("|{}|{}", "-".repeat(28), "-----------------------|".repeat(Encoding::ALL.len()));
+  for (name, msg) in server_messages() { row(name, &msg); }
+  for (name, msg) in client_messages() { row(name, &msg); }
+}
@@ -0,0 +1,82 @@
+//! Generates serde types for the record schemas in `shared/lexicon` into
+//! `$OUT_DIR/lexicon.rs` (included by `src/lexicon.rs`), so the Rust types are
+//! always the schemas as checked in.
+
+use serde_json::Value;
+use std::fmt::Write as _;
+use std::path::Path;
+
+fn main() {
+  let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../lexicon");
+  println!("cargo:rerun-if-changed={}", dir.display());
+  let mut files: Vec<_> = std::fs::read_dir(&dir).expect("shared/lexicon is readable")
+    .filter_map(|e| e.ok().map(|e| e.path()))
+    .filter(|p| p.extension().is_some_and(|x| x == "json"))
+    .collect();
+  files.sort();
+
+  let mut out = String::new();
+  let mut schemas = String::new();
+  for path in &files {
+    println!("cargo:rerun-if-changed={}", path.display());
+    let text = std::fs::read_to_string(path).unwrap();
+    let schema: Value = serde_json::from_str(&text).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+    let nsid = schema["id"].as_str().unwrap_or_else(|| panic!("{}: missing id", path.display()));
+    let file = path.file_name().unwrap().to_string_lossy();
+    record_type(&mut out, nsid, &file, &schema);
+    let abs = path.canonicalize().unwrap();
+    writeln!(schemas, "  ({nsid:?}, include_str!({:?})),", abs.display().to_string()).unwrap();
+  }
+  writeln!(out, "/// Every lexicon schema, by NSID.\npub const SCHEMAS: &[(&str, &str)] = &[\n{schemas}];").unwrap();
+  std::fs::write(Path::new(&std::env::var("OUT_DIR").unwrap()).join("lexicon.rs"), out).unwrap();
+}
+
+fn record_type(out: &mut String, nsid: &str, file: &str, schema: &Value) {
+  assert_eq!(schema["type"], "object", "{nsid}: records must be objects");
+  let name = pascal(nsid.rsplit('.').next().unwrap());
+  let required: Vec<&str> = schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+  let closed = schema["additionalProperties"] == false;
+  writeln!(out, "/// `{nsid}`, generated from `shared/lexicon/{file}`.").unwrap();
+  writeln!(out, "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]").unwrap();
+  if closed { writeln!(out, "#[serde(deny_unknown_fields)]").unwrap(); }
+  writeln!(out, "pub struct {name} {{").unwrap();
+  for (field, prop) in schema["properties"].as_object().into_iter().flatten() {
+    let ty = rust_type(nsid, field, prop);
+    let snake = snake(field);
+    if snake != *field { writeln!(out, "  #[serde(rename = {field:?})]").unwrap(); }
+    if required.contains(&field.as_str()) {
+      writeln!(out, "  pub {snake}: {ty},").unwrap();
+    } else {
+      writeln!(out, "  #[serde(default, skip_serializing_if = \"Option::is_none\")]").unwrap();
+      writeln!(out, "  pub {snake}: Option<{ty}>,").unwrap();
+    }
+  }
+  writeln!(out, "}}\n\nimpl LexiconRecord for {name} {{\n  const NSID: &'static str = {nsid:?};\n}}\n").unwrap();
+}
+
+fn rust_type(nsid: &str, field: &str, prop: &Value) -> String {
+  match prop["type"].as_str() {
+    Some("string") => "String".into(),
+    Some("integer") if prop["minimum"].as_i64().is_some_and(|m| m >= 0) => "u64".into(),
+    Some("integer") => "i64".into(),
+    Some("number") => "f64".into(),
+    Some("boolean") => "bool".into(),
+    Some("array") => format!("Vec<{}>", rust_type(nsid, field, &prop["items"])),
+    Some("object") => "serde_json::Value".into(),
+    other => panic!("{nsid}.{field}: unsupported type {other:?}"),
+  }
+}
+
+fn pascal(s: &str) -> String {
+  let mut c = s.chars();
+  c.next().map(|f| f.to_ascii_uppercase().to_string() + c.as_str()).unwrap_or_default()
+}
+
+fn snake(s: &str) -> String {
+  let mut out = String::new();
+  for ch in s.chars() {
+    if ch.is_ascii_uppercase() { out.push('_'); }
+    out.push(ch.to_ascii_lowercase());
+  }
+  out
+}
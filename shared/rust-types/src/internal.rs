@@ -0,0 +1,44 @@
+//! Shared secret for service-to-service endpoints.
+//!
+//! Internal routes (signaling `/admin/abandon`, coordinator `/claim`, ...) are
+//! only for the other services. Callers send `Authorization: Bearer <token>`
+//! with the `INTERNAL_TOKEN` every service is started with; while it is unset
+//! the routes refuse everyone.
+
+/// Environment variable holding the token.
+pub const ENV: &str = "INTERNAL_TOKEN";
+
+pub fn token() -> Option<String> {
+  std::env::var(ENV).ok().filter(|t| !t.is_empty())
+}
+
+/// `Authorization` header value for outgoing internal calls, if configured.
+pub fn bearer() -> Option<String> {
+  token().map(|t| format!("Bearer {t}"))
+}
+
+/// Whether an `Authorization` header value carries the configured token.
+pub fn authorized(header: Option<&str>) -> bool {
+  check(token().as_deref(), header)
+}
+
+fn check(expected: Option<&str>, header: Option<&str>) -> bool {
+  let (Some(expected), Some(given)) = (expected, header.and_then(|h| h.strip_prefix("Bearer "))) else { return false };
+  // length is not secret; compare the bytes without an early exit
+  given.len() == expected.len() && given.bytes().zip(expected.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+  use super::check;
+
+  #[test]
+  fn needs_a_configured_matching_bearer_token() {
+    assert!(check(Some("s3cret"), Some("Bearer s3cret")));
+    assert!(!check(Some("s3cret"), Some("Bearer s3cre")));
+    assert!(!check(Some("s3cret"), Some("Bearer s3creT")));
+    assert!(!check(Some("s3cret"), Some("s3cret")));
+    assert!(!check(Some("s3cret"), None));
+    assert!(!check(None, Some("Bearer ")));
+  }
+}
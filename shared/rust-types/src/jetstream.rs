@@ -0,0 +1,76 @@
+//! Jetstream events (the JSON firehose at `wss://jetstream*.bsky.network/subscribe`)
+//! and a reader for local JSONL files of the same events.
+
+use serde::Deserialize;
+use std::io::{Read, Seek, SeekFrom};
+
+/// A Jetstream event; only `commit` events carry records.
+#[derive(Debug, Clone, Deserialize)]
+pub struct JetstreamEvent {
+  pub did: String,
+  pub time_us: i64,
+  pub kind: String,
+  #[serde(default)]
+  pub commit: Option<CommitEvent>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CommitEvent {
+  pub operation: String,
+  pub collection: String,
+  pub rkey: String,
+  #[serde(default)]
+  pub record: Option<serde_json::Value>,
+  #[serde(default)]
+  pub cid: Option<String>,
+}
+
+impl JetstreamEvent {
+  /// The commit, for `commit` events.
+  pub fn commit(&self) -> Option<&CommitEvent> { self.commit.as_ref().filter(|_| self.kind == "commit") }
+
+  /// `at://` URI of the committed record.
+  pub fn uri(&self) -> Option<String> { self.commit().map(|c| format!("at://{}/{}/{}", self.did, c.collection, c.rkey)) }
+}
+
+/// Reads complete lines appended to `path` since `offset`; returns each line
+/// parsed (malformed lines as errors) and the offset after the last complete line.
+pub fn read_new_events(path: &str, offset: u64) -> std::io::Result<(Vec<serde_json::Result<JetstreamEvent>>, u64)> {
+  let mut f = std::fs::File::open(path)?;
+  f.seek(SeekFrom::Start(offset))?;
+  let mut buf = String::new();
+  f.read_to_string(&mut buf)?;
+  let complete = buf.rfind('\n').map_or(0, |i| i + 1);
+  let events = buf[..complete].lines().filter(|l| !l.trim().is_empty()).map(serde_json::from_str).collect();
+  Ok((events, offset + complete as u64))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn replays_only_complete_lines() {
+    let path = std::env::temp_dir().join(format!("rps-jetstream-{}.jsonl", std::process::id()));
+    let line = serde_json::json!({ "did": "did:plc:a", "time_us": 7, "kind": "identity" }).to_string();
+    std::fs::write(&path, format!("{line}\nnot json\n{line}\n{{\"did\":")).unwrap();
+    let p = path.to_str().unwrap();
+    let (events, offset) = read_new_events(p, 0).unwrap();
+    assert_eq!(events.iter().map(Result::is_ok).collect::<Vec<_>>(), [true, false, true]);
+    assert_eq!(offset as usize, 2 * (line.len() + 1) + "not json\n".len());
+    let (again, same) = read_new_events(p, offset).unwrap();
+    assert!(again.is_empty());
+    assert_eq!(same, offset);
+    std::fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn commit_uri() {
+    let ev: JetstreamEvent = serde_json::from_value(serde_json::json!({
+      "did": "did:plc:a", "time_us": 1, "kind": "commit",
+      "commit": { "rev": "r", "operation": "delete", "collection": "app.bsky.feed.post", "rkey": "3k" }
+    })).unwrap();
+    assert_eq!(ev.uri().as_deref(), Some("at://did:plc:a/app.bsky.feed.post/3k"));
+    assert!(ev.commit().unwrap().record.is_none());
+  }
+}
@@ -0,0 +1,165 @@
+//! Record types generated from `shared/lexicon` (see `build.rs`) and a runtime
+//! validator for the same schemas.
+//!
+//! The validator covers the JSON Schema subset the lexicon files use: `type`,
+//! `properties`, `required`, `additionalProperties: false`, `items`,
+//! `minimum`/`maximum`, `pattern` and `format: date-time`. A record's `$type`,
+//! if present, must be the schema's NSID.
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+include!(concat!(env!("OUT_DIR"), "/lexicon.rs"));
+
+/// Where a record departs from its schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexiconError { pub path: String, pub reason: String }
+
+impl LexiconError {
+  fn new(path: &str, reason: impl Into<String>) -> Self { LexiconError { path: path.to_string(), reason: reason.into() } }
+}
+
+impl std::fmt::Display for LexiconError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { write!(f, "{}: {}", self.path, self.reason) }
+}
+
+impl std::error::Error for LexiconError {}
+
+/// A generated record type and its NSID.
+pub trait LexiconRecord: Serialize + DeserializeOwned {
+  const NSID: &'static str;
+
+  /// Validates `value` against the schema, then parses it (dropping `$type`).
+  fn from_record(mut value: Value) -> Result<Self, LexiconError> {
+    validate(Self::NSID, &value)?;
+    if let Some(o) = value.as_object_mut() { o.remove("$type"); }
+    serde_json::from_value(value).map_err(|e| LexiconError::new("record", e.to_string()))
+  }
+
+  /// The record as stored in a repo, with `$type` set.
+  fn to_record(&self) -> Value {
+    let mut v = serde_json::to_value(self).expect("records serialize");
+    if let Some(o) = v.as_object_mut() { o.insert("$type".into(), Self::NSID.into()); }
+    v
+  }
+}
+
+/// Parsed schema for `nsid`, if there is one.
+pub fn schema(nsid: &str) -> Option<&'static Value> {
+  static PARSED: OnceLock<HashMap<&'static str, Value>> = OnceLock::new();
+  PARSED.get_or_init(|| SCHEMAS.iter().map(|(id, text)| (*id, serde_json::from_str(text).expect("lexicon schemas are JSON"))).collect())
+    .get(nsid)
+}
+
+/// Checks `record` against the schema for `nsid`.
+pub fn validate(nsid: &str, record: &Value) -> Result<(), LexiconError> {
+  let schema = schema(nsid).ok_or_else(|| LexiconError::new("record", format!("unknown lexicon {nsid}")))?;
+  match record.get("$type") {
+    None => {}
+    Some(t) if t == nsid => {}
+    Some(t) => return Err(LexiconError::new("$type", format!("expected {nsid}, got {t}"))),
+  }
+  check(schema, record, "record")
+}
+
+fn check(schema: &Value, v: &Value, path: &str) -> Result<(), LexiconError> {
+  let err = |reason: String| Err(LexiconError::new(path, reason));
+  match schema["type"].as_str() {
+    Some("object") => {
+      let Some(obj) = v.as_object() else { return err("expected an object".into()) };
+      for name in schema["required"].as_array().into_iter().flatten().filter_map(Value::as_str) {
+        if !obj.contains_key(name) { return Err(LexiconError::new(&join(path, name), "required")); }
+      }
+      for (name, value) in obj {
+        match schema["properties"].get(name) {
+          Some(prop) => check(prop, value, &join(path, name))?,
+          None if name == "$type" && path == "record" => {}
+          None if schema["additionalProperties"] == false => return Err(LexiconError::new(&join(path, name), "not in the schema")),
+          None => {}
+        }
+      }
+      Ok(())
+    }
+    Some("string") => {
+      let Some(s) = v.as_str() else { return err("expected a string".into()) };
+      if schema["format"] == "date-time" && chrono::DateTime::parse_from_rfc3339(s).is_err() {
+        return err("expected an RFC 3339 date-time".into());
+      }
+      if let Some(p) = schema["pattern"].as_str() {
+        let re = regex_lite::Regex::new(p).map_err(|e| LexiconError::new(path, format!("bad pattern in schema: {e}")))?;
+        if !re.is_match(s) { return err(format!("does not match {p}")); }
+      }
+      Ok(())
+    }
+    Some("integer") => {
+      let Some(n) = v.as_i64().map(|n| n as f64).or_else(|| v.as_u64().map(|n| n as f64)) else { return err("expected an integer".into()) };
+      bounds(schema, n, path)
+    }
+    Some("number") => {
+      let Some(n) = v.as_f64() else { return err("expected a number".into()) };
+      bounds(schema, n, path)
+    }
+    Some("boolean") if v.is_boolean() => Ok(()),
+    Some("boolean") => err("expected a boolean".into()),
+    Some("array") => {
+      let Some(items) = v.as_array() else { return err("expected an array".into()) };
+      items.iter().enumerate().try_for_each(|(i, item)| check(&schema["items"], item, &format!("{path}[{i}]")))
+    }
+    None => Ok(()),
+    Some(other) => err(format!("unsupported schema type {other}")),
+  }
+}
+
+fn bounds(schema: &Value, n: f64, path: &str) -> Result<(), LexiconError> {
+  if let Some(min) = schema["minimum"].as_f64().filter(|m| n < *m) { return Err(LexiconError::new(path, format!("below minimum {min}"))); }
+  if let Some(max) = schema["maximum"].as_f64().filter(|m| n > *m) { return Err(LexiconError::new(path, format!("above maximum {max}"))); }
+  Ok(())
+}
+
+fn join(path: &str, name: &str) -> String {
+  if path == "record" { name.to_string() } else { format!("{path}.{name}") }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn anchor() -> Value {
+    json!({ "tid": "t", "round": 1, "aliveRoot": "aa", "pairingSeed": "bb", "drandRound": 7, "merkleRoot": "cc", "postedAt": "2026-10-18T12:00:00Z" })
+  }
+
+  #[test]
+  fn every_lexicon_file_has_a_type() {
+    let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("../lexicon");
+    let files = std::fs::read_dir(dir).unwrap().filter(|e| e.as_ref().unwrap().path().extension().is_some_and(|x| x == "json")).count();
+    assert_eq!(files, SCHEMAS.len());
+    for nsid in [RoundAnchor::NSID, MatchDigest::NSID, Registration::NSID] { assert!(schema(nsid).is_some(), "{nsid}"); }
+  }
+
+  #[test]
+  fn generated_types_round_trip_records() {
+    let a = RoundAnchor::from_record(anchor()).unwrap();
+    assert_eq!((a.round, a.drand_round, a.alive_root.as_str()), (1, Some(7), "aa"));
+    let rec = a.to_record();
+    assert_eq!(rec["$type"], RoundAnchor::NSID);
+    assert_eq!(RoundAnchor::from_record(rec).unwrap(), a);
+  }
+
+  #[test]
+  fn rejects_off_schema_records() {
+    let off = |edit: fn(&mut Value)| { let mut v = anchor(); edit(&mut v); RoundAnchor::from_record(v).unwrap_err() };
+    assert_eq!(off(|v| { v.as_object_mut().unwrap().remove("tid"); }).path, "tid");
+    assert_eq!(off(|v| v["extra"] = json!(1)).path, "extra");
+    assert_eq!(off(|v| v["round"] = json!(-1)).path, "round");
+    assert_eq!(off(|v| v["round"] = json!("1")).path, "round");
+    assert_eq!(off(|v| v["postedAt"] = json!("yesterday")).path, "postedAt");
+    assert_eq!(off(|v| v["$type"] = json!(MatchDigest::NSID)).path, "$type");
+    let reg = json!({ "tid": "t", "did": "did:plc:A", "createdAt": "2026-10-18T12:00:00Z" });
+    assert_eq!(Registration::from_record(reg).unwrap_err().path, "did");
+    let digest = json!({ "tid": "t", "round": 1, "mid": "m", "p1": "a", "p2": "b", "winner": "a", "commitsRoot": "cc", "turns": 5, "aiTurns": [1, -2], "postedAt": "2026-10-18T12:00:00Z" });
+    assert_eq!(MatchDigest::from_record(digest).unwrap_err().path, "aiTurns[1]");
+  }
+}
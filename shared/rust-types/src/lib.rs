@@ -1,29 +1,52 @@
 use serde::{Deserialize, Serialize};
 
+pub mod internal;
+pub mod jetstream;
+pub mod lexicon;
+pub mod wire;
+
+/// Version of the match WebSocket protocol (the `ServerToClient` and
+/// `ClientToServer` messages) spoken by this build. Clients announce theirs in
+/// `HELLO`; one that never does is taken to speak version 1.
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Oldest client protocol version the server still accepts.
+pub const MIN_PROTOCOL_VERSION: u32 = 1;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct Assign {
   pub match_id: String,
   pub role: String,
   pub peer: Peer,
   pub rtc: RtcConfig,
+  // commit encoding for this match ("v1" or "v2"), taken from the ticket; absent means v1
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "codegen", ts(optional))]
+  pub commit_version: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct Peer { pub did: String, pub handle: String }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct RtcConfig { pub turns: Vec<String> }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct TurnStart {
   pub match_id: String,
   pub turn: u32,
+  #[cfg_attr(feature = "codegen", ts(type = "number"))]
   pub deadline_ms_epoch: i64,
   // Server current time in ms epoch when the event was created; clients can compute offset
+  #[cfg_attr(feature = "codegen", ts(type = "number"))]
   pub now_ms_epoch: i64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct TurnResult {
   pub match_id: String,
   pub turn: u32,
@@ -35,18 +58,63 @@ pub struct TurnResult {
   // optional: canonical moves by role
   pub p1_move: Option<String>,
   pub p2_move: Option<String>,
+  // VRF proof for each substituted move, one per entry in ai_for_dids
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub substitutions: Vec<Substitution>,
+}
+
+/// Proof that a move substituted on a missed deadline was drawn uniformly.
+/// Check with fairness `POST /verify` (passing `did`), or offline against `public_key` and `drand_randomness`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
+pub struct Substitution {
+  pub did: String,
+  pub move_: String,
+  pub vrf_output: String,
+  pub vrf_proof: String,
+  pub public_key: String,
+  // drand round bound into the VRF input; 0 when no beacon was available
+  #[cfg_attr(feature = "codegen", ts(type = "number"))]
+  pub drand_round: u64,
+  // that round's randomness (hex) as bound into the VRF input; all zeros for round 0
+  pub drand_randomness: String,
+  // "fairness" when the fairness service proved it, "local" for signaling's fallback key
+  pub source: String,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct MatchResult { pub match_id: String, pub winner: String }
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
+pub struct MatchResult {
+  pub match_id: String,
+  pub winner: String,
+  // positional Merkle root (hex) over the match's commits in turn order, P1 before P2
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  #[cfg_attr(feature = "codegen", ts(optional))]
+  pub commits_root: Option<String>,
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct OpponentLeft { pub match_id: String }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct ErrorMsg { pub code: String, pub msg: String }
 
+/// Reply to `HELLO`: the version the connection will speak and the optional
+/// features both sides support.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
+pub struct Welcome {
+  pub protocol_version: u32,
+  pub min_protocol_version: u32,
+  pub server_name: String,
+  #[serde(default)]
+  pub capabilities: Vec<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 #[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ServerToClient {
   Assign(Assign),
@@ -55,32 +123,81 @@ pub enum ServerToClient {
   MatchResult(MatchResult),
   OpponentLeft(OpponentLeft),
   Error(ErrorMsg),
+  Welcome(Welcome),
+}
+
+/// First message on a connection: the client's protocol version, a name for
+/// logs, and the optional features it understands.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
+pub struct Hello {
+  pub protocol_version: u32,
+  #[serde(default)]
+  #[cfg_attr(feature = "codegen", ts(as = "Option<String>", optional))]
+  pub client_name: String,
+  #[serde(default)]
+  #[cfg_attr(feature = "codegen", ts(as = "Option<Vec<String>>", optional))]
+  pub capabilities: Vec<String>,
+}
+
+impl Hello {
+  /// The server's answer: `WELCOME` with the shared capabilities, or an
+  /// `UNSUPPORTED_VERSION` error if the client's version is outside
+  /// `MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION`.
+  pub fn accept(&self, server_name: &str, capabilities: &[&str]) -> Result<Welcome, ErrorMsg> {
+    if !(MIN_PROTOCOL_VERSION..=PROTOCOL_VERSION).contains(&self.protocol_version) {
+      return Err(ErrorMsg {
+        code: "UNSUPPORTED_VERSION".into(),
+        msg: format!("protocol version {} is not supported; this server speaks {}..={}", self.protocol_version, MIN_PROTOCOL_VERSION, PROTOCOL_VERSION),
+      });
+    }
+    Ok(Welcome {
+      protocol_version: self.protocol_version,
+      min_protocol_version: MIN_PROTOCOL_VERSION,
+      server_name: server_name.to_string(),
+      capabilities: capabilities.iter().filter(|c| self.capabilities.iter().any(|h| h == *c)).map(|c| c.to_string()).collect(),
+    })
+  }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct ReadyForRound { pub tid: String, pub round: u32 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct Heartbeat {}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct SdpOffer { pub match_id: String, pub sdp: String }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct SdpAnswer { pub match_id: String, pub sdp: String }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct Ice { pub match_id: String, pub candidate: String }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct CommitHashes { pub match_id: String, pub hashes: [String; 32] }
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
+pub struct CommitHashes {
+  pub match_id: String,
+  #[cfg_attr(feature = "codegen", ts(type = "Array<string>"))]
+  pub hashes: [String; 32],
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 pub struct Reveal { pub match_id: String, pub turn: u32, pub move_: String, pub nonce: String }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "codegen", derive(ts_rs::TS, schemars::JsonSchema))]
 #[serde(tag = "type", rename_all = "SCREAMING_SNAKE_CASE")]
+#[allow(clippy::large_enum_variant)]
 pub enum ClientToServer {
+  Hello(Hello),
   ReadyForRound(ReadyForRound),
   Heartbeat(Heartbeat),
   SdpOffer(SdpOffer),
@@ -89,3 +206,27 @@ pub enum ClientToServer {
   CommitHashes(CommitHashes),
   Reveal(Reveal),
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hello_round_trips_on_the_wire() {
+    let msg: ClientToServer = serde_json::from_str(r#"{"type":"HELLO","protocol_version":1,"client_name":"web"}"#).unwrap();
+    let ClientToServer::Hello(hello) = msg else { panic!("not a HELLO") };
+    assert!(hello.capabilities.is_empty());
+    let welcome = ServerToClient::Welcome(hello.accept("signaling", &["transcripts"]).unwrap());
+    let wire = serde_json::to_value(&welcome).unwrap();
+    assert_eq!(wire, serde_json::json!({ "type": "WELCOME", "protocol_version": 1, "min_protocol_version": 1, "server_name": "signaling", "capabilities": [] }));
+  }
+
+  #[test]
+  fn accepts_supported_versions_and_shared_capabilities() {
+    let hello = |v: u32| Hello { protocol_version: v, client_name: "t".into(), capabilities: vec!["transcripts".into(), "future".into()] };
+    let w = hello(PROTOCOL_VERSION).accept("s", &["commit_v2", "transcripts"]).unwrap();
+    assert_eq!(w.capabilities, ["transcripts"]);
+    assert_eq!(hello(PROTOCOL_VERSION + 1).accept("s", &[]).unwrap_err().code, "UNSUPPORTED_VERSION");
+    assert_eq!(hello(MIN_PROTOCOL_VERSION - 1).accept("s", &[]).unwrap_err().code, "UNSUPPORTED_VERSION");
+  }
+}
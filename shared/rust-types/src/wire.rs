@@ -0,0 +1,120 @@
+//! Wire encodings for the match WebSocket.
+//!
+//! JSON text frames are the default. MessagePack and CBOR carry the same
+//! serde data model in binary frames (maps with the Rust field names, so the
+//! `type` tag works unchanged) and decode to the same `ClientToServer` /
+//! `ServerToClient` values. A connection picks one with the
+//! `Sec-WebSocket-Protocol` subprotocols `rps.json`, `rps.msgpack`, `rps.cbor`
+//! or the `encoding=json|msgpack|cbor` query parameter.
+
+use serde::{de::DeserializeOwned, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+  #[default]
+  Json,
+  MsgPack,
+  Cbor,
+}
+
+#[derive(Debug)]
+pub struct WireError(String);
+
+impl std::fmt::Display for WireError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { f.write_str(&self.0) }
+}
+
+impl std::error::Error for WireError {}
+
+fn err(e: impl std::fmt::Display) -> WireError { WireError(e.to_string()) }
+
+impl Encoding {
+  pub const ALL: [Encoding; 3] = [Encoding::Json, Encoding::MsgPack, Encoding::Cbor];
+
+  pub fn name(self) -> &'static str {
+    match self { Encoding::Json => "json", Encoding::MsgPack => "msgpack", Encoding::Cbor => "cbor" }
+  }
+
+  /// WebSocket subprotocol naming this encoding.
+  pub fn subprotocol(self) -> &'static str {
+    match self { Encoding::Json => "rps.json", Encoding::MsgPack => "rps.msgpack", Encoding::Cbor => "rps.cbor" }
+  }
+
+  /// Accepts a name (`msgpack`) or a subprotocol (`rps.msgpack`).
+  pub fn parse(s: &str) -> Option<Self> {
+    let s = s.trim();
+    Self::ALL.into_iter().find(|e| s.eq_ignore_ascii_case(e.name()) || s.eq_ignore_ascii_case(e.subprotocol()))
+  }
+
+  /// Binary frames rather than text.
+  pub fn is_binary(self) -> bool { self != Encoding::Json }
+
+  pub fn encode<T: Serialize>(self, msg: &T) -> Result<Vec<u8>, WireError> {
+    match self {
+      Encoding::Json => serde_json::to_vec(msg).map_err(err),
+      Encoding::MsgPack => rmp_serde::to_vec_named(msg).map_err(err),
+      Encoding::Cbor => {
+        let mut out = Vec::new();
+        ciborium::into_writer(msg, &mut out).map_err(err)?;
+        Ok(out)
+      }
+    }
+  }
+
+  pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, WireError> {
+    match self {
+      Encoding::Json => serde_json::from_slice(bytes).map_err(err),
+      Encoding::MsgPack => rmp_serde::from_slice(bytes).map_err(err),
+      Encoding::Cbor => ciborium::from_reader(bytes).map_err(err),
+    }
+  }
+
+  /// Re-encodes a message already serialized as JSON (e.g. one relayed between
+  /// sockets) in this encoding.
+  pub fn transcode_json(self, json: &str) -> Result<Vec<u8>, WireError> {
+    match self {
+      Encoding::Json => Ok(json.as_bytes().to_vec()),
+      _ => self.encode(&serde_json::from_str::<serde_json::Value>(json).map_err(err)?),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::*;
+
+  fn turn_result() -> ServerToClient {
+    ServerToClient::TurnResult(TurnResult {
+      match_id: "cup-r1-did_plc_alice-did_plc_bob".into(), turn: 3, result: "P1".into(), ai: Some(false),
+      ai_for_dids: Some(vec![]), p1_move: Some("R".into()), p2_move: Some("S".into()), substitutions: vec![],
+    })
+  }
+
+  #[test]
+  fn every_encoding_decodes_to_the_same_messages() {
+    let reveal = ClientToServer::Reveal(Reveal { match_id: "m".into(), turn: 2, move_: "P".into(), nonce: "ab".repeat(32) });
+    for enc in Encoding::ALL {
+      let back: ClientToServer = enc.decode(&enc.encode(&reveal).unwrap()).unwrap();
+      assert!(matches!(back, ClientToServer::Reveal(r) if r.turn == 2 && r.move_ == "P"), "{enc:?}");
+      let json = serde_json::to_string(&turn_result()).unwrap();
+      let back: ServerToClient = enc.decode(&enc.transcode_json(&json).unwrap()).unwrap();
+      assert_eq!(serde_json::to_string(&back).unwrap(), json, "{enc:?}");
+    }
+  }
+
+  #[test]
+  fn binary_encodings_are_smaller() {
+    let json = Encoding::Json.encode(&turn_result()).unwrap().len();
+    assert!(Encoding::MsgPack.encode(&turn_result()).unwrap().len() < json);
+    assert!(Encoding::Cbor.encode(&turn_result()).unwrap().len() < json);
+  }
+
+  #[test]
+  fn parses_names_and_subprotocols() {
+    assert_eq!(Encoding::parse("msgpack"), Some(Encoding::MsgPack));
+    assert_eq!(Encoding::parse(" rps.cbor"), Some(Encoding::Cbor));
+    assert_eq!(Encoding::parse("bson"), None);
+    assert!(Encoding::MsgPack.decode::<ClientToServer>(b"\xc1").is_err());
+  }
+}
@@ -1,13 +1,46 @@
+//! Browser bindings for the verification primitives in `rps-crypto`.
+//!
+//! Everything here is a thin wrapper: the hashing, Merkle and VRF code is the
+//! same code the services run natively. Hashes are returned as plain hex.
+
+use rps_crypto::{keccak, merkle, poseidon2, vrf};
 use wasm_bindgen::prelude::*;
 
+/// Keccak-256 (Ethereum's, not SHA3-256) of `input`.
+#[wasm_bindgen]
+pub fn keccak256(input: &[u8]) -> String { hex::encode(keccak::keccak256(input)) }
+
+/// Poseidon2 sponge over BN254 (see `rps_crypto::poseidon2`) of `input`.
 #[wasm_bindgen]
-pub fn keccak256(_input: &[u8]) -> String { "0x00".to_string() }
+pub fn poseidon2(input: &[u8]) -> String { hex::encode(poseidon2::hash_bytes(input)) }
 
+/// Positional Merkle root (hex) over hex-encoded leaves, e.g. a match's commits;
+/// matches `commits_root` in MATCH_RESULT. Malformed hex is an error.
 #[wasm_bindgen]
-pub fn poseidon2(_input: &[u8]) -> String { "0x00".to_string() }
+pub fn merkle_root(leaves: Vec<String>) -> Result<String, JsError> {
+    let leaves = leaves.iter().map(hex::decode).collect::<Result<Vec<_>, _>>()?;
+    Ok(hex::encode(merkle::root(merkle::Mode::Positional, &leaves)))
+}
 
+/// Checks a positional inclusion proof, e.g. from the coordinator's `/alive_proof`
+/// (where `leaf` is the DID's UTF-8 bytes). `root` and `siblings` are hex.
 #[wasm_bindgen]
-pub fn merkle_root(_leaves: JsValue) -> String { "0x00".to_string() }
+pub fn merkle_verify(root: &str, leaf: &[u8], index: usize, leaf_count: usize, siblings: Vec<String>) -> Result<bool, JsError> {
+    let root = hash_from_hex(root)?;
+    let siblings = siblings.iter().map(|s| hash_from_hex(s)).collect::<Result<Vec<_>, _>>()?;
+    let proof = merkle::Proof { index, leaf_count, siblings };
+    Ok(merkle::verify(merkle::Mode::Positional, &root, leaf, &proof))
+}
 
+/// Verifies an ECVRF-EDWARDS25519-SHA512-TAI proof for `alpha` under `public_key`
+/// and checks that it yields `output`. Any malformed input is simply invalid.
 #[wasm_bindgen]
-pub fn verify_vrf(_msg: &[u8], _output: &[u8], _proof: &[u8]) -> bool { true }
+pub fn verify_vrf(public_key: &[u8], alpha: &[u8], output: &[u8], proof: &[u8]) -> bool {
+    vrf::PublicKey::from_bytes(public_key)
+        .and_then(|pk| pk.verify(alpha, proof))
+        .is_ok_and(|beta| beta[..] == *output)
+}
+
+fn hash_from_hex(s: &str) -> Result<merkle::Hash, JsError> {
+    hex::decode(s)?.try_into().map_err(|_| JsError::new("expected a 32-byte hash"))
+}
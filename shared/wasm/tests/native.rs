@@ -0,0 +1,60 @@
+//! Checks the wasm exports against outputs of the native `rps-crypto` build
+//! (`native_vectors.json`). Runs under `wasm-pack test --node` and, natively,
+//! under plain `cargo test`.
+
+use rps_wasm::{keccak256, merkle_root, merkle_verify, poseidon2, verify_vrf};
+use serde_json::Value;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+fn vectors() -> Value {
+    serde_json::from_str(include_str!("native_vectors.json")).unwrap()
+}
+
+fn bytes(v: &Value) -> Vec<u8> {
+    hex::decode(v.as_str().unwrap()).unwrap()
+}
+
+fn strings(v: &Value) -> Vec<String> {
+    v.as_array().unwrap().iter().map(|s| s.as_str().unwrap().to_string()).collect()
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn hashes_match_native() {
+    let v = vectors();
+    for case in v["keccak256"].as_array().unwrap() {
+        assert_eq!(keccak256(&bytes(&case["input"])), case["digest"]);
+    }
+    for case in v["poseidon2"].as_array().unwrap() {
+        assert_eq!(poseidon2(&bytes(&case["input"])), case["digest"]);
+    }
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn merkle_matches_native() {
+    let v = &vectors()["merkle"];
+    let leaves = strings(&v["leaves"]);
+    let root = v["root"].as_str().unwrap();
+    assert_eq!(merkle_root(leaves.clone()).unwrap(), root);
+    for (i, p) in v["proofs"].as_array().unwrap().iter().enumerate() {
+        let (index, count) = (p["index"].as_u64().unwrap() as usize, p["leaf_count"].as_u64().unwrap() as usize);
+        let siblings = strings(&p["siblings"]);
+        let leaf = hex::decode(&leaves[i]).unwrap();
+        assert!(merkle_verify(root, &leaf, index, count, siblings.clone()).unwrap());
+        assert!(!merkle_verify(root, &leaf, (index + 1) % count, count, siblings).unwrap());
+    }
+}
+
+#[wasm_bindgen_test(unsupported = test)]
+fn vrf_matches_native() {
+    let v = &vectors()["vrf"];
+    let (pk, alpha, output, proof) = (bytes(&v["public_key"]), bytes(&v["alpha"]), bytes(&v["output"]), bytes(&v["proof"]));
+    assert!(verify_vrf(&pk, &alpha, &output, &proof));
+    assert!(!verify_vrf(&pk, b"another input", &output, &proof));
+    let mut wrong_output = output.clone();
+    wrong_output[0] ^= 1;
+    assert!(!verify_vrf(&pk, &alpha, &wrong_output, &proof));
+    let mut bad_proof = proof.clone();
+    bad_proof[40] ^= 1;
+    assert!(!verify_vrf(&pk, &alpha, &output, &bad_proof));
+    assert!(!verify_vrf(&pk[..31], &alpha, &output, &proof));
+}
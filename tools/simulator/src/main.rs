@@ -4,6 +4,7 @@ use reqwest::Client;
 use std::time::Duration;
 use tokio::{time::{sleep, timeout}};
 use tokio_tungstenite::{connect_async, tungstenite::Message};
+use rps_shared_types::wire::Encoding;
 
 #[derive(Clone)]
 struct SimConfig {
@@ -47,19 +48,78 @@ async fn queue_until_assigned(http: &Client, name: &str, did: &str, cfg: &SimCon
     Ok(AssignInfo { match_id, ticket, role: role.unwrap() })
 }
 
+/// Joins the queue once, then waits for ASSIGN on the coordinator's `/lobby`
+/// Server-Sent Events channel instead of polling.
+async fn lobby_until_assigned(http: &Client, name: &str, did: &str, cfg: &SimConfig) -> Result<AssignInfo> {
+    let join: serde_json::Value = http
+        .post(format!("{}/queue_ready", cfg.coord))
+        .json(&serde_json::json!({"tid": cfg.tid, "did": did, "handle": name}))
+        .send()
+        .await
+        .with_context(|| "queue_ready request failed")?
+        .json()
+        .await
+        .with_context(|| "queue_ready decode failed")?;
+    if join["status"] == "ASSIGN" { return assign_from_json(&join); }
+    let mut res = http
+        .get(format!("{}/lobby?tid={}&did={}", cfg.coord, urlencoding::encode(&cfg.tid), urlencoding::encode(did)))
+        .send()
+        .await
+        .with_context(|| "lobby subscribe failed")?;
+    let mut buf = String::new();
+    let read = async {
+        while let Some(chunk) = res.chunk().await.with_context(|| "lobby stream error")? {
+            buf.push_str(&String::from_utf8_lossy(&chunk));
+            // SSE frames are separated by a blank line; data lines carry the JSON event
+            while let Some(end) = buf.find("\n\n") {
+                let frame: String = buf.drain(..end + 2).collect();
+                for data in frame.lines().filter_map(|l| l.strip_prefix("data:")) {
+                    let Ok(j) = serde_json::from_str::<serde_json::Value>(data.trim()) else { continue };
+                    if j["type"] == "ASSIGN" { return assign_from_json(&j); }
+                }
+            }
+        }
+        Err(anyhow!("lobby stream closed"))
+    };
+    timeout(Duration::from_secs(20), read).await.map_err(|_| anyhow!("{} lobby timeout", name))?
+}
+
+/// Extracts match id, ticket and role from an ASSIGN payload.
+fn assign_from_json(j: &serde_json::Value) -> Result<AssignInfo> {
+    let role = match j["role"].as_str().unwrap_or("") { "P1" => Role::P1, "P2" => Role::P2, _ => return Err(anyhow!("missing role in ASSIGN")) };
+    Ok(AssignInfo {
+        match_id: j["match_id"].as_str().unwrap_or_default().to_string(),
+        ticket: j["ticket"].as_str().unwrap_or_default().to_string(),
+        role,
+    })
+}
+
 /// Drives a single simulated player: waits for TURN_START, sends REVEAL for
 /// each scripted turn, and validates TURN_RESULT against expected outcome.
 async fn run_player(name: &str, cfg: SimConfig, planned_moves: &[(&'static str, &'static str)], preassign: Option<AssignInfo>) -> Result<()> {
     let http = Client::new();
     let did = format!("did:plc:{}", name);
-    let AssignInfo { match_id, ticket, role } = if let Some(a) = preassign { a } else { queue_until_assigned(&http, name, &did, &cfg).await? };
+    let sim_push = std::env::var("SIM_PUSH").ok().map(|v| v == "1").unwrap_or(false);
+    let AssignInfo { match_id, ticket, role } = match preassign {
+        Some(a) => a,
+        None if sim_push => lobby_until_assigned(&http, name, &did, &cfg).await?,
+        None => queue_until_assigned(&http, name, &did, &cfg).await?,
+    };
     let verbose = std::env::var("SIM_VERBOSE").ok().map(|v| v == "1").unwrap_or(false);
     if verbose { println!("{} assigned: role={:?}", name, role); }
 
-    // connect ws
-    let url = format!("{}?ticket={}", cfg.signal_ws, urlencoding::encode(&ticket));
+    // connect ws; SIM_ENCODING=msgpack|cbor plays over binary frames
+    let encoding = std::env::var("SIM_ENCODING").ok().and_then(|e| Encoding::parse(&e)).unwrap_or_default();
+    let url = format!("{}?ticket={}&encoding={}", cfg.signal_ws, urlencoding::encode(&ticket), encoding.name());
     let (mut ws, _resp) = connect_async(&url).await.with_context(|| "ws connect failed")?;
     if verbose { println!("{} connected", name); }
+    let hello = serde_json::json!({ "type": "HELLO", "protocol_version": rps_shared_types::PROTOCOL_VERSION, "client_name": "rps-simulator" });
+    ws.send(to_frame(encoding, &hello)?).await.context("send HELLO failed")?;
+    let welcome = timeout(Duration::from_secs(5), ws.next()).await.context("WELCOME timeout")?
+        .ok_or_else(|| anyhow!("ws stream closed"))?.context("ws error frame")?;
+    let welcome = from_frame(encoding, &welcome).unwrap_or_default();
+    if welcome["type"] != "WELCOME" { return Err(anyhow!("{} expected WELCOME, got {}", name, welcome)); }
+    if verbose { println!("{} WELCOME protocol_version={}", name, welcome["protocol_version"]); }
 
     // Play deterministic turns from planned_moves. planned_moves is a list of (move_for_P1, move_for_P2)
     // Fast-fail timeouts: 12s per TURN_RESULT wait (covers assign/connect jitter)
@@ -70,13 +130,11 @@ async fn run_player(name: &str, cfg: SimConfig, planned_moves: &[(&'static str,
             let msg = timeout(per_phase, ws.next()).await.context("TURN_START timeout")?
                 .ok_or_else(|| anyhow!("ws stream closed"))?;
             let msg = msg.context("ws error frame")?;
-            if let Message::Text(txt) = msg {
-                if let Ok(j) = serde_json::from_str::<serde_json::Value>(&txt) {
-                    if j["type"] == "TURN_START" {
-                        let t = j["turn"].as_u64().unwrap_or(0) as u32;
-                        if verbose { println!("{} TURN_START turn={} (script idx {})", name, t, turn_idx+1); }
-                        break t;
-                    }
+            if let Some(j) = from_frame(encoding, &msg) {
+                if j["type"] == "TURN_START" {
+                    let t = j["turn"].as_u64().unwrap_or(0) as u32;
+                    if verbose { println!("{} TURN_START turn={} (script idx {})", name, t, turn_idx+1); }
+                    break t;
                 }
             }
         };
@@ -89,32 +147,30 @@ async fn run_player(name: &str, cfg: SimConfig, planned_moves: &[(&'static str,
             "turn": observed_turn,
             "move_": mv,
             "nonce": format!("n{}{}", name, observed_turn)
-        }).to_string();
-        ws.send(Message::Text(reveal)).await.context("send reveal failed")?;
+        });
+        ws.send(to_frame(encoding, &reveal)?).await.context("send reveal failed")?;
 
         // Wait for TURN_RESULT of this turn and validate winner when not DRAW
         loop {
             let msg = timeout(per_phase, ws.next()).await.context("TURN_RESULT timeout")?
                 .ok_or_else(|| anyhow!("ws stream closed"))?;
             let msg = msg.context("ws error frame")?;
-            if let Message::Text(txt) = msg {
-                if let Ok(j) = serde_json::from_str::<serde_json::Value>(&txt) {
-                    if j["type"] == "TURN_RESULT" {
-                        let t = j["turn"].as_u64().unwrap_or(0) as u32;
-                        if t != observed_turn { continue; }
-                        let result = j["result"].as_str().unwrap_or("");
-                        // Compute expected result
-                        let expected = expected_winner(*p1_mv, *p2_mv);
-                        if verbose { println!("{} TURN_RESULT turn={} got={} expected={:?}", name, t, result, expected); }
-                        match expected {
-                            Some(Role::P1) => if result != "P1" { return Err(anyhow!("expected P1 win on turn {} ({} vs {}), got {}", observed_turn, p1_mv, p2_mv, result)); },
-                            Some(Role::P2) => if result != "P2" { return Err(anyhow!("expected P2 win on turn {} ({} vs {}), got {}", observed_turn, p1_mv, p2_mv, result)); },
-                            None => if result != "DRAW" { return Err(anyhow!("expected DRAW on turn {} ({} vs {}), got {}", observed_turn, p1_mv, p2_mv, result)); },
-                        }
-                        break;
-                    } else if j["type"] == "OPPONENT_LEFT" {
-                        return Err(anyhow!("opponent left before resolution on turn {}", observed_turn));
+            if let Some(j) = from_frame(encoding, &msg) {
+                if j["type"] == "TURN_RESULT" {
+                    let t = j["turn"].as_u64().unwrap_or(0) as u32;
+                    if t != observed_turn { continue; }
+                    let result = j["result"].as_str().unwrap_or("");
+                    // Compute expected result
+                    let expected = expected_winner(p1_mv, p2_mv);
+                    if verbose { println!("{} TURN_RESULT turn={} got={} expected={:?}", name, t, result, expected); }
+                    match expected {
+                        Some(Role::P1) => if result != "P1" { return Err(anyhow!("expected P1 win on turn {} ({} vs {}), got {}", observed_turn, p1_mv, p2_mv, result)); },
+                        Some(Role::P2) => if result != "P2" { return Err(anyhow!("expected P2 win on turn {} ({} vs {}), got {}", observed_turn, p1_mv, p2_mv, result)); },
+                        None => if result != "DRAW" { return Err(anyhow!("expected DRAW on turn {} ({} vs {}), got {}", observed_turn, p1_mv, p2_mv, result)); },
                     }
+                    break;
+                } else if j["type"] == "OPPONENT_LEFT" {
+                    return Err(anyhow!("opponent left before resolution on turn {}", observed_turn));
                 }
             }
         }
@@ -126,6 +182,21 @@ async fn run_player(name: &str, cfg: SimConfig, planned_moves: &[(&'static str,
 }
 
 /// Computes the winner role for canonical RPS rules. None => DRAW.
+/// A JSON message as a frame of the connection's encoding.
+fn to_frame(encoding: Encoding, msg: &serde_json::Value) -> Result<Message> {
+    if !encoding.is_binary() { return Ok(Message::Text(msg.to_string())) }
+    Ok(Message::Binary(encoding.encode(msg)?))
+}
+
+/// Decodes a text or binary frame; `None` for control frames and garbage.
+fn from_frame(encoding: Encoding, msg: &Message) -> Option<serde_json::Value> {
+    match msg {
+        Message::Text(txt) => serde_json::from_str(txt).ok(),
+        Message::Binary(bytes) => encoding.decode(bytes).ok(),
+        _ => None,
+    }
+}
+
 fn expected_winner(p1: &str, p2: &str) -> Option<Role> {
     // Returns Some(winner_role) or None for draw
     match (p1, p2) {
@@ -0,0 +1,82 @@
+//! Repo tasks: `cargo xtask codegen [--check]`.
+//!
+//! `codegen` regenerates `shared/proto/messages.ts` and the JSON Schemas in
+//! `shared/proto/schema/` from the protocol types in `rps-shared-types`.
+//! With `--check` it writes nothing and fails if the committed files are stale.
+
+use rps_shared_types::*;
+use std::path::{Path, PathBuf};
+use ts_rs::TS;
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.iter().map(String::as_str).collect::<Vec<_>>().as_slice() {
+        ["codegen"] => {
+            for (path, text) in outputs() {
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(&path, text).unwrap_or_else(|e| panic!("{}: {e}", path.display()));
+                println!("wrote {}", path.display());
+            }
+        }
+        ["codegen", "--check"] => {
+            let stale = stale();
+            for path in &stale { eprintln!("stale: {}", path.display()); }
+            if !stale.is_empty() {
+                eprintln!("run `cargo xtask codegen` and commit the result");
+                std::process::exit(1);
+            }
+        }
+        _ => {
+            eprintln!("usage: cargo xtask codegen [--check]");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn proto_dir() -> PathBuf { Path::new(env!("CARGO_MANIFEST_DIR")).ancestors().nth(2).unwrap().join("shared/proto") }
+
+/// Every generated file and its contents.
+fn outputs() -> Vec<(PathBuf, String)> {
+    let dir = proto_dir();
+    vec![
+        (dir.join("messages.ts"), typescript()),
+        (dir.join("schema/ClientToServer.json"), json_schema(schemars::schema_for!(ClientToServer))),
+        (dir.join("schema/ServerToClient.json"), json_schema(schemars::schema_for!(ServerToClient))),
+    ]
+}
+
+/// Generated files whose committed contents differ.
+fn stale() -> Vec<PathBuf> {
+    outputs().into_iter()
+        .filter(|(path, text)| std::fs::read_to_string(path).ok().as_deref() != Some(text.as_str()))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+fn typescript() -> String {
+    let decls = [
+        Hello::decl(), ReadyForRound::decl(), Heartbeat::decl(), SdpOffer::decl(), SdpAnswer::decl(),
+        Ice::decl(), CommitHashes::decl(), Reveal::decl(), ClientToServer::decl(),
+        Assign::decl(), Peer::decl(), RtcConfig::decl(), TurnStart::decl(), TurnResult::decl(),
+        Substitution::decl(), MatchResult::decl(), OpponentLeft::decl(), ErrorMsg::decl(), Welcome::decl(),
+        ServerToClient::decl(),
+    ];
+    let mut out = String::from("// Generated by `cargo xtask codegen` from rps-shared-types. Do not edit.\n\n");
+    out.push_str(&format!("export const PROTOCOL_VERSION = {PROTOCOL_VERSION};\n"));
+    out.push_str(&format!("export const MIN_PROTOCOL_VERSION = {MIN_PROTOCOL_VERSION};\n"));
+    for decl in decls { out.push_str(&format!("\nexport {decl}\n")); }
+    out
+}
+
+fn json_schema(schema: schemars::Schema) -> String {
+    serde_json::to_string_pretty(&schema).unwrap() + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn committed_protocol_files_are_fresh() {
+        let stale = super::stale();
+        assert!(stale.is_empty(), "stale generated files {stale:?}; run `cargo xtask codegen`");
+    }
+}